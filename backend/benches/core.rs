@@ -0,0 +1,104 @@
+// Throughput of the interpreter core on synthetic programs
+//
+//     cargo bench
+//
+// Each program is a loop that never exits or faults, run from a fixed
+// seed, so results are comparable across commits. Criterion keeps the
+// previous run in target/criterion and reports the change.
+
+use std::hint::black_box;
+
+use backend::display_constants::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use backend::Processor;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+const TICKS: usize = 1000;
+const TICKS_PER_FRAME: usize = 10;
+const FRAMES: usize = 100;
+
+// Register arithmetic only
+const ALU: [u8; 24] = [
+    0x60, 0x17, // LD V0, 0x17
+    0x61, 0x29, // LD V1, 0x29
+    0x70, 0x03, // ADD V0, 0x03
+    0x80, 0x14, // ADD V0, V1
+    0x81, 0x05, // SUB V1, V0
+    0x82, 0x01, // OR V2, V0
+    0x82, 0x12, // AND V2, V1
+    0x83, 0x23, // XOR V3, V2
+    0x83, 0x06, // SHR V3
+    0x84, 0x0E, // SHL V4
+    0x85, 0x47, // SUBN V5, V4
+    0x12, 0x04, // JP 0x204
+];
+
+// Font sprites drawn all over the screen
+const DRAW: [u8; 16] = [
+    0x60, 0x00, // LD V0, 0x00
+    0x61, 0x00, // LD V1, 0x00
+    0xF2, 0x29, // LD F, V2
+    0xD0, 0x15, // DRW V0, V1, 5
+    0x70, 0x05, // ADD V0, 0x05
+    0x71, 0x03, // ADD V1, 0x03
+    0x72, 0x01, // ADD V2, 0x01
+    0x12, 0x04, // JP 0x204
+];
+
+// Block stores and loads plus BCD conversion
+const MEMORY: [u8; 14] = [
+    0xA3, 0x00, // LD I, 0x300
+    0x70, 0x07, // ADD V0, 0x07
+    0xF0, 0x33, // LD B, V0
+    0xFF, 0x55, // LD [I], VF
+    0xFF, 0x65, // LD VF, [I]
+    0xF0, 0x1E, // ADD I, V0
+    0x12, 0x00, // JP 0x200
+];
+
+fn processor(rom: &[u8]) -> Processor {
+    let mut processor = Processor::with_seed(0);
+    processor.load(rom);
+    processor
+}
+
+fn tick(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tick");
+    group.throughput(Throughput::Elements(TICKS as u64));
+    for (name, rom) in [("alu", &ALU[..]), ("draw", &DRAW[..]), ("memory", &MEMORY[..])] {
+        let mut processor = processor(rom);
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                for _ in 0..TICKS {
+                    processor.tick();
+                }
+                black_box(processor.registers())
+            })
+        });
+    }
+    group.finish();
+}
+
+// What a frontend does every frame: run the frame's instructions, count
+// the timers down and copy the screen out
+fn frame(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frame");
+    group.throughput(Throughput::Elements(FRAMES as u64));
+    let mut processor = processor(&DRAW);
+    let mut screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+    group.bench_function("draw", |b| {
+        b.iter(|| {
+            for _ in 0..FRAMES {
+                for _ in 0..TICKS_PER_FRAME {
+                    processor.tick();
+                }
+                processor.tick_timers();
+                screen.copy_from_slice(processor.get_display());
+                black_box(&screen);
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, tick, frame);
+criterion_main!(benches);
@@ -0,0 +1,55 @@
+// Measures throughput in instructions per second when interpreting, with
+// the decode cache and with the recompiler:
+//
+//     cargo run --release --features std --example ips
+//
+// The program is a tight loop of ALU, skip, BCD and load instructions, so
+// the numbers mostly reflect fetch, decode and dispatch overhead.
+
+use std::time::Instant;
+
+use backend::Processor;
+
+const INSTRUCTIONS: usize = 50_000_000;
+
+const ROM: [u8; 22] = [
+    0x60, 0x01, // LD V0, 0x01
+    0x71, 0x01, // ADD V1, 0x01
+    0x80, 0x14, // ADD V0, V1
+    0x82, 0x15, // SUB V2, V1
+    0x33, 0x00, // SE V3, 0x00
+    0x00, 0x00, // NOP
+    0xA3, 0x00, // LD I, 0x300
+    0xF2, 0x33, // LD B, V2
+    0xF2, 0x65, // LD V2, [I]
+    0x83, 0x06, // SHR V3
+    0x12, 0x02, // JP 0x202
+];
+
+fn measure(processor: &mut Processor) -> f64 {
+    processor.load(&ROM);
+    let start = Instant::now();
+    processor.run(INSTRUCTIONS).unwrap();
+    INSTRUCTIONS as f64 / start.elapsed().as_secs_f64()
+}
+
+fn main() {
+    let uncached = measure(&mut Processor::with_seed(0));
+    println!("uncached {:>8.1} M instructions/s", uncached / 1e6);
+
+    let mut processor = Processor::with_seed(0);
+    processor.set_decode_cache(true);
+    let cached = measure(&mut processor);
+    println!("cached   {:>8.1} M instructions/s", cached / 1e6);
+
+    let mut processor = Processor::with_seed(0);
+    processor.set_recompiler(true);
+    let recompiled = measure(&mut processor);
+    println!("recompiled {:>6.1} M instructions/s", recompiled / 1e6);
+
+    println!(
+        "speedup  {:>8.2}x cached, {:.2}x recompiled",
+        cached / uncached,
+        recompiled / uncached
+    );
+}
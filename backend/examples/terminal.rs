@@ -0,0 +1,209 @@
+// Plays a ROM in the terminal, two screen rows to a line of half blocks:
+//
+//     cargo run --features std --example terminal path/to/game
+//
+// Keys are laid out as in the desktop frontend (1234, QWER, ASDF, ZXCV)
+// and Esc quits. Terminals only report presses, so a key stays held for a
+// few frames after each one and key repeat keeps it down. The bell stands
+// in for the beeper. Raw mode is set with stty, so it needs a Unix
+// terminal.
+
+use std::env;
+use std::fs;
+use std::io::{self, Read, Stdout, Write};
+use std::process::{self, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use backend::display_constants::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use backend::input_constants::NUM_KEYS;
+use backend::machine::{AudioSink, Control, InputSource, Machine, VideoSink};
+use backend::Processor;
+
+// about as long as the gap before key repeat starts
+const HOLD_FRAMES: u8 = 30;
+const ESCAPE: u8 = 0x1B;
+const CTRL_C: u8 = 0x03;
+
+struct TerminalVideo {
+    out: Stdout,
+    last: Vec<bool>,
+}
+
+impl VideoSink for TerminalVideo {
+    fn present(&mut self, display: &[bool]) {
+        if display == self.last.as_slice() {
+            return;
+        }
+        self.last = display.to_vec();
+
+        let mut frame = String::from("\x1b[H");
+        for y in (0..SCREEN_HEIGHT).step_by(2) {
+            for x in 0..SCREEN_WIDTH {
+                let top = display[x + SCREEN_WIDTH * y];
+                let bottom = display[x + SCREEN_WIDTH * (y + 1)];
+                frame.push(match (top, bottom) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            // raw mode doesn't return the carriage
+            frame.push_str("\r\n");
+        }
+        let _ = self.out.write_all(frame.as_bytes());
+        let _ = self.out.flush();
+    }
+}
+
+struct Bell;
+
+impl AudioSink for Bell {
+    fn set_beeping(&mut self, beeping: bool) {
+        if beeping {
+            print!("\x07");
+            let _ = io::stdout().flush();
+        }
+    }
+}
+
+struct TerminalInput {
+    bytes: Receiver<u8>,
+    held: [u8; NUM_KEYS], // frames left for each key
+}
+
+impl InputSource for TerminalInput {
+    fn poll(&mut self, keys: &mut [bool; NUM_KEYS]) -> Control {
+        for frames in self.held.iter_mut() {
+            *frames = frames.saturating_sub(1);
+        }
+        loop {
+            match self.bytes.try_recv() {
+                Ok(ESCAPE) | Ok(CTRL_C) | Err(TryRecvError::Disconnected) => {
+                    return Control::Quit;
+                }
+                Ok(byte) => {
+                    if let Some(key) = key_for(byte) {
+                        self.held[key] = HOLD_FRAMES;
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+        for (key, frames) in keys.iter_mut().zip(self.held) {
+            *key = frames > 0;
+        }
+        Control::Continue
+    }
+}
+
+fn key_for(byte: u8) -> Option<usize> {
+    let key = match byte.to_ascii_lowercase() {
+        b'1' => 0x1,
+        b'2' => 0x2,
+        b'3' => 0x3,
+        b'4' => 0xC,
+        b'q' => 0x4,
+        b'w' => 0x5,
+        b'e' => 0x6,
+        b'r' => 0xD,
+        b'a' => 0x7,
+        b's' => 0x8,
+        b'd' => 0x9,
+        b'f' => 0xE,
+        b'z' => 0xA,
+        b'x' => 0x0,
+        b'c' => 0xB,
+        b'v' => 0xF,
+        _ => return None,
+    };
+    Some(key)
+}
+
+// Unbuffered input without echo, undone on drop
+struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        let output = Command::new("stty")
+            .arg("-g")
+            .stdin(Stdio::inherit())
+            .output()?;
+        if !output.status.success() {
+            return Err(io::Error::other("stdin isn't a terminal"));
+        }
+        let saved = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Command::new("stty")
+            .args(["raw", "-echo"])
+            .stdin(Stdio::inherit())
+            .status()?;
+        // hide the cursor and clear the screen
+        print!("\x1b[?25l\x1b[2J");
+        Ok(Self { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\r\n");
+        let _ = io::stdout().flush();
+        let _ = Command::new("stty")
+            .arg(&self.saved)
+            .stdin(Stdio::inherit())
+            .status();
+    }
+}
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            println!("usage: terminal path/to/game");
+            process::exit(1);
+        }
+    };
+    let rom = fs::read(path).expect("Unable to open file");
+    let mut processor = Processor::new();
+    if let Err(error) = processor.try_load(&rom) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+
+    let raw_mode = match RawMode::enable() {
+        Ok(raw_mode) => raw_mode,
+        Err(error) => {
+            eprintln!("Unable to set up the terminal: {}", error);
+            process::exit(1);
+        }
+    };
+
+    // stdin blocks, so it's read on its own thread
+    let (sender, bytes) = mpsc::channel();
+    thread::spawn(move || {
+        for byte in io::stdin().lock().bytes() {
+            match byte {
+                Ok(byte) if sender.send(byte).is_ok() => {}
+                _ => break,
+            }
+        }
+    });
+
+    let video = TerminalVideo {
+        out: io::stdout(),
+        last: Vec::new(),
+    };
+    let input = TerminalInput {
+        bytes,
+        held: [0; NUM_KEYS],
+    };
+    let result = Machine::new(processor, video, Bell, input).run();
+
+    drop(raw_mode);
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,80 @@
+// Shared by the fuzz target and tests/properties.rs, which also replays
+// everything in fuzz/corpus/interpreter
+//
+// An input is one byte with a number of frames, that many big endian
+// 16-bit masks of the keys held during each frame, and then the ROM.
+// Missing masks count as no keys held, and ROM bytes that don't fit in
+// memory are dropped. The ROM runs on the interpreter, the decode cache
+// and the recompiler side by side. Faults are fine, panics and broken
+// invariants aren't.
+
+use backend::memory_constants::{RAM_SIZE, START_ADDRESS};
+use backend::{ErrorKind, Processor};
+
+const STACK_SIZE: usize = 16;
+const TICKS_PER_FRAME: usize = 10;
+
+pub fn run(data: &[u8]) {
+    let Some((&frames, rest)) = data.split_first() else {
+        return;
+    };
+    let masks = (frames as usize * 2).min(rest.len());
+    let (masks, rom) = rest.split_at(masks);
+    let rom = &rom[..rom.len().min(RAM_SIZE - START_ADDRESS as usize)];
+
+    let mut interpreted = Processor::with_seed(0);
+    let mut cached = Processor::with_seed(0);
+    let mut recompiled = Processor::with_seed(0);
+    cached.set_decode_cache(true);
+    recompiled.set_recompiler(true);
+    for processor in [&mut interpreted, &mut cached, &mut recompiled] {
+        processor.load(rom);
+    }
+
+    for frame in 0..frames as usize {
+        let mask = masks
+            .get(frame * 2..frame * 2 + 2)
+            .map_or(0, |bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+        for processor in [&mut interpreted, &mut cached, &mut recompiled] {
+            for key in 0..16 {
+                processor.keypress(key, mask & (1 << key) != 0);
+            }
+        }
+
+        let mut expected = Ok(());
+        for _ in 0..TICKS_PER_FRAME {
+            expected = interpreted.try_tick();
+            assert_eq!(expected, cached.try_tick());
+            check(&interpreted);
+            if expected.is_err() {
+                break;
+            }
+        }
+        assert_eq!(recompiled.run(TICKS_PER_FRAME), expected);
+        check(&recompiled);
+
+        assert_eq!(interpreted.registers(), cached.registers());
+        assert_eq!(interpreted.registers(), recompiled.registers());
+        assert_eq!(interpreted.ram(), recompiled.ram());
+        assert_eq!(interpreted.get_display(), recompiled.get_display());
+        if expected.is_err() {
+            return;
+        }
+        for processor in [&mut interpreted, &mut cached, &mut recompiled] {
+            processor.tick_timers();
+        }
+    }
+}
+
+fn check(processor: &Processor) {
+    let registers = processor.registers();
+    assert!(registers.sp as usize <= STACK_SIZE);
+    assert_eq!(processor.stack().len(), registers.sp as usize);
+    assert_eq!(processor.ram().len(), RAM_SIZE);
+    // BNNN and skips near the end can leave memory, but then the next
+    // fetch has to fault rather than read past the end
+    if registers.pc as usize >= RAM_SIZE - 1 {
+        let mut next = processor.clone();
+        assert_eq!(next.try_tick().unwrap_err().kind, ErrorKind::PcOutOfBounds);
+    }
+}
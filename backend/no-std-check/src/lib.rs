@@ -0,0 +1,35 @@
+// Nothing here may use std or alloc, so if the backend pulled either in
+// this crate would stop building for bare-metal targets
+
+#![no_std]
+
+use backend::display_constants::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use backend::{ExecutionError, Processor};
+
+// Runs `frames` frames of `rom` with a fixed seed and returns how many
+// pixels are lit, the way firmware would drive the processor
+pub fn run(rom: &[u8], frames: usize) -> Result<usize, ExecutionError> {
+    let mut processor = Processor::with_seed(0);
+    processor.load(rom);
+
+    for _ in 0..frames {
+        for _ in 0..10 {
+            processor.try_tick()?;
+        }
+        processor.tick_timers();
+    }
+
+    let lit = processor.get_display().iter().filter(|pixel| **pixel).count();
+    debug_assert!(lit <= SCREEN_WIDTH * SCREEN_HEIGHT);
+    Ok(lit)
+}
+
+// Save states are plain arrays, so they work without an allocator too
+pub fn round_trip_state(rom: &[u8]) -> bool {
+    let mut processor = Processor::with_seed(0);
+    processor.load(rom);
+    let state = processor.save_state();
+
+    let mut restored = Processor::with_seed(1);
+    restored.load_state(&state).is_ok() && restored.save_state() == state
+}
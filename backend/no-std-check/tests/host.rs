@@ -0,0 +1,17 @@
+// Runs the no_std build of the backend on the host
+
+use backend::ErrorKind;
+
+#[test]
+fn runs_without_std() {
+    // draw the font sprite for 8, then loop forever
+    let rom = [0x60, 0x08, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06];
+    assert_eq!(no_std_check::run(&rom, 2), Ok(16));
+    assert!(no_std_check::round_trip_state(&rom));
+}
+
+#[test]
+fn errors_without_std() {
+    let error = no_std_check::run(&[0x00, 0xEE], 1).unwrap_err();
+    assert_eq!(error.kind, ErrorKind::StackUnderflow);
+}
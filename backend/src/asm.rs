@@ -0,0 +1,245 @@
+// Assembler for the disassembler's syntax, so its output assembles back
+// to the same bytes
+//
+//     ; comments run to the end of the line
+//     start:  LD V0, 0x05      ; labels end in a colon
+//             CALL draw
+//             JP start
+//     sprite: DB 0xF0, 0x90    ; raw bytes
+//             DW 0x1234        ; raw big endian words
+//
+// Mnemonics, registers and labels are case insensitive. Numbers are
+// decimal or 0x hex, and a label can stand in for any address. `assemble`
+// places code from 0x200 and `assemble_at` from another origin, such as
+// 0x300 for CHIP-8X or 0x600 for ETI 660 programs. `assemble_with` also
+// predefines labels, for addresses the program shares with its host.
+// CHIP-8X's BGCOL (02A0) and COL (BXYN) from disassemble_chip8x are
+// accepted too.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::constants::START_ADDRESS;
+use crate::number::parse_number;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand<'a> {
+    V(u16),
+    I,
+    IndirectI, // [I]
+    Dt,
+    St,
+    K,
+    F,
+    B,
+    Value(&'a str), // number or label
+}
+
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    assemble_at(source, START_ADDRESS)
+}
+
+// For code that will be loaded at `origin`, labels count from there
+pub fn assemble_at(source: &str, origin: u16) -> Result<Vec<u8>, AsmError> {
+    assemble_with(source, origin, &[])
+}
+
+// `symbols` are labels defined before the source, which can't define them
+// again
+pub fn assemble_with(
+    source: &str,
+    origin: u16,
+    symbols: &[(&str, usize)],
+) -> Result<Vec<u8>, AsmError> {
+    // first pass places the labels, second encodes
+    let mut labels: HashMap<_, _> = symbols
+        .iter()
+        .map(|(name, value)| (name.to_ascii_lowercase(), *value))
+        .collect();
+    let mut address = origin as usize;
+    for (i, line) in source.lines().enumerate() {
+        let (label, statement) = split_line(line);
+        if let Some(label) = label {
+            if labels.insert(label.to_ascii_lowercase(), address).is_some() {
+                return Err(AsmError {
+                    line: i + 1,
+                    reason: "duplicate label",
+                });
+            }
+        }
+        if let Some((mnemonic, operands)) = statement {
+            address += match mnemonic.to_ascii_uppercase().as_str() {
+                "DB" => operands.len(),
+                "DW" => operands.len() * 2,
+                _ => 2,
+            };
+        }
+    }
+
+    let mut rom = Vec::new();
+    for (i, text) in source.lines().enumerate() {
+        let line = i + 1;
+        let Some((mnemonic, operands)) = split_line(text).1 else {
+            continue;
+        };
+        let operands: Vec<_> = operands.iter().map(|text| operand(text)).collect();
+        let value = |operand: &Operand, max: usize| -> Result<u16, AsmError> {
+            let Operand::Value(text) = operand else {
+                return Err(AsmError {
+                    line,
+                    reason: "expected a number or label",
+                });
+            };
+            let value = parse_number(text)
+                .or_else(|| labels.get(&text.to_ascii_lowercase()).copied())
+                .ok_or(AsmError {
+                    line,
+                    reason: "unknown label",
+                })?;
+            if value > max {
+                return Err(AsmError {
+                    line,
+                    reason: "value out of range",
+                });
+            }
+            Ok(value as u16)
+        };
+
+        match mnemonic.to_ascii_uppercase().as_str() {
+            "DB" => {
+                for operand in &operands {
+                    rom.push(value(operand, 0xFF)? as u8);
+                }
+            }
+            "DW" => {
+                for operand in &operands {
+                    rom.extend_from_slice(&value(operand, 0xFFFF)?.to_be_bytes());
+                }
+            }
+            mnemonic => {
+                let opcode = encode(mnemonic, &operands, &value).ok_or(AsmError {
+                    line,
+                    reason: "invalid instruction",
+                })??;
+                rom.extend_from_slice(&opcode.to_be_bytes());
+            }
+        }
+    }
+    Ok(rom)
+}
+
+// None if the mnemonic and operands don't make an instruction
+fn encode<F>(mnemonic: &str, operands: &[Operand], value: &F) -> Option<Result<u16, AsmError>>
+where
+    F: Fn(&Operand, usize) -> Result<u16, AsmError>,
+{
+    use Operand::*;
+
+    let xy = |base: u16, x: u16, y: u16| Some(Ok(base | x << 8 | y << 4));
+    let with = |base: u16, x: u16, operand: &Operand, max: usize| {
+        Some(value(operand, max).map(|value| base | x << 8 | value))
+    };
+
+    match (mnemonic, operands) {
+        ("NOP", []) => Some(Ok(0x0000)),
+        ("CLS", []) => Some(Ok(0x00E0)),
+        ("BGCOL", []) => Some(Ok(0x02A0)),
+        ("RET", []) => Some(Ok(0x00EE)),
+        ("SYS", [addr]) => with(0x0000, 0, addr, 0xFFF),
+        ("JP", [V(0), addr]) => with(0xB000, 0, addr, 0xFFF),
+        ("JP", [addr]) => with(0x1000, 0, addr, 0xFFF),
+        ("CALL", [addr]) => with(0x2000, 0, addr, 0xFFF),
+        ("SE", [V(x), V(y)]) => xy(0x5000, *x, *y),
+        ("SE", [V(x), byte]) => with(0x3000, *x, byte, 0xFF),
+        ("ADDN", [V(x), V(y)]) => xy(0x5001, *x, *y),
+        ("SNE", [V(x), V(y)]) => xy(0x9000, *x, *y),
+        ("SNE", [V(x), byte]) => with(0x4000, *x, byte, 0xFF),
+        ("LD", [V(x), V(y)]) => xy(0x8000, *x, *y),
+        ("LD", [V(x), Dt]) => xy(0xF007, *x, 0),
+        ("LD", [V(x), K]) => xy(0xF00A, *x, 0),
+        ("LD", [V(x), IndirectI]) => xy(0xF065, *x, 0),
+        ("LD", [V(x), byte]) => with(0x6000, *x, byte, 0xFF),
+        ("LD", [I, addr]) => with(0xA000, 0, addr, 0xFFF),
+        ("LD", [Dt, V(x)]) => xy(0xF015, *x, 0),
+        ("LD", [St, V(x)]) => xy(0xF018, *x, 0),
+        ("LD", [F, V(x)]) => xy(0xF029, *x, 0),
+        ("LD", [B, V(x)]) => xy(0xF033, *x, 0),
+        ("LD", [IndirectI, V(x)]) => xy(0xF055, *x, 0),
+        ("ADD", [V(x), V(y)]) => xy(0x8004, *x, *y),
+        ("ADD", [V(x), byte]) => with(0x7000, *x, byte, 0xFF),
+        ("ADD", [I, V(x)]) => xy(0xF01E, *x, 0),
+        ("OR", [V(x), V(y)]) => xy(0x8001, *x, *y),
+        ("AND", [V(x), V(y)]) => xy(0x8002, *x, *y),
+        ("XOR", [V(x), V(y)]) => xy(0x8003, *x, *y),
+        ("SUB", [V(x), V(y)]) => xy(0x8005, *x, *y),
+        ("SHR", [V(x)]) => xy(0x8006, *x, 0),
+        ("SHR", [V(x), V(y)]) => xy(0x8006, *x, *y),
+        ("SUBN", [V(x), V(y)]) => xy(0x8007, *x, *y),
+        ("SHL", [V(x)]) => xy(0x800E, *x, 0),
+        ("SHL", [V(x), V(y)]) => xy(0x800E, *x, *y),
+        ("RND", [V(x), byte]) => with(0xC000, *x, byte, 0xFF),
+        ("DRW", [V(x), V(y), n]) => with(0xD000 | *y << 4, *x, n, 0xF),
+        ("SKP", [V(x)]) => xy(0xE09E, *x, 0),
+        ("SKNP", [V(x)]) => xy(0xE0A1, *x, 0),
+        ("SKP2", [V(x)]) => xy(0xE0F2, *x, 0),
+        ("SKNP2", [V(x)]) => xy(0xE0F5, *x, 0),
+        ("COL", [V(x), V(y), n]) => with(0xB000 | *y << 4, *x, n, 0xF),
+        _ => None,
+    }
+}
+
+// Label if any, then the mnemonic and its operands if there's a statement
+fn split_line(line: &str) -> (Option<&str>, Option<(&str, Vec<&str>)>) {
+    let mut text = line.split(';').next().unwrap_or("").trim();
+    let mut label = None;
+    if let Some((name, rest)) = text.split_once(':') {
+        label = Some(name.trim());
+        text = rest.trim();
+    }
+    if text.is_empty() {
+        return (label, None);
+    }
+
+    let (mnemonic, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let operands = if rest.trim().is_empty() {
+        Vec::new()
+    } else {
+        rest.split(',').map(str::trim).collect()
+    };
+    (label, Some((mnemonic, operands)))
+}
+
+fn operand(text: &str) -> Operand<'_> {
+    let upper = text.to_ascii_uppercase();
+    match upper.as_str() {
+        "I" => return Operand::I,
+        "[I]" => return Operand::IndirectI,
+        "DT" => return Operand::Dt,
+        "ST" => return Operand::St,
+        "K" => return Operand::K,
+        "F" => return Operand::F,
+        "B" => return Operand::B,
+        _ => {}
+    }
+    match upper.strip_prefix('V') {
+        Some(digit) if digit.len() == 1 => match u16::from_str_radix(digit, 16) {
+            Ok(x) => Operand::V(x),
+            Err(_) => Operand::Value(text),
+        },
+        _ => Operand::Value(text),
+    }
+}
@@ -0,0 +1,128 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::process;
+
+use backend::tracediff::{compare_traces, run_lockstep, KeyScript, Schedule};
+use backend::Processor;
+
+const CONTEXT: usize = 8;
+const MAX_STEPS: usize = 10_000_000;
+const SEED: u64 = 0;
+
+// How one side of a lockstep run is set up
+#[derive(Default)]
+struct Side {
+    start: Option<u16>,
+    cdp1802: bool,
+    chip8x: bool,
+    recompiler: bool,
+}
+
+impl Side {
+    fn processor(&self, path: &str) -> Processor {
+        let mut processor = Processor::with_seed(SEED);
+        processor.set_cdp1802(self.cdp1802);
+        // CHIP-8X moves the start to 0x300, --start still wins
+        processor.set_chip8x(self.chip8x);
+        if let Some(address) = self.start {
+            processor.set_start_address(address);
+        }
+        processor.set_recompiler(self.recompiler);
+        processor.load(&fs::read(path).expect("Unable to open file"));
+        processor
+    }
+}
+
+fn main() {
+    let args: Vec<_> = env::args().collect();
+
+    let diverged = match args.len() {
+        3 => diff_traces(&args[1], &args[2]),
+        4.. if args[1] == "--roms" => match options(&args[4..]) {
+            Some((schedule, a, b)) => diff_roms(&args[2], &args[3], &schedule, &a, &b),
+            None => usage(),
+        },
+        _ => usage(),
+    };
+
+    if diverged {
+        process::exit(1);
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: trace-diff a.trace b.trace");
+    eprintln!("       trace-diff --roms a.ch8 b.ch8 [--keys script] [--ticks per-frame]");
+    eprintln!("                  [--a-start address] [--a-cdp1802] [--a-chip8x] [--a-recompiler]");
+    eprintln!("                  [--b-start address] [--b-cdp1802] [--b-chip8x] [--b-recompiler]");
+    eprintln!("Pass the same ROM twice to compare it under two settings.");
+    process::exit(2);
+}
+
+// Options after the two ROMs, for both sides and then each one
+fn options(args: &[String]) -> Option<(Schedule, Side, Side)> {
+    let mut schedule = Schedule::default();
+    let mut a = Side::default();
+    let mut b = Side::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--keys" => {
+                let text = fs::read_to_string(args.next()?).expect("Unable to open key script");
+                schedule.keys = KeyScript::parse(&text).expect("Unable to read key script");
+            }
+            "--ticks" => schedule.ticks_per_frame = args.next()?.parse().ok()?,
+            option => {
+                let (side, option) = match option.split_at_checked(4)? {
+                    ("--a-", option) => (&mut a, option),
+                    ("--b-", option) => (&mut b, option),
+                    _ => return None,
+                };
+                match option {
+                    "start" => {
+                        let hex = args.next()?.trim_start_matches("0x");
+                        side.start = Some(u16::from_str_radix(hex, 16).ok()?);
+                    }
+                    "cdp1802" => side.cdp1802 = true,
+                    "chip8x" => side.chip8x = true,
+                    "recompiler" => side.recompiler = true,
+                    _ => return None,
+                }
+            }
+        }
+    }
+    Some((schedule, a, b))
+}
+
+fn diff_traces(path_a: &str, path_b: &str) -> bool {
+    let a = BufReader::new(File::open(path_a).expect("Unable to open trace"));
+    let b = BufReader::new(File::open(path_b).expect("Unable to open trace"));
+
+    match compare_traces(a, b, CONTEXT).expect("Unable to read trace") {
+        Some(divergence) => {
+            print!("{}", divergence);
+            true
+        }
+        None => {
+            println!("traces are identical");
+            false
+        }
+    }
+}
+
+fn diff_roms(path_a: &str, path_b: &str, schedule: &Schedule, a: &Side, b: &Side) -> bool {
+    let mut a = a.processor(path_a);
+    let mut b = b.processor(path_b);
+
+    match run_lockstep(&mut a, &mut b, schedule, MAX_STEPS, CONTEXT) {
+        Some(divergence) => {
+            print!("{}", divergence);
+            true
+        }
+        None => {
+            println!("no divergence found");
+            false
+        }
+    }
+}
@@ -0,0 +1,166 @@
+// Memory the processor runs against
+//
+// Instructions go through read and write, which may have side effects
+// (memory-mapped devices, logging) and may refuse writes. Loading ROMs,
+// the font and debugger or cheat pokes go through peek and poke, which
+// never fail or trigger anything. The processor checks addresses against
+// size before calling either, so implementations can index directly.
+
+use core::ops::Range;
+
+use crate::constants::RAM_SIZE;
+use crate::ErrorKind;
+
+pub trait Bus {
+    // Number of addressable bytes, starting at 0
+    fn size(&self) -> usize;
+
+    fn read(&mut self, address: usize) -> u8;
+
+    fn write(&mut self, address: usize, value: u8) -> Result<(), ErrorKind>;
+
+    fn peek(&self, address: usize) -> u8;
+
+    fn poke(&mut self, address: usize, value: u8);
+}
+
+// Plain memory, 4 KB unless another size is given (e.g. 64 KB for XO-CHIP)
+#[derive(Debug, Clone)]
+pub struct Ram<const SIZE: usize = RAM_SIZE> {
+    bytes: [u8; SIZE],
+}
+
+impl<const SIZE: usize> Default for Ram<SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SIZE: usize> Ram<SIZE> {
+    pub fn new() -> Self {
+        Self { bytes: [0; SIZE] }
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.bytes
+    }
+}
+
+impl<const SIZE: usize> Bus for Ram<SIZE> {
+    fn size(&self) -> usize {
+        SIZE
+    }
+
+    fn read(&mut self, address: usize) -> u8 {
+        self.bytes[address]
+    }
+
+    fn write(&mut self, address: usize, value: u8) -> Result<(), ErrorKind> {
+        self.bytes[address] = value;
+        Ok(())
+    }
+
+    fn peek(&self, address: usize) -> u8 {
+        self.bytes[address]
+    }
+
+    fn poke(&mut self, address: usize, value: u8) {
+        self.bytes[address] = value;
+    }
+}
+
+// Turns writes into a range, usually where the ROM was loaded, into
+// WriteProtected errors to catch programs overwriting themselves
+#[derive(Debug, Clone)]
+pub struct ProtectedRom<B> {
+    inner: B,
+    protected: Range<usize>,
+}
+
+impl<B: Bus> ProtectedRom<B> {
+    pub fn new(inner: B, protected: Range<usize>) -> Self {
+        Self { inner, protected }
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+impl<B: Bus> Bus for ProtectedRom<B> {
+    fn size(&self) -> usize {
+        self.inner.size()
+    }
+
+    fn read(&mut self, address: usize) -> u8 {
+        self.inner.read(address)
+    }
+
+    fn write(&mut self, address: usize, value: u8) -> Result<(), ErrorKind> {
+        if self.protected.contains(&address) {
+            return Err(ErrorKind::WriteProtected(address));
+        }
+        self.inner.write(address, value)
+    }
+
+    fn peek(&self, address: usize) -> u8 {
+        self.inner.peek(address)
+    }
+
+    fn poke(&mut self, address: usize, value: u8) {
+        self.inner.poke(address, value);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read(usize, u8),
+    Write(usize, u8),
+}
+
+// Reports every access instructions make. Writes are reported even if the
+// inner bus refuses them
+#[derive(Debug, Clone)]
+pub struct Logged<B, F> {
+    inner: B,
+    log: F,
+}
+
+impl<B: Bus, F: FnMut(Access)> Logged<B, F> {
+    pub fn new(inner: B, log: F) -> Self {
+        Self { inner, log }
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+impl<B: Bus, F: FnMut(Access)> Bus for Logged<B, F> {
+    fn size(&self) -> usize {
+        self.inner.size()
+    }
+
+    fn read(&mut self, address: usize) -> u8 {
+        let value = self.inner.read(address);
+        (self.log)(Access::Read(address, value));
+        value
+    }
+
+    fn write(&mut self, address: usize, value: u8) -> Result<(), ErrorKind> {
+        (self.log)(Access::Write(address, value));
+        self.inner.write(address, value)
+    }
+
+    fn peek(&self, address: usize) -> u8 {
+        self.inner.peek(address)
+    }
+
+    fn poke(&mut self, address: usize, value: u8) {
+        self.inner.poke(address, value);
+    }
+}
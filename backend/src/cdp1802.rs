@@ -0,0 +1,316 @@
+// RCA CDP1802, the CPU of the COSMAC VIP, for the machine code
+// subroutines hybrid programs call with 0NNN
+//
+// Memory goes through the bus and is mirrored when the bus is smaller than
+// the 1802's 64 KB, like the VIP's RAM. Devices are reached through `Io`,
+// and `()` is nothing connected: outputs go nowhere, inputs read 0 and
+// the EF lines stay low.
+
+use crate::{Bus, ErrorKind};
+
+// Where the VIP interpreter keeps its state, counted back from the end of
+// RAM (0xECF, 0xEF0 and 0xF00 with 4 KB)
+pub const STACK_TOP: usize = 0x131;
+pub const V_REGISTERS: usize = 0x110;
+pub const DISPLAY: usize = 0x100;
+
+// Instructions a 0NNN subroutine gets to return in, far more than the VIP
+// runs in a frame
+pub const MAX_STEPS: usize = 100_000;
+
+pub trait Io {
+    // OUT 1-7
+    fn output(&mut self, _port: u8, _value: u8) {}
+
+    // INP 1-7
+    fn input(&mut self, _port: u8) -> u8 {
+        0
+    }
+
+    // EF1-EF4, tested by the B and BN branches
+    fn ef(&self, _line: u8) -> bool {
+        false
+    }
+}
+
+impl Io for () {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cdp1802 {
+    pub r: [u16; 16], // scratchpad registers
+    pub p: u8,        // which R is the program counter
+    pub x: u8,        // which R is the data pointer
+    pub d: u8,        // accumulator
+    pub df: bool,     // carry
+    pub t: u8,        // X and P saved by interrupts and MARK
+    pub ie: bool,     // interrupts enabled
+    pub q: bool,      // output flip-flop, drives the VIP's speaker
+    pub idle: bool,   // halted by IDL until an interrupt or DMA
+}
+
+impl Default for Cdp1802 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cdp1802 {
+    // State after a reset: P, X and R0 cleared, so R0 runs from address 0
+    pub fn new() -> Self {
+        Self {
+            r: [0; 16],
+            p: 0,
+            x: 0,
+            d: 0,
+            df: false,
+            t: 0,
+            ie: true,
+            q: false,
+            idle: false,
+        }
+    }
+
+    // Executes one instruction and returns how many machine cycles it
+    // took, 2 or 3 for long branches and skips. Idling takes 1
+    pub fn step<B: Bus, I: Io>(&mut self, bus: &mut B, io: &mut I) -> Result<u32, ErrorKind> {
+        if self.idle {
+            return Ok(1);
+        }
+
+        let opcode = self.fetch(bus);
+        let n = (opcode & 0xF) as usize;
+        let x = self.x as usize;
+
+        match opcode >> 4 {
+            0x0 if n == 0 => self.idle = true,            // IDL
+            0x0 => self.d = read(bus, self.r[n]),         // LDN
+            0x1 => self.r[n] = self.r[n].wrapping_add(1), // INC
+            0x2 => self.r[n] = self.r[n].wrapping_sub(1), // DEC
+            0x3 => {
+                // short branches, 38 is SKP and never branches
+                let taken = self.condition(n, io);
+                let p = self.p as usize;
+                if taken {
+                    let low = read(bus, self.r[p]);
+                    self.r[p] = (self.r[p] & 0xFF00) | low as u16;
+                } else {
+                    self.r[p] = self.r[p].wrapping_add(1);
+                }
+            }
+            0x4 => {
+                // LDA
+                self.d = read(bus, self.r[n]);
+                self.r[n] = self.r[n].wrapping_add(1);
+            }
+            0x5 => write(bus, self.r[n], self.d)?, // STR
+            0x6 => match n {
+                0 => self.r[x] = self.r[x].wrapping_add(1), // IRX
+                1..=7 => {
+                    // OUT
+                    io.output(n as u8, read(bus, self.r[x]));
+                    self.r[x] = self.r[x].wrapping_add(1);
+                }
+                8 => {} // unused on the 1802
+                _ => {
+                    // INP
+                    self.d = io.input(n as u8 - 8);
+                    write(bus, self.r[x], self.d)?;
+                }
+            },
+            0x7 => self.execute_7(n, bus)?,
+            0x8 => self.d = self.r[n] as u8,        // GLO
+            0x9 => self.d = (self.r[n] >> 8) as u8, // GHI
+            0xA => self.r[n] = (self.r[n] & 0xFF00) | self.d as u16, // PLO
+            0xB => self.r[n] = (self.r[n] & 0x00FF) | (self.d as u16) << 8, // PHI
+            0xC => {
+                // long branches and skips, C4 is NOP
+                let p = self.p as usize;
+                match n {
+                    4 => {}
+                    0xC => {
+                        // LSIE
+                        if self.ie {
+                            self.r[p] = self.r[p].wrapping_add(2);
+                        }
+                    }
+                    5..=7 | 0xD..=0xF => {
+                        // skip on Q, D == 0 or DF, inverted for C5-C7
+                        if self.condition((n & 3) | (!n & 8), io) {
+                            self.r[p] = self.r[p].wrapping_add(2);
+                        }
+                    }
+                    _ => {
+                        if self.condition(n, io) {
+                            let high = read(bus, self.r[p]);
+                            let low = read(bus, self.r[p].wrapping_add(1));
+                            self.r[p] = u16::from_be_bytes([high, low]);
+                        } else {
+                            self.r[p] = self.r[p].wrapping_add(2);
+                        }
+                    }
+                }
+                return Ok(3);
+            }
+            0xD => self.p = n as u8, // SEP
+            0xE => self.x = n as u8, // SEX
+            _ => {
+                // ALU on M(R(X)), or the immediate byte from F8 up
+                if n == 0x6 || n == 0xE {
+                    // SHR, SHL
+                    if n == 0x6 {
+                        self.df = self.d & 1 != 0;
+                        self.d >>= 1;
+                    } else {
+                        self.df = self.d & 0x80 != 0;
+                        self.d <<= 1;
+                    }
+                } else {
+                    let operand = if n < 8 {
+                        read(bus, self.r[x])
+                    } else {
+                        self.fetch(bus)
+                    };
+                    self.alu(n & 7, operand, false);
+                }
+            }
+        }
+        Ok(2)
+    }
+
+    // Saves X and P in T and runs the interrupt routine in R1 with R2 as
+    // the stack, if interrupts are enabled
+    pub fn interrupt(&mut self) -> bool {
+        if !self.ie {
+            return false;
+        }
+        self.t = self.x << 4 | self.p;
+        self.p = 1;
+        self.x = 2;
+        self.ie = false;
+        self.idle = false;
+        true
+    }
+
+    // Cycle stolen by a device reading memory at R0, like the CDP1861
+    // fetching the display
+    pub fn dma_out<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        let value = read(bus, self.r[0]);
+        self.r[0] = self.r[0].wrapping_add(1);
+        self.idle = false;
+        value
+    }
+
+    fn fetch<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        let p = self.p as usize;
+        let value = read(bus, self.r[p]);
+        self.r[p] = self.r[p].wrapping_add(1);
+        value
+    }
+
+    // Branch condition for the low nibble of a 3N or CN opcode, the top
+    // bit inverts it
+    fn condition<I: Io>(&self, n: usize, io: &I) -> bool {
+        let condition = match n & 7 {
+            0 => true,
+            1 => self.q,
+            2 => self.d == 0,
+            3 => self.df,
+            line => io.ef(line as u8 - 3),
+        };
+        condition != (n & 8 != 0)
+    }
+
+    // OR, AND, XOR, ADD, SD, SM by the low 3 bits of F1-F7 and F9-FF, or
+    // their with-carry forms from the 7N block
+    fn alu(&mut self, op: usize, operand: u8, with_carry: bool) {
+        let carry = (with_carry && self.df) as u16;
+        let borrow = (with_carry && !self.df) as u16;
+        match op {
+            0 => self.d = operand, // LDX, LDI
+            1 => self.d |= operand,
+            2 => self.d &= operand,
+            3 => self.d ^= operand,
+            4 => {
+                let sum = operand as u16 + self.d as u16 + carry;
+                self.d = sum as u8;
+                self.df = sum > 0xFF;
+            }
+            5 => self.subtract(operand, self.d, borrow),
+            7 => self.subtract(self.d, operand, borrow),
+            _ => unreachable!(),
+        }
+    }
+
+    // DF is set when there's no borrow
+    fn subtract(&mut self, a: u8, b: u8, borrow: u16) {
+        let difference = (a as u16).wrapping_sub(b as u16).wrapping_sub(borrow);
+        self.d = difference as u8;
+        self.df = difference <= 0xFF;
+    }
+
+    fn execute_7<B: Bus>(&mut self, n: usize, bus: &mut B) -> Result<(), ErrorKind> {
+        let x = self.x as usize;
+        match n {
+            0 | 1 => {
+                // RET, DIS
+                let value = read(bus, self.r[x]);
+                self.r[x] = self.r[x].wrapping_add(1);
+                self.x = value >> 4;
+                self.p = value & 0xF;
+                self.ie = n == 0;
+            }
+            2 => {
+                // LDXA
+                self.d = read(bus, self.r[x]);
+                self.r[x] = self.r[x].wrapping_add(1);
+            }
+            3 => {
+                // STXD
+                write(bus, self.r[x], self.d)?;
+                self.r[x] = self.r[x].wrapping_sub(1);
+            }
+            6 => {
+                // SHRC
+                let carry = self.df as u8;
+                self.df = self.d & 1 != 0;
+                self.d = self.d >> 1 | carry << 7;
+            }
+            8 => write(bus, self.r[x], self.t)?, // SAV
+            9 => {
+                // MARK
+                self.t = self.x << 4 | self.p;
+                write(bus, self.r[2], self.t)?;
+                self.x = self.p;
+                self.r[2] = self.r[2].wrapping_sub(1);
+            }
+            0xA => self.q = false, // REQ
+            0xB => self.q = true,  // SEQ
+            0xE => {
+                // SHLC
+                let carry = self.df as u8;
+                self.df = self.d & 0x80 != 0;
+                self.d = self.d << 1 | carry;
+            }
+            _ => {
+                // ADC, SDB, SMB on M(R(X)), then the immediate forms
+                let operand = if n < 8 {
+                    read(bus, self.r[x])
+                } else {
+                    self.fetch(bus)
+                };
+                self.alu(n & 7, operand, true);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read<B: Bus>(bus: &mut B, address: u16) -> u8 {
+    let size = bus.size();
+    bus.read(address as usize % size)
+}
+
+fn write<B: Bus>(bus: &mut B, address: u16, value: u8) -> Result<(), ErrorKind> {
+    let size = bus.size();
+    bus.write(address as usize % size, value)
+}
@@ -0,0 +1,207 @@
+// RAM search, freezes and patches
+//
+// Cheat files are plain text, one cheat per line, and are looked up by
+// the ROM's hash so they follow the game rather than its file name:
+//
+//     # infinite lives
+//     freeze 0x2A4 0x03
+//     # skip the title screen
+//     patch 0x200 0x1260
+//
+// A freeze writes a single byte, a patch writes any number of bytes
+// (usually whole instructions). Both are reapplied every frame.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::number::parse_number;
+use crate::Processor;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal(u8),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+// Narrows down RAM addresses across frames, e.g. search for the value
+// of a lives counter, lose a life, then keep only what decreased
+pub struct MemorySearch {
+    candidates: Vec<usize>,
+    previous: Vec<u8>,
+}
+
+impl MemorySearch {
+    // Starts with every address as a candidate
+    pub fn new(processor: &Processor) -> Self {
+        let ram = processor.ram();
+        Self {
+            candidates: (0..ram.len()).collect(),
+            previous: ram.to_vec(),
+        }
+    }
+
+    // Keeps candidates whose current value matches, then remembers the
+    // current values for the next comparison
+    pub fn narrow(&mut self, processor: &Processor, comparison: Comparison) {
+        let ram = processor.ram();
+        let previous = &self.previous;
+
+        self.candidates.retain(|&address| {
+            let old = previous[address];
+            let new = ram[address];
+            match comparison {
+                Comparison::Equal(value) => new == value,
+                Comparison::Changed => new != old,
+                Comparison::Unchanged => new == old,
+                Comparison::Increased => new > old,
+                Comparison::Decreased => new < old,
+            }
+        });
+        self.previous.copy_from_slice(ram);
+    }
+
+    pub fn candidates(&self) -> &[usize] {
+        &self.candidates
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cheat {
+    Freeze { address: u16, value: u8 },
+    Patch { address: u16, bytes: Vec<u8> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid cheat on line {}", self.line)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheatList {
+    pub cheats: Vec<Cheat>,
+}
+
+impl CheatList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut cheats = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = ParseError { line: i + 1 };
+            let words: Vec<_> = line.split_whitespace().collect();
+            let cheat = match words.as_slice() {
+                ["freeze", address, value] => Cheat::Freeze {
+                    address: parse_number(address).ok_or(error.clone())?,
+                    value: parse_number(value).ok_or(error.clone())?,
+                },
+                ["patch", address, bytes] => Cheat::Patch {
+                    address: parse_number(address).ok_or(error.clone())?,
+                    bytes: parse_bytes(bytes).ok_or(error.clone())?,
+                },
+                _ => return Err(error),
+            };
+            cheats.push(cheat);
+        }
+
+        Ok(Self { cheats })
+    }
+
+    // Writes every cheat into RAM, call once per frame
+    pub fn apply(&self, processor: &mut Processor) {
+        for cheat in &self.cheats {
+            match cheat {
+                Cheat::Freeze { address, value } => {
+                    processor.write_ram(*address as usize, *value);
+                }
+                Cheat::Patch { address, bytes } => {
+                    for (i, value) in bytes.iter().enumerate() {
+                        processor.write_ram(*address as usize + i, *value);
+                    }
+                }
+            }
+        }
+    }
+
+    // Cheats for the ROM in `directory`, empty if there's no file yet
+    pub fn load_for_rom<P: AsRef<Path>>(directory: P, rom: &[u8]) -> io::Result<Self> {
+        match fs::read_to_string(cheat_path(directory, rom)) {
+            Ok(text) => {
+                Self::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save_for_rom<P: AsRef<Path>>(&self, directory: P, rom: &[u8]) -> io::Result<()> {
+        fs::create_dir_all(directory.as_ref())?;
+        fs::write(cheat_path(directory, rom), self.to_string())
+    }
+}
+
+impl fmt::Display for CheatList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for cheat in &self.cheats {
+            match cheat {
+                Cheat::Freeze { address, value } => {
+                    writeln!(f, "freeze {:#05X} {:#04X}", address, value)?;
+                }
+                Cheat::Patch { address, bytes } => {
+                    write!(f, "patch {:#05X} 0x", address)?;
+                    for byte in bytes {
+                        write!(f, "{:02X}", byte)?;
+                    }
+                    writeln!(f)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// 64-bit FNV-1a, stable across platforms and versions
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn cheat_path<P: AsRef<Path>>(directory: P, rom: &[u8]) -> PathBuf {
+    directory
+        .as_ref()
+        .join(format!("{:016x}.cht", rom_hash(rom)))
+}
+
+fn parse_bytes(text: &str) -> Option<Vec<u8>> {
+    let hex = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))?;
+    if hex.is_empty() || !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,118 @@
+// CHIP-8X, the VIP interpreter for the VP-590 color board and a second
+// keypad
+//
+// The screen stays one bit per pixel and the board colors it: lit pixels
+// take the foreground color of the zone they're in, dark ones the
+// background. Zones are 8 pixels wide and one line tall, BXY0 colors them
+// in blocks of 4 lines and BXYN line by line. 02A0 steps the background
+// through blue, black, green and red. Programs start at 0x300.
+
+use crate::constants::{NUM_KEYS, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// The interpreter and color board code take up to 0x2FF
+pub const START_ADDRESS: u16 = 0x300;
+
+pub const ZONE_WIDTH: usize = 8;
+pub const ZONE_COLUMNS: usize = SCREEN_WIDTH / ZONE_WIDTH;
+// lines per zone for BXY0
+pub const BLOCK_HEIGHT: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Black,
+    Red,
+    Blue,
+    Violet,
+    Green,
+    Yellow,
+    Aqua,
+    White,
+}
+
+pub(crate) const BACKGROUNDS: [Color; 4] = [Color::Blue, Color::Black, Color::Green, Color::Red];
+
+impl Color {
+    // The VP-590's color bits, red 1, blue 2 and green 4. Higher bits are
+    // ignored
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 7 {
+            0 => Color::Black,
+            1 => Color::Red,
+            2 => Color::Blue,
+            3 => Color::Violet,
+            4 => Color::Green,
+            5 => Color::Yellow,
+            6 => Color::Aqua,
+            _ => Color::White,
+        }
+    }
+
+    pub fn rgb(self) -> [u8; 3] {
+        let bits = self as u8;
+        let level = |bit: u8| if bits & bit != 0 { 0xFF } else { 0 };
+        [level(1), level(4), level(2)]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chip8x {
+    pub(crate) background: usize, // index into BACKGROUNDS
+    pub(crate) zones: [Color; ZONE_COLUMNS * SCREEN_HEIGHT],
+    pub(crate) keys: [bool; NUM_KEYS], // second keypad, for EXF2 and EXF5
+}
+
+impl Default for Chip8x {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Chip8x {
+    // Red on blue, as the interpreter starts
+    pub fn new() -> Self {
+        Self {
+            background: 0,
+            zones: [Color::Red; ZONE_COLUMNS * SCREEN_HEIGHT],
+            keys: [false; NUM_KEYS],
+        }
+    }
+
+    pub fn background(&self) -> Color {
+        BACKGROUNDS[self.background]
+    }
+
+    // Foreground color of the zone the pixel at (x, y) is in
+    pub fn foreground(&self, x: usize, y: usize) -> Color {
+        self.zones[x / ZONE_WIDTH + ZONE_COLUMNS * y]
+    }
+
+    // Color to show a pixel of Processor::get_display in
+    pub fn pixel(&self, index: usize, lit: bool) -> Color {
+        if lit {
+            self.foreground(index % SCREEN_WIDTH, index / SCREEN_WIDTH)
+        } else {
+            self.background()
+        }
+    }
+
+    pub(crate) fn cycle_background(&mut self) {
+        self.background = (self.background + 1) % BACKGROUNDS.len();
+    }
+
+    // Colors `columns` zones across from `column` on `lines` lines down
+    // from `line`, wrapping at the edges of the screen
+    pub(crate) fn fill(
+        &mut self,
+        column: usize,
+        columns: usize,
+        line: usize,
+        lines: usize,
+        color: Color,
+    ) {
+        for y in line..line + lines {
+            for x in column..column + columns {
+                self.zones[x % ZONE_COLUMNS + ZONE_COLUMNS * (y % SCREEN_HEIGHT)] = color;
+            }
+        }
+    }
+}
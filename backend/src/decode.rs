@@ -0,0 +1,138 @@
+// Opcodes decoded into their operands once, so execution doesn't have to
+// split nibbles again. Variants follow the disassembler's mnemonics, X and
+// Y are register indices. The CHIP-8X ones that don't clash with CHIP-8
+// get their own variants, and are invalid unless it's on.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Nop,                         // 0000
+    Cls,                         // 00E0
+    Ret,                         // 00EE
+    Sys(u16),                    // 0NNN
+    Jp(u16),                     // 1NNN
+    Call(u16),                   // 2NNN
+    SeImm { x: u8, nn: u8 },     // 3XNN
+    SneImm { x: u8, nn: u8 },    // 4XNN
+    SeReg { x: u8, y: u8 },      // 5XY0
+    AddNibbles { x: u8, y: u8 }, // 5XY1, CHIP-8X
+    LdImm { x: u8, nn: u8 },     // 6XNN
+    AddImm { x: u8, nn: u8 },    // 7XNN
+    LdReg { x: u8, y: u8 },      // 8XY0
+    Or { x: u8, y: u8 },         // 8XY1
+    And { x: u8, y: u8 },        // 8XY2
+    Xor { x: u8, y: u8 },        // 8XY3
+    Add { x: u8, y: u8 },        // 8XY4
+    Sub { x: u8, y: u8 },        // 8XY5
+    Shr { x: u8 },               // 8XY6
+    Subn { x: u8, y: u8 },       // 8XY7
+    Shl { x: u8 },               // 8XYE
+    SneReg { x: u8, y: u8 },     // 9XY0
+    LdI(u16),                    // ANNN
+    JpV0(u16),                   // BNNN
+    Rnd { x: u8, nn: u8 },       // CXNN
+    Drw { x: u8, y: u8, n: u8 }, // DXYN
+    Skp { x: u8 },               // EX9E
+    Sknp { x: u8 },              // EXA1
+    SkpPad2 { x: u8 },           // EXF2, CHIP-8X
+    SknpPad2 { x: u8 },          // EXF5, CHIP-8X
+    LdVxDt { x: u8 },            // FX07
+    LdKey { x: u8 },             // FX0A
+    LdDtVx { x: u8 },            // FX15
+    LdStVx { x: u8 },            // FX18
+    AddI { x: u8 },              // FX1E
+    LdFont { x: u8 },            // FX29
+    Bcd { x: u8 },               // FX33
+    Store { x: u8 },             // FX55
+    Load { x: u8 },              // FX65
+    Invalid,
+}
+
+pub fn decode(opcode: u16) -> Instruction {
+    let x = ((opcode & 0x0F00) >> 8) as u8;
+    let y = ((opcode & 0x00F0) >> 4) as u8;
+    let n = (opcode & 0x000F) as u8;
+    let nn = (opcode & 0x00FF) as u8;
+    let nnn = opcode & 0x0FFF;
+
+    match (opcode >> 12, x, y, n) {
+        (0, 0, 0, 0) => Instruction::Nop,
+        (0, 0, 0xE, 0) => Instruction::Cls,
+        (0, 0, 0xE, 0xE) => Instruction::Ret,
+        (0, _, _, _) => Instruction::Sys(nnn),
+        (1, _, _, _) => Instruction::Jp(nnn),
+        (2, _, _, _) => Instruction::Call(nnn),
+        (3, _, _, _) => Instruction::SeImm { x, nn },
+        (4, _, _, _) => Instruction::SneImm { x, nn },
+        (5, _, _, 0) => Instruction::SeReg { x, y },
+        (5, _, _, 1) => Instruction::AddNibbles { x, y },
+        (6, _, _, _) => Instruction::LdImm { x, nn },
+        (7, _, _, _) => Instruction::AddImm { x, nn },
+        (8, _, _, 0) => Instruction::LdReg { x, y },
+        (8, _, _, 1) => Instruction::Or { x, y },
+        (8, _, _, 2) => Instruction::And { x, y },
+        (8, _, _, 3) => Instruction::Xor { x, y },
+        (8, _, _, 4) => Instruction::Add { x, y },
+        (8, _, _, 5) => Instruction::Sub { x, y },
+        (8, _, _, 6) => Instruction::Shr { x },
+        (8, _, _, 7) => Instruction::Subn { x, y },
+        (8, _, _, 0xE) => Instruction::Shl { x },
+        (9, _, _, 0) => Instruction::SneReg { x, y },
+        (0xA, _, _, _) => Instruction::LdI(nnn),
+        (0xB, _, _, _) => Instruction::JpV0(nnn),
+        (0xC, _, _, _) => Instruction::Rnd { x, nn },
+        (0xD, _, _, _) => Instruction::Drw { x, y, n },
+        (0xE, _, 9, 0xE) => Instruction::Skp { x },
+        (0xE, _, 0xA, 1) => Instruction::Sknp { x },
+        (0xE, _, 0xF, 2) => Instruction::SkpPad2 { x },
+        (0xE, _, 0xF, 5) => Instruction::SknpPad2 { x },
+        (0xF, _, 0, 7) => Instruction::LdVxDt { x },
+        (0xF, _, 0, 0xA) => Instruction::LdKey { x },
+        (0xF, _, 1, 5) => Instruction::LdDtVx { x },
+        (0xF, _, 1, 8) => Instruction::LdStVx { x },
+        (0xF, _, 1, 0xE) => Instruction::AddI { x },
+        (0xF, _, 2, 9) => Instruction::LdFont { x },
+        (0xF, _, 3, 3) => Instruction::Bcd { x },
+        (0xF, _, 5, 5) => Instruction::Store { x },
+        (0xF, _, 6, 5) => Instruction::Load { x },
+        (_, _, _, _) => Instruction::Invalid,
+    }
+}
+
+// Decoded instructions by address, with the opcode they came from for
+// tracing and profiling. An instruction spans two bytes, so a write to an
+// address drops the entries starting there and one byte before
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub(crate) struct DecodeCache {
+    entries: Vec<Option<(u16, Instruction)>>,
+}
+
+#[cfg(feature = "std")]
+impl DecodeCache {
+    pub(crate) fn new(size: usize) -> Self {
+        Self {
+            entries: vec![None; size],
+        }
+    }
+
+    pub(crate) fn get(&self, address: usize) -> Option<(u16, Instruction)> {
+        self.entries[address]
+    }
+
+    pub(crate) fn insert(&mut self, address: usize, opcode: u16) -> (u16, Instruction) {
+        let entry = (opcode, decode(opcode));
+        self.entries[address] = Some(entry);
+        entry
+    }
+
+    pub(crate) fn invalidate(&mut self, address: usize) {
+        self.entries[address] = None;
+        if address > 0 {
+            self.entries[address - 1] = None;
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.fill(None);
+    }
+}
@@ -0,0 +1,140 @@
+use core::fmt;
+
+use crate::decode::{decode, Instruction};
+
+// Mnemonic for a single opcode, formatted lazily so callers that
+// only filter or count instructions don't pay for the string
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Disassembly(pub u16);
+
+pub fn disassemble(opcode: u16) -> Disassembly {
+    Disassembly(opcode)
+}
+
+// The same for CHIP-8X, where 02A0 and BXYN mean something else
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chip8xDisassembly(pub u16);
+
+pub fn disassemble_chip8x(opcode: u16) -> Chip8xDisassembly {
+    Chip8xDisassembly(opcode)
+}
+
+// Opcode pattern the instruction belongs to, e.g. "8XY4" or "DXYN"
+pub fn opcode_class(opcode: u16) -> &'static str {
+    class(decode(opcode))
+}
+
+// opcode_class for CHIP-8X
+pub fn chip8x_opcode_class(opcode: u16) -> &'static str {
+    match decode(opcode) {
+        Instruction::Sys(0x2A0) => "02A0",
+        Instruction::JpV0(_) => "BXYN",
+        instruction => class(instruction),
+    }
+}
+
+fn class(instruction: Instruction) -> &'static str {
+    match instruction {
+        Instruction::Nop => "0000",
+        Instruction::Cls => "00E0",
+        Instruction::Ret => "00EE",
+        Instruction::Sys(_) => "0NNN",
+        Instruction::Jp(_) => "1NNN",
+        Instruction::Call(_) => "2NNN",
+        Instruction::SeImm { .. } => "3XNN",
+        Instruction::SneImm { .. } => "4XNN",
+        Instruction::SeReg { .. } => "5XY0",
+        Instruction::AddNibbles { .. } => "5XY1",
+        Instruction::LdImm { .. } => "6XNN",
+        Instruction::AddImm { .. } => "7XNN",
+        Instruction::LdReg { .. } => "8XY0",
+        Instruction::Or { .. } => "8XY1",
+        Instruction::And { .. } => "8XY2",
+        Instruction::Xor { .. } => "8XY3",
+        Instruction::Add { .. } => "8XY4",
+        Instruction::Sub { .. } => "8XY5",
+        Instruction::Shr { .. } => "8XY6",
+        Instruction::Subn { .. } => "8XY7",
+        Instruction::Shl { .. } => "8XYE",
+        Instruction::SneReg { .. } => "9XY0",
+        Instruction::LdI(_) => "ANNN",
+        Instruction::JpV0(_) => "BNNN",
+        Instruction::Rnd { .. } => "CXNN",
+        Instruction::Drw { .. } => "DXYN",
+        Instruction::Skp { .. } => "EX9E",
+        Instruction::Sknp { .. } => "EXA1",
+        Instruction::SkpPad2 { .. } => "EXF2",
+        Instruction::SknpPad2 { .. } => "EXF5",
+        Instruction::LdVxDt { .. } => "FX07",
+        Instruction::LdKey { .. } => "FX0A",
+        Instruction::LdDtVx { .. } => "FX15",
+        Instruction::LdStVx { .. } => "FX18",
+        Instruction::AddI { .. } => "FX1E",
+        Instruction::LdFont { .. } => "FX29",
+        Instruction::Bcd { .. } => "FX33",
+        Instruction::Store { .. } => "FX55",
+        Instruction::Load { .. } => "FX65",
+        Instruction::Invalid => "????",
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match decode(self.0) {
+            Instruction::Nop => write!(f, "NOP"),
+            Instruction::Cls => write!(f, "CLS"),
+            Instruction::Ret => write!(f, "RET"),
+            Instruction::Sys(nnn) => write!(f, "SYS {:#05X}", nnn),
+            Instruction::Jp(nnn) => write!(f, "JP {:#05X}", nnn),
+            Instruction::Call(nnn) => write!(f, "CALL {:#05X}", nnn),
+            Instruction::SeImm { x, nn } => write!(f, "SE V{:X}, {:#04X}", x, nn),
+            Instruction::SneImm { x, nn } => write!(f, "SNE V{:X}, {:#04X}", x, nn),
+            Instruction::SeReg { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::AddNibbles { x, y } => write!(f, "ADDN V{:X}, V{:X}", x, y),
+            Instruction::LdImm { x, nn } => write!(f, "LD V{:X}, {:#04X}", x, nn),
+            Instruction::AddImm { x, nn } => write!(f, "ADD V{:X}, {:#04X}", x, nn),
+            Instruction::LdReg { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::Add { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::Shr { x } => write!(f, "SHR V{:X}", x),
+            Instruction::Subn { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::Shl { x } => write!(f, "SHL V{:X}", x),
+            Instruction::SneReg { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LdI(nnn) => write!(f, "LD I, {:#05X}", nnn),
+            Instruction::JpV0(nnn) => write!(f, "JP V0, {:#05X}", nnn),
+            Instruction::Rnd { x, nn } => write!(f, "RND V{:X}, {:#04X}", x, nn),
+            Instruction::Drw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::Skp { x } => write!(f, "SKP V{:X}", x),
+            Instruction::Sknp { x } => write!(f, "SKNP V{:X}", x),
+            Instruction::SkpPad2 { x } => write!(f, "SKP2 V{:X}", x),
+            Instruction::SknpPad2 { x } => write!(f, "SKNP2 V{:X}", x),
+            Instruction::LdVxDt { x } => write!(f, "LD V{:X}, DT", x),
+            Instruction::LdKey { x } => write!(f, "LD V{:X}, K", x),
+            Instruction::LdDtVx { x } => write!(f, "LD DT, V{:X}", x),
+            Instruction::LdStVx { x } => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddI { x } => write!(f, "ADD I, V{:X}", x),
+            Instruction::LdFont { x } => write!(f, "LD F, V{:X}", x),
+            Instruction::Bcd { x } => write!(f, "LD B, V{:X}", x),
+            Instruction::Store { x } => write!(f, "LD [I], V{:X}", x),
+            Instruction::Load { x } => write!(f, "LD V{:X}, [I]", x),
+            Instruction::Invalid => write!(f, "DW {:#06X}", self.0),
+        }
+    }
+}
+
+impl fmt::Display for Chip8xDisassembly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match decode(self.0) {
+            Instruction::Sys(0x2A0) => write!(f, "BGCOL"),
+            // BXYN colors a zone instead of jumping
+            Instruction::JpV0(nnn) => {
+                let (x, y, n) = (nnn >> 8, (nnn >> 4) & 0xF, nnn & 0xF);
+                write!(f, "COL V{:X}, V{:X}, {}", x, y, n)
+            }
+            _ => Disassembly(self.0).fmt(f),
+        }
+    }
+}
@@ -0,0 +1,193 @@
+// Gym-style environment for training agents
+//
+// An action is the set of pressed keys as a bitmask, bit N for key N.
+// Each step holds those keys for `frame_skip` frames and returns the
+// screen packed 8 pixels per byte (leftmost pixel in the high bit), the
+// change in score and whether the episode is over.
+//
+// The score and done closures read RAM, e.g. the BCD digits a game keeps
+// its score in. They're shared between clones, so cloning an environment
+// for parallel rollouts only copies the processor, along with its decode
+// and block caches when the recompiler is on.
+
+use std::sync::Arc;
+
+use crate::constants::*;
+use crate::Processor;
+
+pub const OBSERVATION_SIZE: usize = SCREEN_WIDTH * SCREEN_HEIGHT / 8;
+
+pub type Observation = [u8; OBSERVATION_SIZE];
+pub type ScoreFn = Arc<dyn Fn(&[u8]) -> f32 + Send + Sync>;
+pub type DoneFn = Arc<dyn Fn(&[u8]) -> bool + Send + Sync>;
+
+const TICKS_PER_FRAME: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Step {
+    pub observation: Observation,
+    pub reward: f32,
+    pub done: bool,
+}
+
+#[derive(Clone)]
+pub struct Env {
+    processor: Processor,
+    start: Processor, // snapshot reset() goes back to
+    score: ScoreFn,
+    last_score: f32,
+    done: Option<DoneFn>,
+    ticks_per_frame: usize,
+    frame_skip: usize,
+    max_frames: Option<usize>,
+    frames: usize,
+    crashed: bool,
+}
+
+impl Env {
+    // Returns None if the ROM doesn't fit in memory
+    pub fn new<F>(rom: &[u8], seed: u64, score: F) -> Option<Self>
+    where
+        F: Fn(&[u8]) -> f32 + Send + Sync + 'static,
+    {
+        let mut start = Processor::with_seed(seed);
+        start.try_load(rom).ok()?;
+        let score: ScoreFn = Arc::new(score);
+        let last_score = score(start.ram());
+
+        Some(Self {
+            processor: start.clone(),
+            start,
+            score,
+            last_score,
+            done: None,
+            ticks_per_frame: TICKS_PER_FRAME,
+            frame_skip: 1,
+            max_frames: None,
+            frames: 0,
+            crashed: false,
+        })
+    }
+
+    // Ends the episode once `done` returns true
+    pub fn with_done<F>(mut self, done: F) -> Self
+    where
+        F: Fn(&[u8]) -> bool + Send + Sync + 'static,
+    {
+        self.done = Some(Arc::new(done));
+        self
+    }
+
+    pub fn with_ticks_per_frame(mut self, ticks: usize) -> Self {
+        self.ticks_per_frame = ticks;
+        self
+    }
+
+    // Frames each action is held for, at least 1
+    pub fn with_frame_skip(mut self, frames: usize) -> Self {
+        self.frame_skip = frames.max(1);
+        self
+    }
+
+    // Ends the episode after this many frames
+    pub fn with_max_frames(mut self, frames: usize) -> Self {
+        self.max_frames = Some(frames);
+        self
+    }
+
+    // Runs the ROM through the recompiler, for both the current state and
+    // the start snapshot
+    pub fn with_recompiler(mut self, enabled: bool) -> Self {
+        self.processor.set_recompiler(enabled);
+        self.start.set_recompiler(enabled);
+        self
+    }
+
+    // Replaces the start snapshot with the current state, e.g. after
+    // skipping a title screen
+    pub fn set_start(&mut self) {
+        self.start = self.processor.clone();
+    }
+
+    // Gives the start snapshot a new seed for CXNN
+    pub fn seed(&mut self, seed: u64) {
+        self.start.seed(seed);
+    }
+
+    pub fn reset(&mut self) -> Observation {
+        self.processor = self.start.clone();
+        self.last_score = (self.score)(self.processor.ram());
+        self.frames = 0;
+        self.crashed = false;
+        self.observation()
+    }
+
+    // Once done, further steps leave the processor alone
+    pub fn step(&mut self, action: u16) -> Step {
+        if self.is_done() {
+            return Step {
+                observation: self.observation(),
+                reward: 0.0,
+                done: true,
+            };
+        }
+
+        for key in 0..NUM_KEYS {
+            self.processor.keypress(key, action & (1 << key) != 0);
+        }
+
+        for _ in 0..self.frame_skip {
+            if self.processor.run(self.ticks_per_frame).is_err() {
+                self.crashed = true;
+                break;
+            }
+            self.processor.tick_timers();
+            self.frames += 1;
+            if self.is_done() {
+                break;
+            }
+        }
+
+        let score = (self.score)(self.processor.ram());
+        let reward = score - self.last_score;
+        self.last_score = score;
+
+        Step {
+            observation: self.observation(),
+            reward,
+            done: self.is_done(),
+        }
+    }
+
+    // True after the ROM hit an execution error, the frame limit was
+    // reached or the done closure said so
+    pub fn is_done(&self) -> bool {
+        self.crashed
+            || self.max_frames.is_some_and(|max| self.frames >= max)
+            || self
+                .done
+                .as_ref()
+                .is_some_and(|done| done(self.processor.ram()))
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    pub fn processor(&self) -> &Processor {
+        &self.processor
+    }
+
+    pub fn observation(&self) -> Observation {
+        let mut observation = [0; OBSERVATION_SIZE];
+        for (byte, pixels) in observation
+            .iter_mut()
+            .zip(self.processor.get_display().chunks(8))
+        {
+            *byte = pixels
+                .iter()
+                .fold(0, |byte, pixel| (byte << 1) | *pixel as u8);
+        }
+        observation
+    }
+}
@@ -0,0 +1,84 @@
+use core::fmt;
+
+// Runtime errors caused by bugs in the input ROM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    // Opcode doesn't match any instruction
+    InvalidOpcode,
+    // 2NNN with a full stack
+    StackOverflow,
+    // 00EE with an empty stack
+    StackUnderflow,
+    // PC points past the end of RAM
+    PcOutOfBounds,
+    // Instruction touched an address past the end of RAM
+    MemoryOutOfBounds(usize),
+    // EX9E/EXA1 with a key index above 0xF
+    InvalidKey(u8),
+    // Instruction wrote to memory the bus protects
+    WriteProtected(usize),
+    // 0NNN machine code at this address didn't return to the interpreter
+    MachineCodeTimeout(u16),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecutionError {
+    pub address: u16, // address of the faulting instruction
+    pub opcode: u16,  // 0 if the opcode couldn't be fetched
+    pub kind: ErrorKind,
+}
+
+// Image that doesn't fit in memory at the address it was loaded to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadError {
+    pub address: usize,
+    pub len: usize,
+    pub capacity: usize, // bytes from the address to the end of memory
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::InvalidOpcode => write!(f, "invalid opcode"),
+            ErrorKind::StackOverflow => write!(f, "stack overflow"),
+            ErrorKind::StackUnderflow => write!(f, "stack underflow"),
+            ErrorKind::PcOutOfBounds => write!(f, "program counter out of bounds"),
+            ErrorKind::MemoryOutOfBounds(address) => {
+                write!(f, "memory access out of bounds at {:#05X}", address)
+            }
+            ErrorKind::InvalidKey(key) => write!(f, "invalid key index {:#04X}", key),
+            ErrorKind::WriteProtected(address) => {
+                write!(f, "write to protected memory at {:#05X}", address)
+            }
+            ErrorKind::MachineCodeTimeout(address) => {
+                write!(f, "machine code at {:#05X} didn't return", address)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at {:#05X} (opcode {:#06X})",
+            self.kind, self.address, self.opcode
+        )
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} bytes don't fit at {:#05X}, only {} bytes of memory are left",
+            self.len, self.address, self.capacity
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ExecutionError {}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LoadError {}
@@ -0,0 +1,444 @@
+// GDB remote serial protocol stub
+//
+// Registers are numbered V0-VF (0-15, 8 bits), I (16, 16 bits),
+// PC (17, 16 bits), SP (18, 8 bits), DT (19, 8 bits) and ST (20, 8 bits),
+// little endian, and described to the client through target.xml.
+// Memory addresses map straight onto the processor's RAM.
+//
+// The stub never blocks once a client is connected: frontends call
+// run_frame once per frame and keep drawing while the target is halted.
+
+use std::collections::BTreeSet;
+use std::io::{self, ErrorKind as IoErrorKind, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+
+use crate::constants::NUM_REGS;
+use crate::number;
+use crate::{ErrorKind, Processor};
+
+const NUM_GDB_REGS: usize = NUM_REGS + 5;
+const REG_I: usize = NUM_REGS;
+const REG_PC: usize = NUM_REGS + 1;
+const REG_SP: usize = NUM_REGS + 2;
+const REG_DT: usize = NUM_REGS + 3;
+const REG_ST: usize = NUM_REGS + 4;
+
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Halted,
+    Running,
+    Detached,
+}
+
+pub struct GdbStub {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    breakpoints: BTreeSet<u16>,
+    status: Status,
+    resuming: bool, // don't stop on the breakpoint we're continuing from
+}
+
+enum Packet {
+    Interrupt,
+    Command(Vec<u8>),
+}
+
+impl GdbStub {
+    // Waits on localhost for a single debugger connection
+    pub fn listen(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        let (stream, _) = listener.accept()?;
+        Self::new(stream)
+    }
+
+    // The target starts out halted, as gdb expects after connecting
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            buffer: Vec::new(),
+            breakpoints: BTreeSet::new(),
+            status: Status::Halted,
+            resuming: false,
+        })
+    }
+
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    // Handles pending packets, then runs up to `ticks` instructions if
+    // the debugger let the target continue. Frontends should only tick
+    // the timers while this returns Running
+    pub fn run_frame(&mut self, processor: &mut Processor, ticks: usize) -> io::Result<Status> {
+        self.receive()?;
+
+        while let Some(packet) = self.next_packet()? {
+            match packet {
+                Packet::Interrupt => {
+                    if self.status == Status::Running {
+                        self.stop(SIGTRAP)?;
+                    }
+                }
+                Packet::Command(command) => self.handle(processor, &command)?,
+            }
+            if self.status == Status::Detached {
+                return Ok(Status::Detached);
+            }
+        }
+
+        if self.status == Status::Running {
+            for _ in 0..ticks {
+                let pc = processor.registers().pc;
+                if !self.resuming && self.breakpoints.contains(&pc) {
+                    self.stop(SIGTRAP)?;
+                    break;
+                }
+                self.resuming = false;
+
+                if let Err(error) = processor.try_tick() {
+                    self.stop(signal_for(error.kind))?;
+                    break;
+                }
+            }
+        }
+
+        Ok(self.status)
+    }
+
+    fn receive(&mut self) -> io::Result<()> {
+        let mut chunk = [0; 1024];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => {
+                    self.status = Status::Detached;
+                    return Ok(());
+                }
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == IoErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == IoErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // Pulls the next complete packet out of the buffer, acknowledging it
+    fn next_packet(&mut self) -> io::Result<Option<Packet>> {
+        loop {
+            match self.buffer.first() {
+                None => return Ok(None),
+                Some(0x03) => {
+                    self.buffer.remove(0);
+                    return Ok(Some(Packet::Interrupt));
+                }
+                Some(b'$') => break,
+                // acks and line noise
+                Some(_) => {
+                    self.buffer.remove(0);
+                }
+            }
+        }
+
+        let end = match self.buffer.iter().position(|b| *b == b'#') {
+            Some(end) if end + 2 < self.buffer.len() => end,
+            _ => return Ok(None),
+        };
+
+        let packet: Vec<u8> = self.buffer.drain(..end + 3).collect();
+        let data = &packet[1..end];
+        let expected = parse_hex(&packet[end + 1..]);
+
+        if expected == Some(checksum(data) as u64) {
+            self.send_raw(b"+")?;
+            Ok(Some(Packet::Command(data.to_vec())))
+        } else {
+            self.send_raw(b"-")?;
+            Ok(None)
+        }
+    }
+
+    fn handle(&mut self, processor: &mut Processor, command: &[u8]) -> io::Result<()> {
+        let (kind, args) = match command.split_first() {
+            Some((kind, args)) => (*kind, args),
+            None => return self.send(b""),
+        };
+
+        match kind {
+            b'?' => self.send_stop(SIGTRAP),
+            b'g' => {
+                let bytes = register_bytes(processor);
+                self.send(encode_hex(&bytes).as_bytes())
+            }
+            b'G' => match decode_hex(args) {
+                Some(bytes) if bytes.len() == register_bytes(processor).len() => {
+                    let mut registers = processor.registers();
+                    let mut offset = 0;
+                    for n in 0..NUM_GDB_REGS {
+                        let size = register_size(n);
+                        set_register(&mut registers, n, &bytes[offset..offset + size]);
+                        offset += size;
+                    }
+                    processor.set_registers(registers);
+                    self.send(b"OK")
+                }
+                _ => self.send(b"E01"),
+            },
+            b'p' => match parse_hex(args).map(|n| n as usize) {
+                Some(n) if n < NUM_GDB_REGS => {
+                    let bytes = register_bytes(processor);
+                    let offset: usize = (0..n).map(register_size).sum();
+                    let value = &bytes[offset..offset + register_size(n)];
+                    self.send(encode_hex(value).as_bytes())
+                }
+                _ => self.send(b"E01"),
+            },
+            b'P' => {
+                let mut parts = args.splitn(2, |b| *b == b'=');
+                let n = parts.next().and_then(parse_hex).map(|n| n as usize);
+                let value = parts.next().and_then(decode_hex);
+                match (n, value) {
+                    (Some(n), Some(value))
+                        if n < NUM_GDB_REGS && value.len() == register_size(n) =>
+                    {
+                        let mut registers = processor.registers();
+                        set_register(&mut registers, n, &value);
+                        processor.set_registers(registers);
+                        self.send(b"OK")
+                    }
+                    _ => self.send(b"E01"),
+                }
+            }
+            b'm' => {
+                let mut parts = args.splitn(2, |b| *b == b',');
+                let address = parts.next().and_then(parse_hex).map(|a| a as usize);
+                let length = parts.next().and_then(parse_hex).map(|l| l as usize);
+                let ram = processor.ram();
+                match (address, length) {
+                    (Some(address), Some(length)) if address < ram.len() => {
+                        let end = address.saturating_add(length).min(ram.len());
+                        let bytes = &ram[address..end];
+                        self.send(encode_hex(bytes).as_bytes())
+                    }
+                    _ => self.send(b"E14"),
+                }
+            }
+            b'M' => {
+                let mut parts = args.splitn(2, |b| *b == b':');
+                let header = parts.next().unwrap_or(&[]);
+                let data = parts.next().and_then(decode_hex);
+                let address = header
+                    .split(|b| *b == b',')
+                    .next()
+                    .and_then(parse_hex)
+                    .map(|a| a as usize);
+                match (address, data) {
+                    (Some(address), Some(data)) => match address.checked_add(data.len()) {
+                        Some(end) if end <= processor.ram().len() => {
+                            for (i, value) in data.iter().enumerate() {
+                                processor.write_ram(address + i, *value);
+                            }
+                            self.send(b"OK")
+                        }
+                        Some(_) => self.send(b"E14"),
+                        None => self.send(b"E01"),
+                    },
+                    _ => self.send(b"E14"),
+                }
+            }
+            b'Z' | b'z' => {
+                let mut parts = args.split(|b| *b == b',');
+                let breakpoint_type = parts.next();
+                let address = parts.next().and_then(parse_hex);
+                match (breakpoint_type, address) {
+                    // software and hardware breakpoints behave the same here
+                    (Some(b"0"), Some(address)) | (Some(b"1"), Some(address)) => {
+                        if kind == b'Z' {
+                            self.breakpoints.insert(address as u16);
+                        } else {
+                            self.breakpoints.remove(&(address as u16));
+                        }
+                        self.send(b"OK")
+                    }
+                    _ => self.send(b""),
+                }
+            }
+            b's' => {
+                if let Some(address) = parse_hex(args) {
+                    let mut registers = processor.registers();
+                    registers.pc = address as u16;
+                    processor.set_registers(registers);
+                }
+                match processor.try_tick() {
+                    Ok(()) => self.send_stop(SIGTRAP),
+                    Err(error) => self.send_stop(signal_for(error.kind)),
+                }
+            }
+            b'c' => {
+                if let Some(address) = parse_hex(args) {
+                    let mut registers = processor.registers();
+                    registers.pc = address as u16;
+                    processor.set_registers(registers);
+                }
+                self.status = Status::Running;
+                self.resuming = true;
+                Ok(())
+            }
+            b'D' => {
+                self.send(b"OK")?;
+                self.status = Status::Detached;
+                Ok(())
+            }
+            b'k' => {
+                self.status = Status::Detached;
+                Ok(())
+            }
+            b'H' => self.send(b"OK"),
+            b'q' => self.handle_query(args),
+            _ => self.send(b""),
+        }
+    }
+
+    fn handle_query(&mut self, query: &[u8]) -> io::Result<()> {
+        const XFER_TARGET: &[u8] = b"Xfer:features:read:target.xml:";
+
+        if query.starts_with(b"Supported") {
+            self.send(b"PacketSize=1000;qXfer:features:read+")
+        } else if query == b"Attached" {
+            self.send(b"1")
+        } else if query == b"C" {
+            self.send(b"QC1")
+        } else if query == b"fThreadInfo" {
+            self.send(b"m1")
+        } else if query == b"sThreadInfo" {
+            self.send(b"l")
+        } else if let Some(range) = query.strip_prefix(XFER_TARGET) {
+            let mut parts = range.split(|b| *b == b',');
+            let offset = parts.next().and_then(parse_hex).map(|o| o as usize);
+            let length = parts.next().and_then(parse_hex).map(|l| l as usize);
+            match (offset, length) {
+                (Some(offset), Some(length)) => {
+                    let xml = target_xml();
+                    let xml = xml.as_bytes();
+                    let start = offset.min(xml.len());
+                    let end = start.saturating_add(length).min(xml.len());
+                    let marker = if end == xml.len() { b'l' } else { b'm' };
+                    let mut reply = vec![marker];
+                    reply.extend_from_slice(&xml[start..end]);
+                    self.send(&reply)
+                }
+                _ => self.send(b"E01"),
+            }
+        } else {
+            self.send(b"")
+        }
+    }
+
+    fn stop(&mut self, signal: u8) -> io::Result<()> {
+        self.status = Status::Halted;
+        self.send_stop(signal)
+    }
+
+    fn send_stop(&mut self, signal: u8) -> io::Result<()> {
+        self.send(format!("S{:02x}", signal).as_bytes())
+    }
+
+    fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut packet = Vec::with_capacity(data.len() + 4);
+        packet.push(b'$');
+        packet.extend_from_slice(data);
+        packet.extend_from_slice(format!("#{:02x}", checksum(data)).as_bytes());
+        self.send_raw(&packet)
+    }
+
+    fn send_raw(&mut self, data: &[u8]) -> io::Result<()> {
+        // replies are small, so just block until they're out
+        self.stream.set_nonblocking(false)?;
+        let result = self.stream.write_all(data);
+        self.stream.set_nonblocking(true)?;
+        result
+    }
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+fn signal_for(kind: ErrorKind) -> u8 {
+    match kind {
+        ErrorKind::InvalidOpcode | ErrorKind::InvalidKey(_) => SIGILL,
+        _ => SIGSEGV,
+    }
+}
+
+fn register_size(n: usize) -> usize {
+    match n {
+        REG_I | REG_PC => 2,
+        _ => 1,
+    }
+}
+
+fn register_bytes(processor: &Processor) -> Vec<u8> {
+    let registers = processor.registers();
+    let mut bytes = registers.v.to_vec();
+    bytes.extend_from_slice(&registers.i.to_le_bytes());
+    bytes.extend_from_slice(&registers.pc.to_le_bytes());
+    bytes.push(registers.sp as u8);
+    bytes.push(registers.dt);
+    bytes.push(registers.st);
+    bytes
+}
+
+fn set_register(registers: &mut crate::Registers, n: usize, value: &[u8]) {
+    match n {
+        REG_I => registers.i = u16::from_le_bytes([value[0], value[1]]),
+        REG_PC => registers.pc = u16::from_le_bytes([value[0], value[1]]),
+        REG_SP => registers.sp = value[0] as u16,
+        REG_DT => registers.dt = value[0],
+        REG_ST => registers.st = value[0],
+        _ => registers.v[n] = value[0],
+    }
+}
+
+fn target_xml() -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\"?>\
+         <!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+         <target version=\"1.0\"><feature name=\"org.chip8.core\">",
+    );
+    for n in 0..NUM_REGS {
+        xml.push_str(&format!(
+            "<reg name=\"v{:x}\" bitsize=\"8\" type=\"uint8\" regnum=\"{}\"/>",
+            n, n
+        ));
+    }
+    xml.push_str("<reg name=\"i\" bitsize=\"16\" type=\"data_ptr\"/>");
+    xml.push_str("<reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\"/>");
+    xml.push_str("<reg name=\"sp\" bitsize=\"8\" type=\"uint8\"/>");
+    xml.push_str("<reg name=\"dt\" bitsize=\"8\" type=\"uint8\"/>");
+    xml.push_str("<reg name=\"st\" bitsize=\"8\" type=\"uint8\"/>");
+    xml.push_str("</feature></target>");
+    xml
+}
+
+fn parse_hex(digits: &[u8]) -> Option<u64> {
+    number::parse_hex(std::str::from_utf8(digits).ok()?)
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode_hex(digits: &[u8]) -> Option<Vec<u8>> {
+    if !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    digits
+        .chunks(2)
+        .map(|pair| parse_hex(pair).map(|b| b as u8))
+        .collect()
+}
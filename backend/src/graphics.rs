@@ -0,0 +1,108 @@
+// embedded-graphics adapters for driving panels and keypads directly
+//
+// Framebuffer draws the screen onto any DrawTarget, each CHIP-8 pixel
+// becoming a `scale` x `scale` block, e.g. scale 2 fills a 128x64 OLED.
+// Neither the framebuffer nor the keypad scan allocate.
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::{Dimensions, Point, Size};
+use embedded_graphics::pixelcolor::PixelColor;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::Drawable;
+
+use crate::constants::{NUM_KEYS, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::{Bus, Processor};
+
+pub struct Framebuffer<'a, C> {
+    display: &'a [bool],
+    on: C,
+    off: C,
+    scale: u32,
+    offset: Point,
+}
+
+impl<'a, C: PixelColor> Framebuffer<'a, C> {
+    pub fn new<B: Bus>(processor: &'a Processor<B>, on: C, off: C) -> Self {
+        Self {
+            display: processor.get_display(),
+            on,
+            off,
+            scale: 1,
+            offset: Point::zero(),
+        }
+    }
+
+    // At least 1
+    pub fn scale(mut self, scale: u32) -> Self {
+        self.scale = scale.max(1);
+        self
+    }
+
+    // Position of the top left corner on the target
+    pub fn offset(mut self, offset: Point) -> Self {
+        self.offset = offset;
+        self
+    }
+}
+
+impl<C> Dimensions for Framebuffer<'_, C> {
+    fn bounding_box(&self) -> Rectangle {
+        Rectangle::new(
+            self.offset,
+            Size::new(
+                SCREEN_WIDTH as u32 * self.scale,
+                SCREEN_HEIGHT as u32 * self.scale,
+            ),
+        )
+    }
+}
+
+impl<C: PixelColor> Drawable for Framebuffer<'_, C> {
+    type Color = C;
+    type Output = ();
+
+    // Every pixel is written, so the target doesn't need clearing first
+    fn draw<D>(&self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = C>,
+    {
+        let scale = self.scale as usize;
+        let width = SCREEN_WIDTH * scale;
+        let height = SCREEN_HEIGHT * scale;
+
+        let colors = (0..width * height).map(|index| {
+            let x = index % width / scale;
+            let y = index / width / scale;
+            if self.display[x + SCREEN_WIDTH * y] {
+                self.on
+            } else {
+                self.off
+            }
+        });
+        target.fill_contiguous(&self.bounding_box(), colors)
+    }
+}
+
+// Key at each row and column of the COSMAC VIP's hex keypad
+pub const VIP_KEYPAD: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+// Reads a 4x4 key matrix, `is_pressed(row, column)` typically drives the
+// row pin and samples the column pin. Keys outside 0x0-0xF are ignored
+pub fn scan_keypad<B, F>(processor: &mut Processor<B>, layout: &[[u8; 4]; 4], mut is_pressed: F)
+where
+    B: Bus,
+    F: FnMut(usize, usize) -> bool,
+{
+    for (row, keys) in layout.iter().enumerate() {
+        for (column, key) in keys.iter().enumerate() {
+            if (*key as usize) < NUM_KEYS {
+                processor.keypress(*key as usize, is_pressed(row, column));
+            }
+        }
+    }
+}
@@ -1,18 +1,89 @@
+// Without the std feature the crate is no_std and never allocates, which
+// leaves out tracing, profiling, debugging and the other tooling modules
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+pub mod asm;
+#[cfg(feature = "std")]
+pub mod cheats;
+pub mod bus;
+pub mod cdp1802;
+pub mod chip8x;
 mod constants;
+pub mod decode;
+pub mod disasm;
+#[cfg(feature = "std")]
+pub mod env;
+mod error;
+#[cfg(feature = "std")]
+pub mod gdb;
+#[cfg(feature = "std")]
+pub mod manifest;
+#[cfg(feature = "std")]
+mod number;
+#[cfg(feature = "embedded-graphics")]
+pub mod graphics;
+pub mod machine;
+#[cfg(feature = "std")]
+pub mod profile;
+#[cfg(feature = "std")]
+mod recompiler;
+mod rng;
+mod state;
+#[cfg(feature = "std")]
+pub mod testrom;
+#[cfg(feature = "std")]
+pub mod trace;
+#[cfg(feature = "std")]
+pub mod tracediff;
+pub mod vip;
 
 pub mod display_constants {
     pub use super::constants::{SCREEN_HEIGHT, SCREEN_WIDTH};
 }
 
-use crate::constants::*;
+pub mod memory_constants {
+    pub use super::constants::{RAM_SIZE, START_ADDRESS};
+}
+
+pub mod input_constants {
+    pub use super::constants::NUM_KEYS;
+}
+
+pub use crate::bus::{Bus, Ram};
+pub use crate::error::{ErrorKind, ExecutionError, LoadError};
+pub use crate::state::{StateError, STATE_SIZE};
 
-use rand::random;
+use crate::cdp1802::Cdp1802;
+use crate::chip8x::{Chip8x, Color};
+use crate::constants::*;
+#[cfg(feature = "std")]
+use crate::decode::DecodeCache;
+use crate::decode::{decode, Instruction};
+#[cfg(feature = "std")]
+use crate::profile::Profile;
+#[cfg(feature = "std")]
+use crate::recompiler::BlockCache;
+use crate::rng::Rng;
+#[cfg(feature = "std")]
+use crate::trace::{TraceStep, Tracer};
+
+// Copy of the CPU registers, used for tracing and debugging
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Registers {
+    pub pc: u16,
+    pub v: [u8; NUM_REGS],
+    pub i: u16,
+    pub sp: u16,
+    pub dt: u8,
+    pub st: u8,
+}
 
-// TODO add flags for runtime errors caused
-//      by bugs in the input ROM (should be similar to how screen is used)
-pub struct Processor {
-    pc: u16, // program counter
-    ram: [u8; RAM_SIZE],
+// Generic over the memory it runs against, plain 4 KB RAM by default
+pub struct Processor<B: Bus = Ram> {
+    pc: u16,    // program counter
+    start: u16, // where load puts the ROM and the PC starts
+    bus: B,
     screen: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
     sound: bool,
     v_reg: [u8; NUM_REGS],
@@ -20,15 +91,89 @@ pub struct Processor {
     sp: u16, // stack pointer
     stack: [u16; STACK_SIZE],
     keys: [bool; NUM_KEYS],
-    dt: u8, // delay timer
-    st: u8, // sound timer
+    dt: u8,      // delay timer
+    st: u8,      // sound timer
+    rng: Rng,    // source for CXNN
+    cdp1802: Option<Cdp1802>, // runs 0NNN when set
+    chip8x: Option<Chip8x>,   // colors and second keypad when set
+    #[cfg(feature = "std")]
+    tracer: Option<Box<dyn Tracer + Send>>,
+    #[cfg(feature = "std")]
+    profile: Option<Box<Profile>>,
+    #[cfg(feature = "std")]
+    cache: Option<Box<DecodeCache>>,
+    #[cfg(feature = "std")]
+    blocks: Option<Box<BlockCache<B>>>,
+}
+
+#[cfg(feature = "std")]
+impl Default for Processor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The copy starts without a tracer or profile, everything else including
+// the random number generator's position and the decode and block caches
+// is duplicated
+impl<B: Bus + Clone> Clone for Processor<B> {
+    fn clone(&self) -> Self {
+        Self {
+            pc: self.pc,
+            start: self.start,
+            bus: self.bus.clone(),
+            screen: self.screen,
+            sound: self.sound,
+            v_reg: self.v_reg,
+            i_reg: self.i_reg,
+            sp: self.sp,
+            stack: self.stack,
+            keys: self.keys,
+            dt: self.dt,
+            st: self.st,
+            rng: self.rng.clone(),
+            cdp1802: self.cdp1802,
+            chip8x: self.chip8x,
+            #[cfg(feature = "std")]
+            tracer: None,
+            #[cfg(feature = "std")]
+            profile: None,
+            #[cfg(feature = "std")]
+            cache: self.cache.clone(),
+            #[cfg(feature = "std")]
+            blocks: self.blocks.clone(),
+        }
+    }
 }
 
 impl Processor {
+    // Seeds CXNN from the OS, without std use with_seed
+    #[cfg(feature = "std")]
     pub fn new() -> Self {
+        Self::with_rng(Ram::new(), Rng::from_entropy())
+    }
+
+    // Same seed and same inputs always give the same run
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_bus(Ram::new(), seed)
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        self.bus.as_slice()
+    }
+}
+
+impl<B: Bus> Processor<B> {
+    // The font is poked into the bus, anything else on it is left alone
+    pub fn with_bus(bus: B, seed: u64) -> Self {
+        Self::with_rng(bus, Rng::from_seed(seed))
+    }
+
+    fn with_rng(bus: B, rng: Rng) -> Self {
         let mut new_processor = Self {
             pc: START_ADDRESS,
-            ram: [0; RAM_SIZE],
+            start: START_ADDRESS,
+            bus,
             screen: [false; SCREEN_WIDTH * SCREEN_HEIGHT],
             sound: false,
             v_reg: [0; NUM_REGS],
@@ -38,14 +183,104 @@ impl Processor {
             keys: [false; NUM_KEYS],
             dt: 0,
             st: 0,
+            rng,
+            cdp1802: None,
+            chip8x: None,
+            #[cfg(feature = "std")]
+            tracer: None,
+            #[cfg(feature = "std")]
+            profile: None,
+            #[cfg(feature = "std")]
+            cache: None,
+            #[cfg(feature = "std")]
+            blocks: None,
         };
-        new_processor.ram[..DIGIT_SPRITES_SIZE].copy_from_slice(&DIGIT_SPRITES);
+        new_processor.load_font();
         new_processor
     }
 
+    fn load_font(&mut self) {
+        for (address, byte) in DIGIT_SPRITES.iter().enumerate() {
+            self.bus.poke(address, *byte);
+        }
+    }
+
+    pub fn bus(&self) -> &B {
+        &self.bus
+    }
+
+    // Drops the decode cache, since anything could be written through it
+    pub fn bus_mut(&mut self) -> &mut B {
+        self.clear_cache();
+        &mut self.bus
+    }
+
+    // Caches decoded instructions by address so hot loops skip the fetch
+    // and decode. Writes through the processor invalidate entries, but
+    // buses that change memory on their own (e.g. memory-mapped devices
+    // under the program) need it off, and cached fetches don't reach
+    // the bus
+    #[cfg(feature = "std")]
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.cache = enabled.then(|| Box::new(DecodeCache::new(self.bus.size())));
+    }
+
+    // Compiles straight-line runs of instructions into blocks that `run`
+    // executes without fetching and decoding each instruction. Same
+    // caveats as the decode cache, and tracing or profiling falls back to
+    // interpreting
+    #[cfg(feature = "std")]
+    pub fn set_recompiler(&mut self, enabled: bool) {
+        self.blocks = enabled.then(|| Box::new(BlockCache::new(self.bus.size())));
+    }
+
+    fn invalidate(&mut self, _address: usize) {
+        #[cfg(feature = "std")]
+        {
+            if let Some(cache) = self.cache.as_mut() {
+                cache.invalidate(_address);
+            }
+            if let Some(blocks) = self.blocks.as_mut() {
+                blocks.invalidate(_address);
+            }
+        }
+    }
+
+    fn clear_cache(&mut self) {
+        #[cfg(feature = "std")]
+        {
+            if let Some(cache) = self.cache.as_mut() {
+                cache.clear();
+            }
+            if let Some(blocks) = self.blocks.as_mut() {
+                blocks.clear();
+            }
+        }
+    }
+
+    // The profile's call stack stops matching the processor's
+    fn reset_calls(&mut self) {
+        #[cfg(feature = "std")]
+        if let Some(profile) = self.profile.as_mut() {
+            profile.reset_calls();
+        }
+    }
+
+    // Instruction writes, which may be refused by the bus
+    fn write(&mut self, address: usize, value: u8) -> Result<(), ErrorKind> {
+        self.bus.write(address, value)?;
+        self.invalidate(address);
+        Ok(())
+    }
+
+    // Clears the whole bus through poke, like a power cycle
     pub fn reset(&mut self) {
-        self.pc = START_ADDRESS;
-        self.ram = [0; RAM_SIZE];
+        self.clear_cache();
+        self.reset_calls();
+        self.pc = self.start;
+        for address in 0..self.bus.size() {
+            self.bus.poke(address, 0);
+        }
         self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
         self.sound = false;
         self.v_reg = [0; NUM_REGS];
@@ -55,26 +290,210 @@ impl Processor {
         self.keys = [false; NUM_KEYS];
         self.dt = 0;
         self.st = 0;
-        self.ram[..DIGIT_SPRITES_SIZE].copy_from_slice(&DIGIT_SPRITES);
+        if self.chip8x.is_some() {
+            self.chip8x = Some(Chip8x::new());
+        }
+        self.load_font();
     }
 
-    // TODO: behavior for overflow?
-    fn push(&mut self, value: u16) {
+    fn push(&mut self, value: u16) -> Result<(), ErrorKind> {
+        if self.sp as usize >= STACK_SIZE {
+            return Err(ErrorKind::StackOverflow);
+        }
         self.stack[self.sp as usize] = value;
         self.sp += 1;
+        Ok(())
     }
 
-    // TODO: behavior for underflow?
-    fn pop(&mut self) -> u16 {
+    fn pop(&mut self) -> Result<u16, ErrorKind> {
+        if self.sp == 0 {
+            return Err(ErrorKind::StackUnderflow);
+        }
         self.sp -= 1;
-        self.stack[self.sp as usize]
+        Ok(self.stack[self.sp as usize])
     }
 
+    // Panics if the ROM does something invalid, see try_tick
     pub fn tick(&mut self) {
-        // Fetch
-        let opcode = self.fetch();
-        // Decode and Execute
-        self.execute(opcode);
+        if let Err(error) = self.try_tick() {
+            panic!("{}", error);
+        }
+    }
+
+    // Executes `ticks` instructions, through compiled blocks when the
+    // recompiler is on
+    pub fn run(&mut self, ticks: usize) -> Result<(), ExecutionError> {
+        #[cfg(feature = "std")]
+        if self.blocks.is_some() && self.tracer.is_none() && self.profile.is_none() {
+            return self.run_blocks(ticks);
+        }
+
+        for _ in 0..ticks {
+            self.try_tick()?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    fn run_blocks(&mut self, ticks: usize) -> Result<(), ExecutionError> {
+        let mut remaining = ticks;
+        while remaining > 0 {
+            let Some(blocks) = self.blocks.as_mut() else {
+                break;
+            };
+            let start = self.pc as usize;
+            let block = match blocks.get(start) {
+                Some(block) => block,
+                None => match blocks.compile(&self.bus, start) {
+                    Some(block) => block,
+                    // let the interpreter report the bad PC
+                    None => {
+                        self.try_tick()?;
+                        remaining -= 1;
+                        continue;
+                    }
+                },
+            };
+            let generation = blocks.generation();
+
+            for (index, op) in block.ops.iter().take(remaining).enumerate() {
+                let address = (block.start + index * 2) as u16;
+                let error = |kind| ExecutionError {
+                    address,
+                    opcode: op.opcode,
+                    kind,
+                };
+                self.pc = address
+                    .checked_add(2)
+                    .ok_or(error(ErrorKind::PcOutOfBounds))?;
+                remaining -= 1;
+                (op.run)(self, op).map_err(error)?;
+                // the block was changed under us
+                if self.blocks.as_ref().map(|blocks| blocks.generation()) != Some(generation) {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn try_tick(&mut self) -> Result<(), ExecutionError> {
+        let address = self.pc;
+        #[cfg(feature = "std")]
+        let before = self.tracer.as_ref().map(|_| self.registers());
+
+        // Fetch and Decode
+        let result = match self.fetch() {
+            // Execute
+            Ok((opcode, instruction)) => self
+                .execute(instruction)
+                .map(|_| opcode)
+                .map_err(|kind| ExecutionError {
+                    address,
+                    opcode,
+                    kind,
+                }),
+            Err(kind) => Err(ExecutionError {
+                address,
+                opcode: 0,
+                kind,
+            }),
+        };
+
+        #[cfg(feature = "std")]
+        {
+            if let (Ok(opcode), Some(profile)) = (result, self.profile.as_mut()) {
+                profile.record(address, opcode);
+            }
+
+            if let Some(before) = before {
+                let after = self.registers();
+                if let Some(tracer) = self.tracer.as_mut() {
+                    match result {
+                        Ok(opcode) => tracer.record(&TraceStep {
+                            address,
+                            opcode,
+                            before,
+                            after,
+                        }),
+                        Err(ref error) => tracer.error(error),
+                    }
+                }
+            }
+        }
+
+        result.map(|_| ())
+    }
+
+    #[cfg(feature = "std")]
+    pub fn set_tracer(&mut self, tracer: Box<dyn Tracer + Send>) {
+        self.tracer = Some(tracer);
+    }
+
+    #[cfg(feature = "std")]
+    pub fn take_tracer(&mut self) -> Option<Box<dyn Tracer + Send>> {
+        self.tracer.take()
+    }
+
+    // Starts counting executions from scratch
+    #[cfg(feature = "std")]
+    pub fn start_profiling(&mut self) {
+        let profile = Profile::with_size(self.bus.size()).with_chip8x(self.chip8x.is_some());
+        self.profile = Some(Box::new(profile));
+    }
+
+    #[cfg(feature = "std")]
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_deref()
+    }
+
+    #[cfg(feature = "std")]
+    pub fn take_profile(&mut self) -> Option<Box<Profile>> {
+        self.profile.take()
+    }
+
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = Rng::from_seed(seed);
+    }
+
+    pub fn registers(&self) -> Registers {
+        Registers {
+            pc: self.pc,
+            v: self.v_reg,
+            i: self.i_reg,
+            sp: self.sp,
+            dt: self.dt,
+            st: self.st,
+        }
+    }
+
+    // SP is clamped to the stack size
+    pub fn set_registers(&mut self, registers: Registers) {
+        self.pc = registers.pc;
+        self.v_reg = registers.v;
+        self.i_reg = registers.i;
+        self.sp = registers.sp.min(STACK_SIZE as u16);
+        self.dt = registers.dt;
+        self.st = registers.st;
+        self.reset_calls();
+    }
+
+    // Reads without side effects, None past the end of memory
+    pub fn read_ram(&self, address: usize) -> Option<u8> {
+        (address < self.bus.size()).then(|| self.bus.peek(address))
+    }
+
+    // Writes past the end of memory are ignored, protection is bypassed
+    pub fn write_ram(&mut self, address: usize, value: u8) {
+        if address < self.bus.size() {
+            self.bus.poke(address, value);
+            self.invalidate(address);
+        }
+    }
+
+    // Only the occupied part of the stack
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.sp as usize]
     }
 
     pub fn get_display(&self) -> &[bool] {
@@ -89,120 +508,268 @@ impl Processor {
         self.keys[index] = pressed;
     }
 
+    // Panics if the data doesn't fit in memory
     pub fn load(&mut self, data: &[u8]) {
-        let start = START_ADDRESS as usize;
-        let end = (START_ADDRESS as usize) + data.len();
-        self.ram[start..end].copy_from_slice(data);
+        if let Err(error) = self.try_load(data) {
+            panic!("{}", error);
+        }
+    }
+
+    // Copies the ROM to the start address, refusing ROMs that don't fit
+    pub fn try_load(&mut self, data: &[u8]) -> Result<(), LoadError> {
+        self.load_at(self.start, data)
     }
 
-    fn execute(&mut self, opcode: u16) {
-        let digit1 = (opcode & 0xF000) >> (3 * 4);
-        let digit2 = (opcode & 0x0F00) >> (2 * 4);
-        let digit3 = (opcode & 0x00F0) >> 4;
-        let digit4 = opcode & 0x000F;
+    // Copies data to any address, e.g. a data segment that a program
+    // expects at a fixed address. Doesn't move the PC
+    pub fn load_at(&mut self, address: u16, data: &[u8]) -> Result<(), LoadError> {
+        let address = address as usize;
+        let capacity = self.bus.size().saturating_sub(address);
+        if data.len() > capacity {
+            return Err(LoadError {
+                address,
+                len: data.len(),
+                capacity,
+            });
+        }
+        for (offset, byte) in data.iter().enumerate() {
+            self.bus.poke(address + offset, *byte);
+        }
+        self.clear_cache();
+        Ok(())
+    }
+
+    // Where ROMs load and the PC starts, 0x200 unless changed, e.g. 0x600
+    // for ETI 660 programs. Moves the PC there too, so set it before
+    // running
+    pub fn set_start_address(&mut self, address: u16) {
+        self.start = address;
+        self.pc = address;
+    }
+
+    pub fn start_address(&self) -> u16 {
+        self.start
+    }
+
+    // Runs 0NNN subroutines on an emulated CDP1802 like the COSMAC VIP,
+    // for hybrid programs. Off by default, which leaves 0NNN invalid
+    pub fn set_cdp1802(&mut self, enabled: bool) {
+        self.cdp1802 = enabled.then(Cdp1802::new);
+    }
+
+    // The 1802 keeps its registers between calls, some hybrid programs
+    // pass values in the free ones
+    pub fn cdp1802(&self) -> Option<&Cdp1802> {
+        self.cdp1802.as_ref()
+    }
+
+    // CHIP-8X for the VP-590 color board: 02A0 and BXYN color the screen,
+    // 5XY1 adds nibbles and EXF2 and EXF5 read a second keypad. BXYN takes
+    // the place of BNNN. Off by default. Turning it on or off moves the
+    // start address to 0x300 or back to 0x200, set another one after
+    pub fn set_chip8x(&mut self, enabled: bool) {
+        if enabled != self.chip8x.is_some() {
+            self.set_start_address(if enabled {
+                chip8x::START_ADDRESS
+            } else {
+                START_ADDRESS
+            });
+        }
+        self.chip8x = enabled.then(Chip8x::new);
+    }
+
+    // The colors to show the display in, with CHIP-8X on
+    pub fn chip8x(&self) -> Option<&Chip8x> {
+        self.chip8x.as_ref()
+    }
+
+    // Keys on CHIP-8X's second keypad, ignored when it's off
+    pub fn second_keypress(&mut self, index: usize, pressed: bool) {
+        if let Some(chip8x) = self.chip8x.as_mut() {
+            chip8x.keys[index] = pressed;
+        }
+    }
+
+    fn chip8x_or_invalid(&mut self) -> Result<&mut Chip8x, ErrorKind> {
+        self.chip8x.as_mut().ok_or(ErrorKind::InvalidOpcode)
+    }
+
+    // Lays the CHIP-8 state out the way the VIP interpreter keeps it, runs
+    // the subroutine and reads the state back. V0-VF sit at 0xEF0 and the
+    // display at 0xF00 a bit per pixel, with 4 KB of RAM. The subroutine
+    // runs with R3 as the PC and X = 2, and returns with SEP R4 (D4).
+    // RA holds I, R5 the CHIP-8 PC, R6 and R7 point at VX and VY, R8
+    // holds the delay timer high and the sound timer low and RB points at
+    // the display
+    fn machine_code(&mut self, nnn: u16) -> Result<(), ErrorKind> {
+        let Some(mut cpu) = self.cdp1802 else {
+            return Err(ErrorKind::InvalidOpcode);
+        };
+        let top = self.bus.size();
+        let v_registers = top - cdp1802::V_REGISTERS;
+        let display = top - cdp1802::DISPLAY;
+
+        for (i, value) in self.v_reg.iter().enumerate() {
+            self.bus.poke(v_registers + i, *value);
+        }
+        for (i, pixels) in self.screen.chunks(8).enumerate() {
+            let byte = pixels
+                .iter()
+                .fold(0, |byte, pixel| (byte << 1) | *pixel as u8);
+            self.bus.poke(display + i, byte);
+        }
+
+        // X and Y are nibbles of the address, as for any other opcode
+        let x = (nnn >> 8) as usize;
+        let y = (nnn >> 4 & 0xF) as usize;
+        cpu.r[2] = (top - cdp1802::STACK_TOP) as u16;
+        cpu.r[3] = nnn;
+        cpu.r[5] = self.pc;
+        cpu.r[6] = (v_registers + x) as u16;
+        cpu.r[7] = (v_registers + y) as u16;
+        cpu.r[8] = u16::from_be_bytes([self.dt, self.st]);
+        cpu.r[0xA] = self.i_reg;
+        cpu.r[0xB] = display as u16;
+        cpu.p = 3;
+        cpu.x = 2;
+
+        let mut result = Err(ErrorKind::MachineCodeTimeout(nnn));
+        for _ in 0..cdp1802::MAX_STEPS {
+            if let Err(error) = cpu.step(&mut self.bus, &mut ()) {
+                result = Err(error);
+                break;
+            }
+            if cpu.p == 4 {
+                result = Ok(());
+                break;
+            }
+        }
+        self.cdp1802 = Some(cpu);
+        // the subroutine could have written anywhere
+        self.clear_cache();
+        result?;
+
+        for (i, value) in self.v_reg.iter_mut().enumerate() {
+            *value = self.bus.peek(v_registers + i);
+        }
+        for (i, pixel) in self.screen.iter_mut().enumerate() {
+            *pixel = self.bus.peek(display + i / 8) & (0x80 >> (i % 8)) != 0;
+        }
+        let [dt, st] = cpu.r[8].to_be_bytes();
+        self.dt = dt;
+        self.st = st;
+        self.i_reg = cpu.r[0xA];
+        self.pc = cpu.r[5];
+        Ok(())
+    }
 
-        match (digit1, digit2, digit3, digit4) {
+    fn execute(&mut self, instruction: Instruction) -> Result<(), ErrorKind> {
+        match instruction {
             // Nop
-            (0, 0, 0, 0) => return,
+            Instruction::Nop => {}
 
             // Clear screen
-            (0, 0, 0xE, 0) => {
+            Instruction::Cls => {
                 self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
             }
 
             // Return from subroutine
-            (0, 0, 0xE, 0xE) => {
-                self.pc = self.pop();
+            Instruction::Ret => {
+                self.pc = self.pop()?;
+            }
+
+            // (02A0) CHIP-8X: Next background color
+            Instruction::Sys(0x2A0) if self.chip8x.is_some() => {
+                self.chip8x_or_invalid()?.cycle_background();
             }
 
+            // (0NNN) Call 1802 machine code at 0xNNN
+            //        Only with the CDP1802 on, invalid otherwise
+            Instruction::Sys(nnn) => self.machine_code(nnn)?,
+
             // (1NNN) Jump to address 0xNNN
-            (1, _, _, _) => {
-                self.pc = opcode & 0xFFF;
+            Instruction::Jp(nnn) => {
+                self.pc = nnn;
             }
 
             // (2NNN) Call 0xNNN
             //        Enter subroutine at 0xNNN, adding current PC to stack
             //        so we can return here
-            (2, _, _, _) => {
-                self.push(self.pc);
-                self.pc = opcode & 0xFFF;
+            Instruction::Call(nnn) => {
+                self.push(self.pc)?;
+                self.pc = nnn;
             }
 
             // (3XNN) Skip if VX == 0xNN
-            (3, _, _, _) => {
-                let x = digit2 as usize;
-                let nn = (opcode & 0xFF) as u8;
-                if self.v_reg[x] == nn {
-                    self.pc += 2;
+            Instruction::SeImm { x, nn } => {
+                if self.v_reg[x as usize] == nn {
+                    self.skip()?;
                 }
             }
 
             // (4XNN) Skip if VX != 0xNN
-            (4, _, _, _) => {
-                let x = digit2 as usize;
-                let nn = (opcode & 0xFF) as u8;
-                if self.v_reg[x] != nn {
-                    self.pc += 2
+            Instruction::SneImm { x, nn } => {
+                if self.v_reg[x as usize] != nn {
+                    self.skip()?;
                 }
             }
 
             // (5XY0) Skip if VX == VY
-            (5, _, _, 0) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
-                if self.v_reg[x] == self.v_reg[y] {
-                    self.pc += 2
+            Instruction::SeReg { x, y } => {
+                if self.v_reg[x as usize] == self.v_reg[y as usize] {
+                    self.skip()?;
                 }
             }
 
+            // (5XY1) CHIP-8X: VX += VY, a nibble at a time
+            //        Neither nibble carries into the other, VF is untouched
+            Instruction::AddNibbles { x, y } => {
+                self.chip8x_or_invalid()?;
+                let vx = self.v_reg[x as usize];
+                let vy = self.v_reg[y as usize];
+
+                let high = (vx & 0xF0).wrapping_add(vy & 0xF0);
+                let low = vx.wrapping_add(vy) & 0xF;
+                self.v_reg[x as usize] = high | low;
+            }
+
             // (6XNN) VX = 0xNN
-            (6, _, _, _) => {
-                let x = digit2 as usize;
-                let nn = (opcode & 0xFF) as u8;
-                self.v_reg[x] = nn;
+            Instruction::LdImm { x, nn } => {
+                self.v_reg[x as usize] = nn;
             }
 
             // (7XNN) VX += 0xNN
             //        Doesn't affect carry flag
-            (7, _, _, _) => {
-                let x = digit2 as usize;
-                let nn = (opcode & 0xFF) as u8;
+            Instruction::AddImm { x, nn } => {
+                let x = x as usize;
                 self.v_reg[x] = self.v_reg[x].wrapping_add(nn);
             }
 
             // (8XY0) VX = VY
-            (8, _, _, 0) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
-                self.v_reg[x] = self.v_reg[y];
+            Instruction::LdReg { x, y } => {
+                self.v_reg[x as usize] = self.v_reg[y as usize];
             }
 
             // (8XY1) VX |= VY
-            (8, _, _, 1) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
-                self.v_reg[x] |= self.v_reg[y];
+            Instruction::Or { x, y } => {
+                self.v_reg[x as usize] |= self.v_reg[y as usize];
             }
 
             // (8XY2) VX &= VY
-            (8, _, _, 2) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
-                self.v_reg[x] &= self.v_reg[y];
+            Instruction::And { x, y } => {
+                self.v_reg[x as usize] &= self.v_reg[y as usize];
             }
 
             // (8XY3) VX ^= VY
-            (8, _, _, 3) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
-                self.v_reg[x] ^= self.v_reg[y];
+            Instruction::Xor { x, y } => {
+                self.v_reg[x as usize] ^= self.v_reg[y as usize];
             }
 
             // (8XY4) VX += VY
             //        Sets VF if carry
-            (8, _, _, 4) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
+            Instruction::Add { x, y } => {
+                let x = x as usize;
+                let y = y as usize;
 
                 let (new_vx, carry) = self.v_reg[x].overflowing_add(self.v_reg[y]);
                 let new_vf = if carry { 1 } else { 0 };
@@ -213,9 +780,9 @@ impl Processor {
 
             // (8XY5) VX -= VY
             //        Clears VF if borrow
-            (8, _, _, 5) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
+            Instruction::Sub { x, y } => {
+                let x = x as usize;
+                let y = y as usize;
 
                 let (new_vx, carry) = self.v_reg[x].overflowing_sub(self.v_reg[y]);
                 let new_vf = if carry { 0 } else { 1 };
@@ -226,8 +793,8 @@ impl Processor {
 
             // (8XY6) VX >>= 1
             //        Stores dropped bit in VF
-            (8, _, _, 6) => {
-                let x = digit2 as usize;
+            Instruction::Shr { x } => {
+                let x = x as usize;
 
                 let dropped_bit = self.v_reg[x] & 1;
 
@@ -237,21 +804,21 @@ impl Processor {
 
             // (8XY7) VX = VY - VX
             //        Clears VF if borrow
-            (8, _, _, 7) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
+            Instruction::Subn { x, y } => {
+                let x = x as usize;
+                let y = y as usize;
 
                 let (new_vx, carry) = self.v_reg[y].overflowing_sub(self.v_reg[x]);
-                let new_vf = if carry { 1 } else { 0 };
+                let new_vf = if carry { 0 } else { 1 };
 
                 self.v_reg[x] = new_vx;
                 self.v_reg[0xF] = new_vf;
             }
 
-            // (8XYE) VX <<= VY
+            // (8XYE) VX <<= 1
             //        Store dropped bit in VF
-            (8, _, _, 0xE) => {
-                let x = digit2 as usize;
+            Instruction::Shl { x } => {
+                let x = x as usize;
 
                 let dropped_bit = (self.v_reg[x] >> 7) & 1;
 
@@ -260,53 +827,73 @@ impl Processor {
             }
 
             // (9XY0) Skip if VX != VY
-            (9, _, _, 0) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
-
-                if self.v_reg[x] != self.v_reg[y] {
-                    self.pc += 2
+            Instruction::SneReg { x, y } => {
+                if self.v_reg[x as usize] != self.v_reg[y as usize] {
+                    self.skip()?;
                 }
             }
 
             // (ANNN) I = 0xNNN
-            (0xA, _, _, _) => {
-                let nnn = opcode & 0xFFF;
-
+            Instruction::LdI(nnn) => {
                 self.i_reg = nnn;
             }
 
             // (BNNN) Jump to V0 + 0xNNN
-            (0xB, _, _, _) => {
-                let nnn = opcode & 0xFFF;
-
+            //        Not in CHIP-8X, which has BXYN instead
+            Instruction::JpV0(nnn) if self.chip8x.is_none() => {
                 self.pc = (self.v_reg[0] as u16) + nnn;
             }
 
-            // (CXNN) VX = rand() & 0xNN
-            (0xC, _, _, _) => {
-                let x = digit2 as usize;
-                let nn = (opcode & 0xFF) as u8;
+            // (BXYN) CHIP-8X: Set the foreground color to VY
+            //        VX's low nibble is the first zone column and its high
+            //        nibble how many more to color. With N = 0 V(X+1) picks
+            //        blocks of 4 lines the same way, otherwise N lines are
+            //        colored from line V(X+1)
+            Instruction::JpV0(nnn) => {
+                let x = (nnn >> 8) as usize;
+                let horizontal = self.v_reg[x];
+                let vertical = self.v_reg[(x + 1) % NUM_REGS];
+                let color = Color::from_bits(self.v_reg[(nnn >> 4 & 0xF) as usize]);
+                let n = (nnn & 0xF) as usize;
+
+                let column = (horizontal & 0xF) as usize;
+                let columns = (horizontal >> 4) as usize + 1;
+                let (line, lines) = if n == 0 {
+                    let blocks = (vertical >> 4) as usize + 1;
+                    let line = (vertical & 0xF) as usize * chip8x::BLOCK_HEIGHT;
+                    (line, blocks * chip8x::BLOCK_HEIGHT)
+                } else {
+                    (vertical as usize, n)
+                };
+                let chip8x = self.chip8x_or_invalid()?;
+                chip8x.fill(column, columns, line, lines, color);
+            }
 
-                let random_integer: u8 = random();
+            // (CXNN) VX = rand() & 0xNN
+            Instruction::Rnd { x, nn } => {
+                let random_integer = self.rng.next_u8();
 
-                self.v_reg[x] = random_integer & nn;
+                self.v_reg[x as usize] = random_integer & nn;
             }
 
             // (DXYN) Draw sprite at (VX, VY)
             //        Sprite is 0xN pixels tall, on/off based on value in I,
             //        VF set if any pixels flipped (from on to off)
-            (0xD, _, _, _) => {
+            Instruction::Drw { x, y, n } => {
                 // get coords where sprite will be drawn
-                let x_coord = self.v_reg[digit2 as usize] as u16;
-                let y_coord = self.v_reg[digit3 as usize] as u16;
-                let num_rows = digit4;
+                let x_coord = self.v_reg[x as usize] as u16;
+                let y_coord = self.v_reg[y as usize] as u16;
+                let num_rows = n as u16;
+
+                if num_rows > 0 {
+                    self.check_address(self.i_reg as usize + num_rows as usize - 1)?;
+                }
 
                 let mut flipped = false;
 
                 for y_line in 0..num_rows {
-                    let address = self.i_reg + y_line as u16;
-                    let pixels = self.ram[address as usize];
+                    let address = self.i_reg + y_line;
+                    let pixels = self.bus.read(address as usize);
 
                     for x_line in 0..8 {
                         // use mask to get current pixel's bit
@@ -335,34 +922,62 @@ impl Processor {
             }
 
             // (EX9E) Skip if key index in VX is pressed
-            (0xE, _, 9, 0xE) => {
-                let vx = self.v_reg[digit2 as usize];
-
-                if self.keys[vx as usize] {
-                    self.pc += 2;
+            Instruction::Skp { x } => {
+                let vx = self.v_reg[x as usize];
+
+                if *self
+                    .keys
+                    .get(vx as usize)
+                    .ok_or(ErrorKind::InvalidKey(vx))?
+                {
+                    self.skip()?;
                 }
             }
 
             // (EXA1) Skip if key index in VX isn't pressed
-            (0xE, _, 0xA, 1) => {
-                let vx = self.v_reg[digit2 as usize];
+            Instruction::Sknp { x } => {
+                let vx = self.v_reg[x as usize];
+
+                if !*self
+                    .keys
+                    .get(vx as usize)
+                    .ok_or(ErrorKind::InvalidKey(vx))?
+                {
+                    self.skip()?;
+                }
+            }
 
-                if !self.keys[vx as usize] {
-                    self.pc += 2;
+            // (EXF2) CHIP-8X: Skip if key index in VX is pressed on the
+            //        second keypad
+            Instruction::SkpPad2 { x } => {
+                let vx = self.v_reg[x as usize];
+                let keys = &self.chip8x_or_invalid()?.keys;
+
+                if *keys.get(vx as usize).ok_or(ErrorKind::InvalidKey(vx))? {
+                    self.skip()?;
                 }
             }
 
-            // (FX07) VX = Delay Timer
-            (0xF, _, 0, 7) => {
-                let x = digit2 as usize;
+            // (EXF5) CHIP-8X: Skip if key index in VX isn't pressed on
+            //        the second keypad
+            Instruction::SknpPad2 { x } => {
+                let vx = self.v_reg[x as usize];
+                let keys = &self.chip8x_or_invalid()?.keys;
 
-                self.v_reg[x] = self.dt;
+                if !*keys.get(vx as usize).ok_or(ErrorKind::InvalidKey(vx))? {
+                    self.skip()?;
+                }
+            }
+
+            // (FX07) VX = Delay Timer
+            Instruction::LdVxDt { x } => {
+                self.v_reg[x as usize] = self.dt;
             }
 
             // (FX0A) Waits for keypress, stores index in VX
             //        Blocking operation
-            (0xF, _, 0, 0xA) => {
-                let x = digit2 as usize;
+            Instruction::LdKey { x } => {
+                let x = x as usize;
 
                 let mut pressed = false;
 
@@ -381,80 +996,97 @@ impl Processor {
             }
 
             // (FX15) Delay Timer = VX
-            (0xF, _, 1, 5) => {
-                let x = digit2 as usize;
-
-                self.dt = self.v_reg[x];
+            Instruction::LdDtVx { x } => {
+                self.dt = self.v_reg[x as usize];
             }
 
             // (FX18) Sound Timer = VX
-            (0xF, _, 1, 8) => {
-                let x = digit2 as usize;
-
-                self.st = self.v_reg[x];
+            Instruction::LdStVx { x } => {
+                self.st = self.v_reg[x as usize];
             }
 
             // (FX1E) I += VX
-            (0xF, _, 1, 0xE) => {
-                let x = digit2 as usize;
-
-                self.i_reg = self.i_reg.wrapping_add(self.v_reg[x] as u16);
+            Instruction::AddI { x } => {
+                self.i_reg = self.i_reg.wrapping_add(self.v_reg[x as usize] as u16);
             }
 
             // (FX29) Set I to address of font character in VX
-            (0xF, _, 2, 9) => {
-                let x = digit2 as usize;
-
-                self.i_reg = (self.v_reg[x] as u16) * 5;
+            Instruction::LdFont { x } => {
+                self.i_reg = (self.v_reg[x as usize] as u16) * 5;
             }
 
             // (FX33) Stores BCD encoding of VX into I
-            (0xF, _, 3, 3) => {
-                let vx = self.v_reg[digit2 as usize];
+            Instruction::Bcd { x } => {
+                let vx = self.v_reg[x as usize];
 
                 let hundreds = (vx - vx % 100) / 100;
                 let ones = vx % 10;
                 let tens = (vx - 100 * hundreds - ones) / 10;
 
-                self.ram[self.i_reg as usize] = hundreds;
-                self.ram[(self.i_reg + 1) as usize] = tens;
-                self.ram[(self.i_reg + 2) as usize] = ones;
+                self.check_address(self.i_reg as usize + 2)?;
+                let address = self.i_reg as usize;
+
+                self.write(address, hundreds)?;
+                self.write(address + 1, tens)?;
+                self.write(address + 2, ones)?;
             }
 
             // (FX55) Stores V0 thru VX into RAM address starting at I
             //        Inclusive range
-            (0xF, _, 5, 5) => {
-                let x = digit2 as usize;
+            Instruction::Store { x } => {
+                let x = x as usize;
                 let i_reg_value = self.i_reg as usize;
+                self.check_address(i_reg_value + x)?;
 
                 for i in 0..=x {
-                    self.ram[i_reg_value + i] = self.v_reg[i];
+                    self.write(i_reg_value + i, self.v_reg[i])?;
                 }
             }
 
             // (FX65) Fills V0 thru VX with RAM values starting at address in I
             //        Inclusive
-            (0xF, _, 6, 5) => {
-                let x = digit2 as usize;
+            Instruction::Load { x } => {
+                let x = x as usize;
                 let i_reg_value = self.i_reg as usize;
+                self.check_address(i_reg_value + x)?;
 
                 for i in 0..=x {
-                    self.v_reg[i] = self.ram[i_reg_value + i];
+                    self.v_reg[i] = self.bus.read(i_reg_value + i);
                 }
             }
 
-            // TODO behavior for invalid opcode? interpreter will only reach
-            //      the bottom catch-all pattern if there is a bug in the ROM
-            (_, _, _, _) => unimplemented!("Unimplemented opcode: {}", opcode),
+            // Interpreter will only reach this if there is a bug in the ROM
+            Instruction::Invalid => return Err(ErrorKind::InvalidOpcode),
         }
+
+        Ok(())
+    }
+
+    // Steps over the next instruction. The PC doesn't wrap, so on a 64K
+    // bus there's nothing to skip to past 0xFFFF
+    pub(crate) fn skip(&mut self) -> Result<(), ErrorKind> {
+        self.pc = self.pc.checked_add(2).ok_or(ErrorKind::PcOutOfBounds)?;
+        Ok(())
     }
 
-    fn fetch(&mut self) -> u16 {
-        let higher_byte = self.ram[self.pc as usize] as u16;
-        let lower_byte = self.ram[(self.pc + 1) as usize] as u16;
-        let opcode = (higher_byte << 8) | lower_byte;
-        self.pc += 2;
-        opcode
+    fn fetch(&mut self) -> Result<(u16, Instruction), ErrorKind> {
+        if self.pc as usize + 1 >= self.bus.size() {
+            return Err(ErrorKind::PcOutOfBounds);
+        }
+        let address = self.pc as usize;
+        self.pc = self.pc.checked_add(2).ok_or(ErrorKind::PcOutOfBounds)?;
+
+        #[cfg(feature = "std")]
+        if let Some(cache) = self.cache.as_mut() {
+            if let Some(entry) = cache.get(address) {
+                return Ok(entry);
+            }
+            let opcode = read_opcode(&mut self.bus, address);
+            return Ok(cache.insert(address, opcode));
+        }
+
+        let opcode = read_opcode(&mut self.bus, address);
+        Ok((opcode, decode(opcode)))
     }
 
     pub fn tick_timers(&mut self) {
@@ -471,4 +1103,18 @@ impl Processor {
             self.st -= 1;
         }
     }
+
+    fn check_address(&self, address: usize) -> Result<usize, ErrorKind> {
+        if address < self.bus.size() {
+            Ok(address)
+        } else {
+            Err(ErrorKind::MemoryOutOfBounds(address))
+        }
+    }
+}
+
+fn read_opcode<B: Bus>(bus: &mut B, address: usize) -> u16 {
+    let higher_byte = bus.read(address) as u16;
+    let lower_byte = bus.read(address + 1) as u16;
+    (higher_byte << 8) | lower_byte
 }
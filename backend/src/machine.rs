@@ -0,0 +1,264 @@
+// Frontend-agnostic emulation loop
+//
+// A frontend provides somewhere to show the screen, something that beeps
+// and a source of key presses, and Machine does the rest: each frame it
+// polls input, runs the processor, counts the timers down and hands the
+// results back. `run` also paces frames at 60 Hz for frontends that
+// don't block on vsync.
+//
+// It drives anything that implements Core, usually a Processor but also a
+// whole emulated COSMAC VIP.
+
+use crate::bus::Bus;
+use crate::chip8x::Chip8x;
+use crate::constants::NUM_KEYS;
+use crate::vip::Vip;
+use crate::{ExecutionError, Processor};
+
+const TICKS_PER_FRAME: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Continue,
+    Quit,
+}
+
+pub trait VideoSink {
+    // Called once per frame with the whole screen, row major
+    fn present(&mut self, display: &[bool]);
+
+    // Called instead of present when the core has colors, e.g. CHIP-8X.
+    // Sinks that only show monochrome can leave it be
+    fn present_colors(&mut self, display: &[bool], _colors: &Chip8x) {
+        self.present(display);
+    }
+}
+
+pub trait AudioSink {
+    // Called when the beeper turns on or off
+    fn set_beeping(&mut self, beeping: bool);
+}
+
+pub trait InputSource {
+    // Called at the start of every frame, updates the held keys.
+    // Returning Quit stops `run`
+    fn poll(&mut self, keys: &mut [bool; NUM_KEYS]) -> Control;
+
+    // Called right after poll with CHIP-8X's second keypad. Sources with
+    // one keypad can leave it be
+    fn poll_second(&mut self, _keys: &mut [bool; NUM_KEYS]) {}
+}
+
+// The display, sound and key API Machine runs a frame through
+pub trait Core {
+    fn keypress(&mut self, index: usize, pressed: bool);
+
+    // Keys on a second keypad, if there is one
+    fn second_keypress(&mut self, _index: usize, _pressed: bool) {}
+
+    fn run(&mut self, ticks: usize) -> Result<(), ExecutionError>;
+
+    fn tick_timers(&mut self);
+
+    fn get_display(&self) -> &[bool];
+
+    fn get_sound(&self) -> bool;
+
+    // How to color the display, if it has colors
+    fn get_colors(&self) -> Option<&Chip8x> {
+        None
+    }
+}
+
+impl<B: Bus> Core for Processor<B> {
+    fn keypress(&mut self, index: usize, pressed: bool) {
+        Processor::keypress(self, index, pressed)
+    }
+
+    fn second_keypress(&mut self, index: usize, pressed: bool) {
+        Processor::second_keypress(self, index, pressed)
+    }
+
+    fn run(&mut self, ticks: usize) -> Result<(), ExecutionError> {
+        Processor::run(self, ticks)
+    }
+
+    fn tick_timers(&mut self) {
+        Processor::tick_timers(self)
+    }
+
+    fn get_display(&self) -> &[bool] {
+        Processor::get_display(self)
+    }
+
+    fn get_sound(&self) -> bool {
+        Processor::get_sound(self)
+    }
+
+    fn get_colors(&self) -> Option<&Chip8x> {
+        Processor::chip8x(self)
+    }
+}
+
+impl Core for Vip {
+    fn keypress(&mut self, index: usize, pressed: bool) {
+        Vip::keypress(self, index, pressed)
+    }
+
+    fn run(&mut self, ticks: usize) -> Result<(), ExecutionError> {
+        Vip::run(self, ticks)
+    }
+
+    fn tick_timers(&mut self) {
+        Vip::tick_timers(self)
+    }
+
+    fn get_display(&self) -> &[bool] {
+        Vip::get_display(self)
+    }
+
+    fn get_sound(&self) -> bool {
+        Vip::get_sound(self)
+    }
+}
+
+// For frontends without sound
+impl AudioSink for () {
+    fn set_beeping(&mut self, _beeping: bool) {}
+}
+
+pub struct Machine<V, A, I, C: Core = Processor> {
+    processor: C,
+    video: V,
+    audio: A,
+    input: I,
+    keys: [bool; NUM_KEYS],
+    second_keys: [bool; NUM_KEYS],
+    beeping: bool,
+    ticks_per_frame: usize,
+}
+
+impl<V, A, I, C> Machine<V, A, I, C>
+where
+    V: VideoSink,
+    A: AudioSink,
+    I: InputSource,
+    C: Core,
+{
+    pub fn new(processor: C, video: V, audio: A, input: I) -> Self {
+        Self {
+            processor,
+            video,
+            audio,
+            input,
+            keys: [false; NUM_KEYS],
+            second_keys: [false; NUM_KEYS],
+            beeping: false,
+            ticks_per_frame: TICKS_PER_FRAME,
+        }
+    }
+
+    pub fn with_ticks_per_frame(mut self, ticks: usize) -> Self {
+        self.ticks_per_frame = ticks;
+        self
+    }
+
+    pub fn processor(&self) -> &C {
+        &self.processor
+    }
+
+    pub fn processor_mut(&mut self) -> &mut C {
+        &mut self.processor
+    }
+
+    pub fn video(&self) -> &V {
+        &self.video
+    }
+
+    pub fn audio(&self) -> &A {
+        &self.audio
+    }
+
+    pub fn input(&self) -> &I {
+        &self.input
+    }
+
+    pub fn into_parts(self) -> (C, V, A, I) {
+        (self.processor, self.video, self.audio, self.input)
+    }
+
+    pub fn run_frame(&mut self) -> Result<Control, ExecutionError> {
+        self.run_frame_with(|processor, ticks| {
+            processor.run(ticks)?;
+            Ok(true)
+        })
+    }
+
+    // Like run_frame, but `execute` runs the instructions, e.g. through a
+    // debugger. It gets the ticks per frame and returns whether the
+    // processor ran, the timers stay frozen when it didn't
+    pub fn run_frame_with<F>(&mut self, execute: F) -> Result<Control, ExecutionError>
+    where
+        F: FnOnce(&mut C, usize) -> Result<bool, ExecutionError>,
+    {
+        let control = self.input.poll(&mut self.keys);
+        self.input.poll_second(&mut self.second_keys);
+        for (key, pressed) in self.keys.iter().enumerate() {
+            self.processor.keypress(key, *pressed);
+        }
+        for (key, pressed) in self.second_keys.iter().enumerate() {
+            self.processor.second_keypress(key, *pressed);
+        }
+        if control == Control::Quit {
+            return Ok(control);
+        }
+
+        if execute(&mut self.processor, self.ticks_per_frame)? {
+            self.processor.tick_timers();
+        }
+
+        let display = self.processor.get_display();
+        match self.processor.get_colors() {
+            Some(colors) => self.video.present_colors(display, colors),
+            None => self.video.present(display),
+        }
+        let beeping = self.processor.get_sound();
+        if beeping != self.beeping {
+            self.audio.set_beeping(beeping);
+            self.beeping = beeping;
+        }
+        Ok(Control::Continue)
+    }
+
+    // Runs frames at 60 Hz until the input source quits
+    #[cfg(feature = "std")]
+    pub fn run(&mut self) -> Result<(), ExecutionError> {
+        self.run_with(|processor, ticks| {
+            processor.run(ticks)?;
+            Ok(true)
+        })
+    }
+
+    #[cfg(feature = "std")]
+    pub fn run_with<F>(&mut self, mut execute: F) -> Result<(), ExecutionError>
+    where
+        F: FnMut(&mut C, usize) -> Result<bool, ExecutionError>,
+    {
+        use std::thread;
+        use std::time::{Duration, Instant};
+
+        let frame_time = Duration::from_nanos(1_000_000_000 / 60);
+        let mut next_frame = Instant::now();
+        while self.run_frame_with(&mut execute)? == Control::Continue {
+            next_frame += frame_time;
+            let now = Instant::now();
+            if next_frame > now {
+                thread::sleep(next_frame - now);
+            } else {
+                // fell behind, don't try to catch up
+                next_frame = now;
+            }
+        }
+        Ok(())
+    }
+}
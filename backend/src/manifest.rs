@@ -0,0 +1,93 @@
+// Load manifests for images made of more than one file
+//
+//     # ETI 660 game with its level data at a fixed address
+//     start 0x600
+//     load 0x600 game.ch8
+//     load 0xA00 levels.bin
+//
+// Each `load` line copies a file to an address, with paths relative to the
+// manifest. Later segments overwrite earlier ones where they overlap.
+// `start` is where the PC starts, the first segment's address unless
+// given. Lines starting with # are comments.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::number::parse_number;
+use crate::{Bus, LoadError, Processor};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub address: u16,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    pub start: u16,
+    pub segments: Vec<Segment>,
+}
+
+impl Manifest {
+    // Reads the manifest and every file it names
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text, path.parent().unwrap_or(Path::new("")))
+    }
+
+    // Reads the named files from `dir`
+    pub fn parse(text: &str, dir: &Path) -> io::Result<Self> {
+        let mut start = None;
+        let mut segments = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid manifest on line {}", i + 1),
+                )
+            };
+
+            let (keyword, rest) = line.split_once(char::is_whitespace).ok_or_else(invalid)?;
+            match keyword {
+                "start" => start = Some(parse_number(rest).ok_or_else(invalid)?),
+                "load" => {
+                    let (address, file) = rest
+                        .trim()
+                        .split_once(char::is_whitespace)
+                        .ok_or_else(invalid)?;
+                    let address = parse_number(address).ok_or_else(invalid)?;
+                    let file = dir.join(file.trim());
+                    let data = fs::read(&file).map_err(|e| {
+                        io::Error::new(e.kind(), format!("{}: {}", file.display(), e))
+                    })?;
+                    segments.push(Segment { address, data });
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        let first = segments.first().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "manifest doesn't load anything")
+        })?;
+        Ok(Self {
+            start: start.unwrap_or(first.address),
+            segments,
+        })
+    }
+
+    // Loads every segment and moves the start address and PC
+    pub fn apply<B: Bus>(&self, processor: &mut Processor<B>) -> Result<(), LoadError> {
+        for segment in &self.segments {
+            processor.load_at(segment.address, &segment.data)?;
+        }
+        processor.set_start_address(self.start);
+        Ok(())
+    }
+}
@@ -0,0 +1,23 @@
+// Numbers in the text formats: 0x or 0X hex, or decimal, checked against
+// the range of the type they're read into
+
+pub(crate) fn parse_number<T: TryFrom<u64>>(text: &str) -> Option<T> {
+    let text = text.trim();
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => parse_hex(hex),
+        None => parse_digits(text, 10),
+    }
+}
+
+// Hex digits without the prefix, as in GDB packets
+pub(crate) fn parse_hex<T: TryFrom<u64>>(digits: &str) -> Option<T> {
+    parse_digits(digits, 16)
+}
+
+fn parse_digits<T: TryFrom<u64>>(digits: &str, radix: u32) -> Option<T> {
+    // from_str_radix would also take a leading +
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    T::try_from(u64::from_str_radix(digits, radix).ok()?).ok()
+}
@@ -0,0 +1,303 @@
+// Execution counts for coverage and hotspot reports
+//
+// Routines are identified by their entry address, the target of the
+// 2NNN that called them. Code that runs outside any call is attributed
+// to the address execution started at.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::ops::Range;
+
+use crate::constants::RAM_SIZE;
+use crate::disasm::{chip8x_opcode_class, disassemble, disassemble_chip8x, opcode_class};
+
+pub struct Profile {
+    address_counts: Vec<u64>,
+    class_counts: BTreeMap<&'static str, u64>,
+    routine_counts: BTreeMap<u16, u64>,
+    call_edges: BTreeMap<(u16, u16), u64>,
+    call_stack: Vec<u16>, // entry addresses of the active routines
+    entry: Option<u16>,
+    chip8x: bool, // classify and list 02A0 and BXYN as CHIP-8X
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profile {
+    // Counts the addresses of the default 4 KB of RAM
+    pub fn new() -> Self {
+        Self::with_size(RAM_SIZE)
+    }
+
+    // For buses of other sizes, addresses from `size` up are never counted
+    pub fn with_size(size: usize) -> Self {
+        Self {
+            address_counts: vec![0; size],
+            class_counts: BTreeMap::new(),
+            routine_counts: BTreeMap::new(),
+            call_edges: BTreeMap::new(),
+            call_stack: Vec::new(),
+            entry: None,
+            chip8x: false,
+        }
+    }
+
+    // For programs run with CHIP-8X on
+    pub fn with_chip8x(mut self, chip8x: bool) -> Self {
+        self.chip8x = chip8x;
+        self
+    }
+
+    pub(crate) fn record(&mut self, address: u16, opcode: u16) {
+        let entry = *self.entry.get_or_insert(address);
+        let routine = *self.call_stack.last().unwrap_or(&entry);
+
+        if let Some(count) = self.address_counts.get_mut(address as usize) {
+            *count += 1;
+        }
+        let class = if self.chip8x {
+            chip8x_opcode_class(opcode)
+        } else {
+            opcode_class(opcode)
+        };
+        *self.class_counts.entry(class).or_insert(0) += 1;
+        *self.routine_counts.entry(routine).or_insert(0) += 1;
+
+        match class {
+            "2NNN" => {
+                let target = opcode & 0xFFF;
+                *self.call_edges.entry((routine, target)).or_insert(0) += 1;
+                self.call_stack.push(target);
+            }
+            "00EE" => {
+                self.call_stack.pop();
+            }
+            _ => (),
+        }
+    }
+
+    // For when the processor's stack is replaced, e.g. by loading a state,
+    // so the returns that follow don't match calls that never happened
+    pub(crate) fn reset_calls(&mut self) {
+        self.call_stack.clear();
+    }
+
+    pub fn count(&self, address: u16) -> u64 {
+        self.count_at(address as usize)
+    }
+
+    fn count_at(&self, address: usize) -> u64 {
+        self.address_counts.get(address).copied().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.class_counts.values().sum()
+    }
+
+    // Executions per opcode pattern, e.g. "DXYN"
+    pub fn class_counts(&self) -> &BTreeMap<&'static str, u64> {
+        &self.class_counts
+    }
+
+    // (caller entry, callee entry) -> number of calls
+    pub fn call_edges(&self) -> &BTreeMap<(u16, u16), u64> {
+        &self.call_edges
+    }
+
+    // Routines by number of instructions executed in their own body,
+    // busiest first
+    pub fn hot_routines(&self) -> Vec<(u16, u64)> {
+        let mut routines: Vec<_> = self.routine_counts.iter().map(|(a, c)| (*a, *c)).collect();
+        routines.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        routines
+    }
+
+    // Byte ranges inside `rom` that no executed instruction covers
+    pub fn never_executed(&self, rom: Range<usize>) -> Vec<Range<usize>> {
+        let size = self.address_counts.len();
+        let rom = rom.start.min(size)..rom.end.min(size);
+        let mut covered = vec![false; size];
+        for (address, count) in self.address_counts.iter().enumerate() {
+            if *count > 0 {
+                covered[address] = true;
+                if address + 1 < size {
+                    covered[address + 1] = true;
+                }
+            }
+        }
+
+        let mut ranges = Vec::new();
+        let mut start = None;
+        for address in rom.clone() {
+            match (covered[address], start) {
+                (false, None) => start = Some(address),
+                (true, Some(s)) => {
+                    ranges.push(s..address);
+                    start = None;
+                }
+                _ => (),
+            }
+        }
+        if let Some(s) = start {
+            ranges.push(s..rom.end);
+        }
+        ranges
+    }
+
+    pub fn write_text<W: Write>(&self, writer: &mut W, rom: Range<usize>) -> io::Result<()> {
+        let total = self.total().max(1);
+
+        writeln!(writer, "instructions executed: {}", self.total())?;
+
+        writeln!(writer, "\nhot routines:")?;
+        for (address, count) in self.hot_routines() {
+            writeln!(
+                writer,
+                "  {:04X} {:>12} {:>6.2}%",
+                address,
+                count,
+                100.0 * count as f64 / total as f64
+            )?;
+        }
+
+        writeln!(writer, "\nopcodes:")?;
+        let mut classes: Vec<_> = self.class_counts.iter().collect();
+        classes.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (class, count) in classes {
+            writeln!(writer, "  {} {:>12}", class, count)?;
+        }
+
+        writeln!(writer, "\ncalls:")?;
+        for ((caller, callee), count) in &self.call_edges {
+            writeln!(writer, "  {:04X} -> {:04X} {:>12}", caller, callee, count)?;
+        }
+
+        writeln!(writer, "\nnever executed:")?;
+        for range in self.never_executed(rom) {
+            writeln!(writer, "  {:04X}-{:04X}", range.start, range.end - 1)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_json<W: Write>(&self, writer: &mut W, rom: Range<usize>) -> io::Result<()> {
+        write!(writer, "{{\"total\":{}", self.total())?;
+
+        write!(writer, ",\"addresses\":{{")?;
+        let executed = self
+            .address_counts
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| **c > 0);
+        for (i, (address, count)) in executed.enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            write!(writer, "{}\"0x{:04X}\":{}", separator, address, count)?;
+        }
+
+        write!(writer, "}},\"opcodes\":{{")?;
+        for (i, (class, count)) in self.class_counts.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            write!(writer, "{}\"{}\":{}", separator, class, count)?;
+        }
+
+        write!(writer, "}},\"routines\":[")?;
+        for (i, (address, count)) in self.hot_routines().iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            write!(
+                writer,
+                "{}{{\"address\":\"0x{:04X}\",\"count\":{}}}",
+                separator, address, count
+            )?;
+        }
+
+        write!(writer, "],\"calls\":[")?;
+        for (i, ((caller, callee), count)) in self.call_edges.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            write!(
+                writer,
+                "{}{{\"from\":\"0x{:04X}\",\"to\":\"0x{:04X}\",\"count\":{}}}",
+                separator, caller, callee, count
+            )?;
+        }
+
+        write!(writer, "],\"never_executed\":[")?;
+        for (i, range) in self.never_executed(rom).iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            write!(
+                writer,
+                "{}{{\"start\":\"0x{:04X}\",\"end\":\"0x{:04X}\"}}",
+                separator,
+                range.start,
+                range.end - 1
+            )?;
+        }
+        writeln!(writer, "]}}")
+    }
+
+    // Disassembly of `rom` with execution counts in the left column,
+    // `.` marking instructions that never ran. Routine entries get a
+    // `sub_XXXX:` label
+    pub fn write_listing<W: Write>(
+        &self,
+        writer: &mut W,
+        ram: &[u8],
+        rom: Range<usize>,
+    ) -> io::Result<()> {
+        let end = rom.end.min(ram.len());
+        let mut address = rom.start;
+
+        while address < end {
+            if self.routine_counts.contains_key(&(address as u16))
+                || self
+                    .call_edges
+                    .keys()
+                    .any(|(_, callee)| *callee as usize == address)
+            {
+                writeln!(writer, "sub_{:04X}:", address)?;
+            }
+
+            let count = self.count_at(address);
+            // odd-aligned code: show the skipped byte as data
+            if count == 0 && address + 1 < end && self.count_at(address + 1) > 0 {
+                writeln!(
+                    writer,
+                    "{:>12}  {:04X}  {:02X}    DB {:#04X}",
+                    ".", address, ram[address], ram[address]
+                )?;
+                address += 1;
+                continue;
+            }
+
+            if address + 1 >= end {
+                writeln!(
+                    writer,
+                    "{:>12}  {:04X}  {:02X}    DB {:#04X}",
+                    ".", address, ram[address], ram[address]
+                )?;
+                break;
+            }
+
+            let opcode = u16::from_be_bytes([ram[address], ram[address + 1]]);
+            let column = if count > 0 {
+                count.to_string()
+            } else {
+                ".".to_string()
+            };
+            let text = if self.chip8x {
+                disassemble_chip8x(opcode).to_string()
+            } else {
+                disassemble(opcode).to_string()
+            };
+            writeln!(
+                writer,
+                "{:>12}  {:04X}  {:04X}  {}",
+                column, address, opcode, text
+            )?;
+            address += 2;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,260 @@
+// Straight-line runs of instructions compiled ahead of execution
+//
+// A block starts at whatever address the program counter lands on and
+// runs until the first instruction that can change the flow of control
+// (jumps, calls, returns, skips and waiting for a key). Each instruction
+// becomes an op: a function pointer specialised for the instruction with
+// its operands already split out. Instructions that touch memory, the
+// screen or anything else beyond the registers go through
+// Processor::execute, so they behave exactly as when interpreting.
+//
+// A write to a byte covered by a block drops the block, and bumps the
+// generation so a block that rewrites itself stops after the write.
+
+use std::sync::Arc;
+
+use crate::bus::Bus;
+use crate::decode::{decode, Instruction};
+use crate::{ErrorKind, Processor};
+
+// Keeps blocks short enough that invalidation only has to look back a
+// little way for blocks covering an address
+const MAX_BLOCK_OPS: usize = 64;
+const MAX_BLOCK_BYTES: usize = MAX_BLOCK_OPS * 2;
+
+pub(crate) type OpFn<B> = fn(&mut Processor<B>, &Op<B>) -> Result<(), ErrorKind>;
+
+pub(crate) struct Op<B: Bus> {
+    pub(crate) run: OpFn<B>,
+    pub(crate) opcode: u16,
+    pub(crate) instruction: Instruction,
+    x: usize,
+    y: usize,
+    nn: u8,
+    nnn: u16,
+}
+
+pub(crate) struct Block<B: Bus> {
+    pub(crate) start: usize,
+    pub(crate) ops: Vec<Op<B>>,
+}
+
+impl<B: Bus> Block<B> {
+    // First byte past the last instruction
+    fn end(&self) -> usize {
+        self.start + self.ops.len() * 2
+    }
+}
+
+pub(crate) struct BlockCache<B: Bus> {
+    blocks: Vec<Option<Arc<Block<B>>>>,
+    // number of blocks each byte is part of
+    coverage: Vec<u16>,
+    generation: u64,
+}
+
+// Blocks are shared, so a copy only duplicates the tables
+impl<B: Bus> Clone for BlockCache<B> {
+    fn clone(&self) -> Self {
+        Self {
+            blocks: self.blocks.clone(),
+            coverage: self.coverage.clone(),
+            generation: self.generation,
+        }
+    }
+}
+
+impl<B: Bus> BlockCache<B> {
+    pub(crate) fn new(size: usize) -> Self {
+        Self {
+            blocks: vec![None; size],
+            coverage: vec![0; size],
+            generation: 0,
+        }
+    }
+
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub(crate) fn get(&self, address: usize) -> Option<Arc<Block<B>>> {
+        self.blocks.get(address)?.clone()
+    }
+
+    // None if there isn't a whole instruction at the address
+    pub(crate) fn compile(&mut self, bus: &B, start: usize) -> Option<Arc<Block<B>>> {
+        let size = bus.size();
+        let mut ops = Vec::new();
+        let mut address = start;
+        while address + 1 < size && ops.len() < MAX_BLOCK_OPS {
+            let opcode = (bus.peek(address) as u16) << 8 | bus.peek(address + 1) as u16;
+            let op = compile_op(opcode);
+            address += 2;
+            let ends_block = ends_block(op.instruction);
+            ops.push(op);
+            if ends_block {
+                break;
+            }
+        }
+        if ops.is_empty() {
+            return None;
+        }
+
+        let block = Arc::new(Block { start, ops });
+        for count in &mut self.coverage[start..block.end()] {
+            *count += 1;
+        }
+        self.blocks[start] = Some(Arc::clone(&block));
+        Some(block)
+    }
+
+    // Drops every block with an instruction byte at the address
+    pub(crate) fn invalidate(&mut self, address: usize) {
+        if self.coverage[address] == 0 {
+            return;
+        }
+        let first = address.saturating_sub(MAX_BLOCK_BYTES - 1);
+        for start in first..=address {
+            let covers = self.blocks[start]
+                .as_ref()
+                .is_some_and(|block| address < block.end());
+            if covers {
+                self.remove(start);
+            }
+        }
+        self.generation += 1;
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.blocks.fill(None);
+        self.coverage.fill(0);
+        self.generation += 1;
+    }
+
+    fn remove(&mut self, start: usize) {
+        if let Some(block) = self.blocks[start].take() {
+            for count in &mut self.coverage[start..block.end()] {
+                *count -= 1;
+            }
+        }
+    }
+}
+
+fn ends_block(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Ret
+            | Instruction::Sys(_)
+            | Instruction::Jp(_)
+            | Instruction::Call(_)
+            | Instruction::SeImm { .. }
+            | Instruction::SneImm { .. }
+            | Instruction::SeReg { .. }
+            | Instruction::SneReg { .. }
+            | Instruction::JpV0(_)
+            | Instruction::Skp { .. }
+            | Instruction::Sknp { .. }
+            | Instruction::SkpPad2 { .. }
+            | Instruction::SknpPad2 { .. }
+            | Instruction::LdKey { .. }
+            | Instruction::Invalid
+    )
+}
+
+fn compile_op<B: Bus>(opcode: u16) -> Op<B> {
+    let instruction = decode(opcode);
+    let run: OpFn<B> = match instruction {
+        Instruction::Jp(_) => jp,
+        Instruction::SeImm { .. } => se_imm,
+        Instruction::SneImm { .. } => sne_imm,
+        Instruction::SeReg { .. } => se_reg,
+        Instruction::LdImm { .. } => ld_imm,
+        Instruction::AddImm { .. } => add_imm,
+        Instruction::LdReg { .. } => ld_reg,
+        Instruction::Add { .. } => add,
+        Instruction::Sub { .. } => sub,
+        Instruction::SneReg { .. } => sne_reg,
+        Instruction::LdI(_) => ld_i,
+        _ => interpret,
+    };
+    Op {
+        run,
+        opcode,
+        instruction,
+        x: ((opcode & 0x0F00) >> 8) as usize,
+        y: ((opcode & 0x00F0) >> 4) as usize,
+        nn: (opcode & 0x00FF) as u8,
+        nnn: opcode & 0x0FFF,
+    }
+}
+
+fn interpret<B: Bus>(processor: &mut Processor<B>, op: &Op<B>) -> Result<(), ErrorKind> {
+    processor.execute(op.instruction)
+}
+
+fn jp<B: Bus>(processor: &mut Processor<B>, op: &Op<B>) -> Result<(), ErrorKind> {
+    processor.pc = op.nnn;
+    Ok(())
+}
+
+fn se_imm<B: Bus>(processor: &mut Processor<B>, op: &Op<B>) -> Result<(), ErrorKind> {
+    if processor.v_reg[op.x] == op.nn {
+        processor.skip()?;
+    }
+    Ok(())
+}
+
+fn sne_imm<B: Bus>(processor: &mut Processor<B>, op: &Op<B>) -> Result<(), ErrorKind> {
+    if processor.v_reg[op.x] != op.nn {
+        processor.skip()?;
+    }
+    Ok(())
+}
+
+fn se_reg<B: Bus>(processor: &mut Processor<B>, op: &Op<B>) -> Result<(), ErrorKind> {
+    if processor.v_reg[op.x] == processor.v_reg[op.y] {
+        processor.skip()?;
+    }
+    Ok(())
+}
+
+fn sne_reg<B: Bus>(processor: &mut Processor<B>, op: &Op<B>) -> Result<(), ErrorKind> {
+    if processor.v_reg[op.x] != processor.v_reg[op.y] {
+        processor.skip()?;
+    }
+    Ok(())
+}
+
+fn ld_imm<B: Bus>(processor: &mut Processor<B>, op: &Op<B>) -> Result<(), ErrorKind> {
+    processor.v_reg[op.x] = op.nn;
+    Ok(())
+}
+
+fn add_imm<B: Bus>(processor: &mut Processor<B>, op: &Op<B>) -> Result<(), ErrorKind> {
+    processor.v_reg[op.x] = processor.v_reg[op.x].wrapping_add(op.nn);
+    Ok(())
+}
+
+fn ld_reg<B: Bus>(processor: &mut Processor<B>, op: &Op<B>) -> Result<(), ErrorKind> {
+    processor.v_reg[op.x] = processor.v_reg[op.y];
+    Ok(())
+}
+
+fn add<B: Bus>(processor: &mut Processor<B>, op: &Op<B>) -> Result<(), ErrorKind> {
+    let (new_vx, carry) = processor.v_reg[op.x].overflowing_add(processor.v_reg[op.y]);
+    processor.v_reg[op.x] = new_vx;
+    processor.v_reg[0xF] = carry as u8;
+    Ok(())
+}
+
+fn sub<B: Bus>(processor: &mut Processor<B>, op: &Op<B>) -> Result<(), ErrorKind> {
+    let (new_vx, borrow) = processor.v_reg[op.x].overflowing_sub(processor.v_reg[op.y]);
+    processor.v_reg[op.x] = new_vx;
+    processor.v_reg[0xF] = !borrow as u8;
+    Ok(())
+}
+
+fn ld_i<B: Bus>(processor: &mut Processor<B>, op: &Op<B>) -> Result<(), ErrorKind> {
+    processor.i_reg = op.nnn;
+    Ok(())
+}
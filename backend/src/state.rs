@@ -0,0 +1,236 @@
+// Save states
+//
+// Layout, all integers big endian:
+//
+//     "C8" version:u8 pc:u16 ram[RAM_SIZE] screen[SCREEN_BYTES]
+//     v[NUM_REGS] i:u16 sp:u8 stack[STACK_SIZE]:u16 dt:u8 st:u8 sound:u8
+//     start:u16 modes:u8
+//     background:u8 zones[ZONES] second_keys:u16
+//     r[16]:u16 p:u8 x:u8 d:u8 df:u8 t:u8 ie:u8 q:u8 idle:u8
+//
+// The screen is packed 8 pixels per byte, leftmost pixel in the high bit.
+// Bit 0 of modes is set with CHIP-8X on and bit 1 with the CDP1802, and
+// the CHIP-8X board and 1802 registers that follow are zero when their
+// mode is off. Zones are a color's bits each, and the second keypad a
+// mask with key N in bit N. The first keypad and the random number
+// generator aren't part of the state.
+
+use core::fmt;
+
+use crate::cdp1802::Cdp1802;
+use crate::chip8x::{Chip8x, Color, BACKGROUNDS, ZONE_COLUMNS};
+use crate::constants::*;
+use crate::Processor;
+
+const MAGIC: &[u8; 2] = b"C8";
+const VERSION: u8 = 2;
+const SCREEN_BYTES: usize = SCREEN_WIDTH * SCREEN_HEIGHT / 8;
+const ZONES: usize = ZONE_COLUMNS * SCREEN_HEIGHT;
+
+const CHIP8X: u8 = 1;
+const CDP1802: u8 = 2;
+
+// Up to and including the modes
+const CHIP8_SIZE: usize =
+    3 + 2 + RAM_SIZE + SCREEN_BYTES + NUM_REGS + 2 + 1 + 2 * STACK_SIZE + 3 + 2 + 1;
+const CHIP8X_SIZE: usize = 1 + ZONES + 2;
+const CDP1802_SIZE: usize = 2 * 16 + 8;
+
+pub const STATE_SIZE: usize = CHIP8_SIZE + CHIP8X_SIZE + CDP1802_SIZE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateError {
+    WrongSize,
+    WrongVersion,
+    Corrupt,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::WrongSize => write!(f, "save state has the wrong size"),
+            StateError::WrongVersion => write!(f, "save state is from another version"),
+            StateError::Corrupt => write!(f, "save state is corrupt"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for StateError {}
+
+impl Processor {
+    pub fn save_state(&self) -> [u8; STATE_SIZE] {
+        let mut state = [0; STATE_SIZE];
+        let mut writer = Writer {
+            state: &mut state,
+            offset: 0,
+        };
+
+        writer.bytes(MAGIC);
+        writer.bytes(&[VERSION]);
+        writer.u16(self.pc);
+        writer.bytes(self.bus.as_slice());
+        for pixels in self.screen.chunks(8) {
+            let byte = pixels
+                .iter()
+                .fold(0, |byte, pixel| (byte << 1) | *pixel as u8);
+            writer.bytes(&[byte]);
+        }
+        writer.bytes(&self.v_reg);
+        writer.u16(self.i_reg);
+        writer.bytes(&[self.sp as u8]);
+        for value in self.stack {
+            writer.u16(value);
+        }
+        writer.bytes(&[self.dt, self.st, self.sound as u8]);
+        writer.u16(self.start);
+        let modes = if self.chip8x.is_some() { CHIP8X } else { 0 }
+            | if self.cdp1802.is_some() { CDP1802 } else { 0 };
+        writer.bytes(&[modes]);
+
+        let chip8x = self.chip8x.unwrap_or_default();
+        writer.bytes(&[chip8x.background as u8]);
+        for color in chip8x.zones {
+            writer.bytes(&[color as u8]);
+        }
+        writer.u16(mask(&chip8x.keys));
+
+        let cpu = self.cdp1802.unwrap_or_default();
+        for value in cpu.r {
+            writer.u16(value);
+        }
+        writer.bytes(&[cpu.p, cpu.x, cpu.d, cpu.df as u8, cpu.t]);
+        writer.bytes(&[cpu.ie as u8, cpu.q as u8, cpu.idle as u8]);
+        debug_assert_eq!(writer.offset, STATE_SIZE);
+
+        state
+    }
+
+    // Leaves the processor untouched if the state is rejected
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        if state.len() != STATE_SIZE {
+            return Err(StateError::WrongSize);
+        }
+        if &state[..2] != MAGIC || state[2] != VERSION {
+            return Err(StateError::WrongVersion);
+        }
+
+        let mut reader = Reader { state, offset: 3 };
+
+        let pc = reader.u16();
+        let ram = reader.bytes(RAM_SIZE);
+        let screen = reader.bytes(SCREEN_BYTES);
+        let v_reg = reader.bytes(NUM_REGS);
+        let i_reg = reader.u16();
+        let sp = reader.bytes(1)[0] as u16;
+        let mut stack = [0; STACK_SIZE];
+        for value in stack.iter_mut() {
+            *value = reader.u16();
+        }
+        let timers = reader.bytes(3);
+        let start = reader.u16();
+        let modes = reader.bytes(1)[0];
+
+        let background = reader.bytes(1)[0] as usize;
+        let zones = reader.bytes(ZONES);
+        let second_keys = reader.u16();
+
+        let mut r = [0; 16];
+        for value in r.iter_mut() {
+            *value = reader.u16();
+        }
+        let cpu = reader.bytes(8);
+
+        if sp as usize > STACK_SIZE
+            || timers[2] > 1
+            || modes & !(CHIP8X | CDP1802) != 0
+            || background >= BACKGROUNDS.len()
+            || zones.iter().any(|&bits| bits > 7)
+            || cpu[0] > 0xF
+            || cpu[1] > 0xF
+            || cpu[3..].iter().any(|&flag| flag > 1)
+        {
+            return Err(StateError::Corrupt);
+        }
+
+        self.pc = pc;
+        self.bus_mut().as_mut_slice().copy_from_slice(ram);
+        for (i, pixel) in self.screen.iter_mut().enumerate() {
+            *pixel = screen[i / 8] & (0b1000_0000 >> (i % 8)) != 0;
+        }
+        self.v_reg.copy_from_slice(v_reg);
+        self.i_reg = i_reg;
+        self.sp = sp;
+        self.stack = stack;
+        self.reset_calls();
+        self.dt = timers[0];
+        self.st = timers[1];
+        self.sound = timers[2] == 1;
+        self.start = start;
+
+        self.chip8x = (modes & CHIP8X != 0).then(|| {
+            let mut chip8x = Chip8x::new();
+            chip8x.background = background;
+            for (zone, &bits) in chip8x.zones.iter_mut().zip(zones) {
+                *zone = Color::from_bits(bits);
+            }
+            for (key, pressed) in chip8x.keys.iter_mut().enumerate() {
+                *pressed = second_keys & (1 << key) != 0;
+            }
+            chip8x
+        });
+        self.cdp1802 = (modes & CDP1802 != 0).then(|| Cdp1802 {
+            r,
+            p: cpu[0],
+            x: cpu[1],
+            d: cpu[2],
+            df: cpu[3] == 1,
+            t: cpu[4],
+            ie: cpu[5] == 1,
+            q: cpu[6] == 1,
+            idle: cpu[7] == 1,
+        });
+
+        Ok(())
+    }
+}
+
+fn mask(keys: &[bool; NUM_KEYS]) -> u16 {
+    keys.iter()
+        .enumerate()
+        .fold(0, |mask, (key, pressed)| mask | (*pressed as u16) << key)
+}
+
+struct Writer<'a> {
+    state: &'a mut [u8],
+    offset: usize,
+}
+
+impl Writer<'_> {
+    fn bytes(&mut self, data: &[u8]) {
+        self.state[self.offset..self.offset + data.len()].copy_from_slice(data);
+        self.offset += data.len();
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_be_bytes());
+    }
+}
+
+struct Reader<'a> {
+    state: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, length: usize) -> &'a [u8] {
+        let data = &self.state[self.offset..self.offset + length];
+        self.offset += length;
+        data
+    }
+
+    fn u16(&mut self) -> u16 {
+        let data = self.bytes(2);
+        u16::from_be_bytes([data[0], data[1]])
+    }
+}
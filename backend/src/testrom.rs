@@ -0,0 +1,238 @@
+// Headless runs of test ROMs checked against an expected screen
+//
+// Each ROM has an expectation file next to it, `name.ch8` and `name.txt`:
+//
+//     # frames 120
+//     # selector 3
+//     ##..#...
+//     ...
+//
+// followed by the screen as 32 rows of 64 characters, `#` for lit pixels
+// and `.` for dark ones. The ROM runs for `frames` frames of `ticks`
+// instructions each (60 and 10 unless given). `selector`, if given, is
+// poked into 0x1FF before starting, which the chip8-test-suite ROMs read
+// to pick a test without going through their menu.
+//
+// Self-checking ROMs don't need an expectation: they write "OK" to
+// SIGNATURE_ADDRESS when every check passed, or "F" and the number of the
+// failed check. It's below 0xEA0, where the VIP interpreter keeps its
+// stack, registers and display, so the verdict survives running there.
+// `assemble_self_check` makes it the `signature` label.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::asm::{assemble_with, AsmError};
+use crate::constants::{SCREEN_HEIGHT, SCREEN_WIDTH, START_ADDRESS};
+use crate::number::parse_number;
+use crate::{ExecutionError, Processor};
+
+pub const SELECTOR_ADDRESS: usize = 0x1FF;
+pub const SIGNATURE_ADDRESS: usize = 0xE80;
+const FRAMES: usize = 60;
+const TICKS_PER_FRAME: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expectation {
+    pub frames: usize,
+    pub ticks: usize,
+    pub selector: Option<u8>,
+    pub screen: Vec<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Pass,
+    Fail(u8), // number of the failed check
+    Unfinished,
+}
+
+impl Verdict {
+    pub fn read(processor: &Processor) -> Self {
+        let byte = |offset| processor.read_ram(SIGNATURE_ADDRESS + offset);
+        match (byte(0), byte(1)) {
+            (Some(b'O'), Some(b'K')) => Verdict::Pass,
+            (Some(b'F'), Some(check)) => Verdict::Fail(check),
+            _ => Verdict::Unfinished,
+        }
+    }
+}
+
+// Assembles a self-checking ROM with `signature` defined
+pub fn assemble_self_check(source: &str) -> Result<Vec<u8>, AsmError> {
+    assemble_with(source, START_ADDRESS, &[("signature", SIGNATURE_ADDRESS)])
+}
+
+// Runs a self-checking ROM until it writes a verdict or `frames` run out.
+// `input` is called before every frame with its number, e.g. to press keys
+pub fn run_self_check<F>(rom: &[u8], frames: usize, mut input: F) -> Result<Verdict, ExecutionError>
+where
+    F: FnMut(usize, &mut Processor),
+{
+    let mut processor = Processor::with_seed(0);
+    processor.load(rom);
+    for frame in 0..frames {
+        input(frame, &mut processor);
+        processor.run(TICKS_PER_FRAME)?;
+        processor.tick_timers();
+        let verdict = Verdict::read(&processor);
+        if verdict != Verdict::Unfinished {
+            return Ok(verdict);
+        }
+    }
+    Ok(Verdict::Unfinished)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid expectation on line {}", self.line)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Expectation {
+    // Blank screen after the default number of frames
+    pub fn new() -> Self {
+        Self {
+            frames: FRAMES,
+            ticks: TICKS_PER_FRAME,
+            selector: None,
+            screen: vec![false; SCREEN_WIDTH * SCREEN_HEIGHT],
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut expectation = Self::new();
+        let mut rows = 0;
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            let error = ParseError { line: i + 1 };
+
+            // a row of lit pixels also starts with #, so rows come first
+            if is_row(line) {
+                if rows == SCREEN_HEIGHT {
+                    return Err(error);
+                }
+                for (x, pixel) in line.chars().enumerate() {
+                    expectation.screen[x + SCREEN_WIDTH * rows] = pixel == '#';
+                }
+                rows += 1;
+            } else if let Some(setting) = line.strip_prefix('#') {
+                // settings before the screen, anything else is a comment
+                let words: Vec<_> = setting.split_whitespace().collect();
+                match words.as_slice() {
+                    _ if rows > 0 => return Err(error),
+                    ["frames", frames] => expectation.frames = frames.parse().map_err(|_| error)?,
+                    ["ticks", ticks] => expectation.ticks = ticks.parse().map_err(|_| error)?,
+                    ["selector", selector] => {
+                        expectation.selector = Some(parse_number(selector).ok_or(error)?)
+                    }
+                    _ => {}
+                }
+            } else if !line.is_empty() {
+                return Err(error);
+            }
+        }
+
+        if rows != SCREEN_HEIGHT {
+            return Err(ParseError {
+                line: text.lines().count() + 1,
+            });
+        }
+        Ok(expectation)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    // Runs the ROM with this expectation's settings and returns the screen
+    pub fn run(&self, rom: &[u8]) -> Result<Vec<bool>, ExecutionError> {
+        let mut processor = Processor::with_seed(0);
+        processor.load(rom);
+        if let Some(selector) = self.selector {
+            processor.write_ram(SELECTOR_ADDRESS, selector);
+        }
+        for _ in 0..self.frames {
+            processor.run(self.ticks)?;
+            processor.tick_timers();
+        }
+        Ok(processor.get_display().to_vec())
+    }
+
+    // None if the screen matches, otherwise both screens side by side
+    // with the rows that differ marked
+    pub fn diff(&self, actual: &[bool]) -> Option<String> {
+        if self.screen == actual {
+            return None;
+        }
+
+        let pixels = self
+            .screen
+            .iter()
+            .zip(actual)
+            .filter(|(a, b)| a != b)
+            .count();
+        let mut diff = format!("{} pixels differ\n", pixels);
+        diff += &format!("  {:<width$}   actual\n", "expected", width = SCREEN_WIDTH);
+        let rows = self
+            .screen
+            .chunks(SCREEN_WIDTH)
+            .zip(actual.chunks(SCREEN_WIDTH));
+        for (expected, actual) in rows {
+            let marker = if expected == actual { ' ' } else { '>' };
+            diff += &format!(
+                "{} {} | {}\n",
+                marker,
+                render_row(expected),
+                render_row(actual)
+            );
+        }
+        Some(diff)
+    }
+}
+
+impl Default for Expectation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Settings that differ from the defaults, then the screen
+impl fmt::Display for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.frames != FRAMES {
+            writeln!(f, "# frames {}", self.frames)?;
+        }
+        if self.ticks != TICKS_PER_FRAME {
+            writeln!(f, "# ticks {}", self.ticks)?;
+        }
+        if let Some(selector) = self.selector {
+            writeln!(f, "# selector {}", selector)?;
+        }
+        for row in self.screen.chunks(SCREEN_WIDTH) {
+            writeln!(f, "{}", render_row(row))?;
+        }
+        Ok(())
+    }
+}
+
+fn render_row(row: &[bool]) -> String {
+    row.iter()
+        .map(|pixel| if *pixel { '#' } else { '.' })
+        .collect()
+}
+
+fn is_row(line: &str) -> bool {
+    line.len() == SCREEN_WIDTH && line.chars().all(|c| c == '#' || c == '.')
+}
@@ -0,0 +1,293 @@
+// Per-instruction execution tracing
+//
+// Text format, one line per instruction:
+//
+//     0200 6A02 LD VA, 0x02 ; VA=00->02
+//     0202 A22A LD I, 0x22A ; I=000->22A
+//     0204 1204 JP 0x204
+//     ! stack underflow at 0x206 (opcode 0x00EE)
+//
+// Address and opcode are 4 hex digits, followed by the mnemonic and, if
+// anything changed, a `;` and the register/I/timer deltas. Errors are
+// prefixed with `!`.
+//
+// Binary format starts with BINARY_MAGIC, followed by records:
+//
+//     0x00 address:u16 opcode:u16 mask:u32 values...
+//     0x01 address:u16 opcode:u16 len:u8 message[len]
+//
+// All integers are big endian. Bits 0-15 of the mask flag changed V
+// registers, then I (16), DT (17), ST (18) and SP (19). The new values
+// follow in bit order, I as a u16 and everything else as a u8.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use crate::disasm::disassemble;
+use crate::{ExecutionError, Registers};
+
+pub const BINARY_MAGIC: &[u8; 4] = b"C8T1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceStep {
+    pub address: u16,
+    pub opcode: u16,
+    pub before: Registers,
+    pub after: Registers,
+}
+
+// Formats the step as one line of the text format, without the newline
+impl fmt::Display for TraceStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let before = &self.before;
+        let after = &self.after;
+
+        write!(
+            f,
+            "{:04X} {:04X} {}",
+            self.address,
+            self.opcode,
+            disassemble(self.opcode)
+        )?;
+
+        let mut separator = " ;";
+        for i in 0..before.v.len() {
+            if before.v[i] != after.v[i] {
+                write!(
+                    f,
+                    "{} V{:X}={:02X}->{:02X}",
+                    separator, i, before.v[i], after.v[i]
+                )?;
+                separator = "";
+            }
+        }
+        if before.i != after.i {
+            write!(f, "{} I={:03X}->{:03X}", separator, before.i, after.i)?;
+            separator = "";
+        }
+        if before.dt != after.dt {
+            write!(f, "{} DT={:02X}->{:02X}", separator, before.dt, after.dt)?;
+            separator = "";
+        }
+        if before.st != after.st {
+            write!(f, "{} ST={:02X}->{:02X}", separator, before.st, after.st)?;
+            separator = "";
+        }
+        if before.sp != after.sp {
+            write!(f, "{} SP={:X}->{:X}", separator, before.sp, after.sp)?;
+        }
+        Ok(())
+    }
+}
+
+pub trait Tracer {
+    // Called after every successfully executed instruction
+    fn record(&mut self, step: &TraceStep);
+
+    // Called when an instruction fails, instead of record
+    fn error(&mut self, _error: &ExecutionError) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    Text,
+    Binary,
+}
+
+// Writes every step it sees. IO errors stop the log and are reported
+// by into_inner, since the processor can't do anything about them
+pub struct TraceLog<W: Write> {
+    writer: W,
+    format: TraceFormat,
+    started: bool,
+    status: io::Result<()>,
+}
+
+impl TraceLog<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P, format: TraceFormat) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?), format))
+    }
+}
+
+impl<W: Write> TraceLog<W> {
+    pub fn new(writer: W, format: TraceFormat) -> Self {
+        Self {
+            writer,
+            format,
+            started: false,
+            status: Ok(()),
+        }
+    }
+
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.status?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_with<F>(&mut self, write: F)
+    where
+        F: FnOnce(&mut W, TraceFormat) -> io::Result<()>,
+    {
+        if self.status.is_err() {
+            return;
+        }
+        if !self.started && self.format == TraceFormat::Binary {
+            self.status = self.writer.write_all(BINARY_MAGIC);
+        }
+        self.started = true;
+        if self.status.is_ok() {
+            self.status = write(&mut self.writer, self.format);
+        }
+    }
+}
+
+impl<W: Write> Tracer for TraceLog<W> {
+    fn record(&mut self, step: &TraceStep) {
+        self.write_with(|writer, format| match format {
+            TraceFormat::Text => writeln!(writer, "{}", step),
+            TraceFormat::Binary => write_binary_step(writer, step),
+        });
+    }
+
+    fn error(&mut self, error: &ExecutionError) {
+        self.write_with(|writer, format| match format {
+            TraceFormat::Text => writeln!(writer, "! {}", error),
+            TraceFormat::Binary => write_binary_error(writer, error),
+        });
+        // the processor is likely about to panic, so don't rely on drop
+        if self.status.is_ok() {
+            self.status = self.writer.flush();
+        }
+    }
+}
+
+// Only forwards steps whose address is inside the range. Errors are
+// always forwarded
+pub struct AddressFilter<T: Tracer> {
+    range: RangeInclusive<u16>,
+    inner: T,
+}
+
+impl<T: Tracer> AddressFilter<T> {
+    pub fn new(range: RangeInclusive<u16>, inner: T) -> Self {
+        Self { range, inner }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Tracer> Tracer for AddressFilter<T> {
+    fn record(&mut self, step: &TraceStep) {
+        if self.range.contains(&step.address) {
+            self.inner.record(step);
+        }
+    }
+
+    fn error(&mut self, error: &ExecutionError) {
+        self.inner.error(error);
+    }
+}
+
+// Keeps the last `capacity` steps in memory and only hands them to the
+// inner tracer when an error occurs, so long runs stay cheap
+pub struct RingTrace<T: Tracer> {
+    steps: VecDeque<TraceStep>,
+    capacity: usize,
+    inner: T,
+}
+
+impl<T: Tracer> RingTrace<T> {
+    pub fn new(capacity: usize, inner: T) -> Self {
+        Self {
+            steps: VecDeque::with_capacity(capacity),
+            capacity,
+            inner,
+        }
+    }
+
+    pub fn steps(&self) -> impl Iterator<Item = &TraceStep> {
+        self.steps.iter()
+    }
+
+    // Flushes the buffered steps to the inner tracer
+    pub fn dump(&mut self) {
+        for step in self.steps.drain(..) {
+            self.inner.record(&step);
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Tracer> Tracer for RingTrace<T> {
+    fn record(&mut self, step: &TraceStep) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.steps.len() == self.capacity {
+            self.steps.pop_front();
+        }
+        self.steps.push_back(*step);
+    }
+
+    fn error(&mut self, error: &ExecutionError) {
+        self.dump();
+        self.inner.error(error);
+    }
+}
+
+fn write_binary_step<W: Write>(writer: &mut W, step: &TraceStep) -> io::Result<()> {
+    let before = &step.before;
+    let after = &step.after;
+
+    let mut mask: u32 = 0;
+    let mut values = Vec::with_capacity(24);
+    for i in 0..before.v.len() {
+        if before.v[i] != after.v[i] {
+            mask |= 1 << i;
+            values.push(after.v[i]);
+        }
+    }
+    if before.i != after.i {
+        mask |= 1 << 16;
+        values.extend_from_slice(&after.i.to_be_bytes());
+    }
+    if before.dt != after.dt {
+        mask |= 1 << 17;
+        values.push(after.dt);
+    }
+    if before.st != after.st {
+        mask |= 1 << 18;
+        values.push(after.st);
+    }
+    if before.sp != after.sp {
+        mask |= 1 << 19;
+        values.push(after.sp as u8);
+    }
+
+    writer.write_all(&[0x00])?;
+    writer.write_all(&step.address.to_be_bytes())?;
+    writer.write_all(&step.opcode.to_be_bytes())?;
+    writer.write_all(&mask.to_be_bytes())?;
+    writer.write_all(&values)
+}
+
+fn write_binary_error<W: Write>(writer: &mut W, error: &ExecutionError) -> io::Result<()> {
+    let message = error.kind.to_string();
+    let message = &message.as_bytes()[..message.len().min(u8::MAX as usize)];
+
+    writer.write_all(&[0x01])?;
+    writer.write_all(&error.address.to_be_bytes())?;
+    writer.write_all(&error.opcode.to_be_bytes())?;
+    writer.write_all(&[message.len() as u8])?;
+    writer.write_all(message)
+}
@@ -0,0 +1,386 @@
+// Finds the first point where two emulation runs stop agreeing, either
+// by running two processors in lockstep or by comparing two text traces
+//
+// Lockstep runs are split into frames like Machine's: the timers count
+// down between frames, and keys from a script are held during them.
+// Scripts have one press per line, with the key in hex and the frames
+// counted from 0, end excluded:
+//
+//     # hold 5 for the first second, then tap A
+//     press 5 from 0 to 60
+//     press A from 90 to 95
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, BufRead};
+use std::ops::Range;
+
+use crate::constants::NUM_KEYS;
+use crate::trace::TraceStep;
+use crate::{ExecutionError, Processor};
+
+const TICKS_PER_FRAME: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difference {
+    Pc(u16, u16),
+    V(usize, u8, u8),
+    I(u16, u16),
+    Sp(u16, u16),
+    Dt(u8, u8),
+    St(u8, u8),
+    Stack(usize, u16, u16),
+    Ram(usize, u8, u8),
+    Error(Option<ExecutionError>, Option<ExecutionError>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub step: usize, // number of instructions both runs executed before diverging
+    pub differences: Vec<Difference>,
+    pub history: Vec<(TraceStep, TraceStep)>, // up to and including the diverging step
+    pub after_a: Vec<TraceStep>,
+    pub after_b: Vec<TraceStep>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyScript {
+    presses: Vec<(usize, Range<usize>)>,
+}
+
+impl KeyScript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut script = Self::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let words: Vec<_> = line.split_whitespace().collect();
+            let press = match words.as_slice() {
+                ["press", key, "from", start, "to", end] => {
+                    let key = usize::from_str_radix(key, 16).ok();
+                    match (key, start.parse().ok(), end.parse().ok()) {
+                        (Some(key), Some(start), Some(end)) if key < NUM_KEYS => {
+                            Some((key, start..end))
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+            let (key, frames) = press.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid key script on line {}", i + 1),
+                )
+            })?;
+            script.press(key, frames);
+        }
+        Ok(script)
+    }
+
+    // Panics if the key is past 0xF
+    pub fn press(&mut self, key: usize, frames: Range<usize>) {
+        assert!(key < NUM_KEYS, "invalid key index {:#04X}", key);
+        self.presses.push((key, frames));
+    }
+
+    pub fn held(&self, key: usize, frame: usize) -> bool {
+        self.presses
+            .iter()
+            .any(|(pressed, frames)| *pressed == key && frames.contains(&frame))
+    }
+}
+
+// How lockstep runs are driven, 10 instructions a frame and no keys
+// unless changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    pub ticks_per_frame: usize,
+    pub keys: KeyScript,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self {
+            ticks_per_frame: TICKS_PER_FRAME,
+            keys: KeyScript::new(),
+        }
+    }
+}
+
+impl Schedule {
+    // Ticks the timers at the end of a frame and holds the next frame's
+    // keys, before the first instruction of each frame
+    fn before_step(&self, processor: &mut Processor, step: usize) {
+        let ticks_per_frame = self.ticks_per_frame.max(1);
+        if !step.is_multiple_of(ticks_per_frame) {
+            return;
+        }
+        if step > 0 {
+            processor.tick_timers();
+        }
+        let frame = step / ticks_per_frame;
+        for key in 0..NUM_KEYS {
+            processor.keypress(key, self.keys.held(key, frame));
+        }
+    }
+}
+
+// Runs both processors one instruction at a time and compares their
+// full state after each step. Returns None if they agree for max_steps
+// instructions or fail identically
+pub fn run_lockstep(
+    a: &mut Processor,
+    b: &mut Processor,
+    schedule: &Schedule,
+    max_steps: usize,
+    context: usize,
+) -> Option<Divergence> {
+    let mut history = VecDeque::with_capacity(context + 1);
+
+    for step in 0..max_steps {
+        schedule.before_step(a, step);
+        schedule.before_step(b, step);
+        let result_a = traced_tick(a);
+        let result_b = traced_tick(b);
+
+        let mut differences = compare(a, b);
+        match (result_a, result_b) {
+            (Ok(step_a), Ok(step_b)) => {
+                if history.len() > context {
+                    history.pop_front();
+                }
+                history.push_back((step_a, step_b));
+            }
+            (Err(error_a), Err(error_b)) if error_a == error_b && differences.is_empty() => {
+                return None;
+            }
+            (result_a, result_b) => {
+                differences.push(Difference::Error(result_a.err(), result_b.err()));
+            }
+        }
+
+        if !differences.is_empty() {
+            return Some(Divergence {
+                step,
+                differences,
+                history: history.into(),
+                after_a: run_context(a, schedule, step + 1, context),
+                after_b: run_context(b, schedule, step + 1, context),
+            });
+        }
+    }
+
+    None
+}
+
+pub fn compare(a: &Processor, b: &Processor) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    let regs_a = a.registers();
+    let regs_b = b.registers();
+
+    if regs_a.pc != regs_b.pc {
+        differences.push(Difference::Pc(regs_a.pc, regs_b.pc));
+    }
+    for i in 0..regs_a.v.len() {
+        if regs_a.v[i] != regs_b.v[i] {
+            differences.push(Difference::V(i, regs_a.v[i], regs_b.v[i]));
+        }
+    }
+    if regs_a.i != regs_b.i {
+        differences.push(Difference::I(regs_a.i, regs_b.i));
+    }
+    if regs_a.sp != regs_b.sp {
+        differences.push(Difference::Sp(regs_a.sp, regs_b.sp));
+    }
+    if regs_a.dt != regs_b.dt {
+        differences.push(Difference::Dt(regs_a.dt, regs_b.dt));
+    }
+    if regs_a.st != regs_b.st {
+        differences.push(Difference::St(regs_a.st, regs_b.st));
+    }
+    for (i, (x, y)) in a.stack().iter().zip(b.stack()).enumerate() {
+        if x != y {
+            differences.push(Difference::Stack(i, *x, *y));
+        }
+    }
+    for (i, (x, y)) in a.ram().iter().zip(b.ram()).enumerate() {
+        if x != y {
+            differences.push(Difference::Ram(i, *x, *y));
+        }
+    }
+
+    differences
+}
+
+fn traced_tick(processor: &mut Processor) -> Result<TraceStep, ExecutionError> {
+    let before = processor.registers();
+    let ram = processor.ram();
+    let address = before.pc as usize;
+    let opcode = match (ram.get(address), ram.get(address + 1)) {
+        (Some(high), Some(low)) => u16::from_be_bytes([*high, *low]),
+        _ => 0,
+    };
+
+    // through the recompiler if it's on
+    processor.run(1)?;
+
+    Ok(TraceStep {
+        address: before.pc,
+        opcode,
+        before,
+        after: processor.registers(),
+    })
+}
+
+fn run_context(
+    processor: &mut Processor,
+    schedule: &Schedule,
+    first_step: usize,
+    context: usize,
+) -> Vec<TraceStep> {
+    let mut steps = Vec::with_capacity(context);
+    for step in first_step..first_step + context {
+        schedule.before_step(processor, step);
+        match traced_tick(processor) {
+            Ok(step) => steps.push(step),
+            Err(_) => break,
+        }
+    }
+    steps
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Difference::Pc(a, b) => write!(f, "PC {:04X} != {:04X}", a, b),
+            Difference::V(x, a, b) => write!(f, "V{:X} {:02X} != {:02X}", x, a, b),
+            Difference::I(a, b) => write!(f, "I {:03X} != {:03X}", a, b),
+            Difference::Sp(a, b) => write!(f, "SP {:X} != {:X}", a, b),
+            Difference::Dt(a, b) => write!(f, "DT {:02X} != {:02X}", a, b),
+            Difference::St(a, b) => write!(f, "ST {:02X} != {:02X}", a, b),
+            Difference::Stack(i, a, b) => write!(f, "stack[{}] {:04X} != {:04X}", i, a, b),
+            Difference::Ram(i, a, b) => write!(f, "RAM[{:03X}] {:02X} != {:02X}", i, a, b),
+            Difference::Error(a, b) => {
+                let describe = |error: &Option<ExecutionError>| match error {
+                    Some(error) => error.to_string(),
+                    None => "ok".to_string(),
+                };
+                write!(f, "result {} != {}", describe(a), describe(b))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "runs diverge at step {}", self.step)?;
+        for difference in &self.differences {
+            writeln!(f, "  {}", difference)?;
+        }
+
+        writeln!(f, "history:")?;
+        for (step_a, step_b) in &self.history {
+            if step_a == step_b {
+                writeln!(f, "    {}", step_a)?;
+            } else {
+                writeln!(f, "  a {}", step_a)?;
+                writeln!(f, "  b {}", step_b)?;
+            }
+        }
+
+        writeln!(f, "after (a):")?;
+        for step in &self.after_a {
+            writeln!(f, "    {}", step)?;
+        }
+        writeln!(f, "after (b):")?;
+        for step in &self.after_b {
+            writeln!(f, "    {}", step)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineDivergence {
+    pub line: usize,         // 1-based line number of the first differing line
+    pub before: Vec<String>, // matching lines leading up to it
+    pub a: Vec<String>,      // differing line and what follows, empty at end of file
+    pub b: Vec<String>,
+}
+
+// Compares two traces in the text format line by line. Binary traces
+// aren't supported, record those runs as text instead
+pub fn compare_traces<A: BufRead, B: BufRead>(
+    a: A,
+    b: B,
+    context: usize,
+) -> io::Result<Option<LineDivergence>> {
+    let mut lines_a = a.lines();
+    let mut lines_b = b.lines();
+    let mut before = VecDeque::with_capacity(context + 1);
+    let mut line = 0;
+
+    loop {
+        line += 1;
+        let line_a = lines_a.next().transpose()?;
+        let line_b = lines_b.next().transpose()?;
+
+        if line_a == line_b {
+            match line_a {
+                Some(text) => {
+                    if before.len() == context {
+                        before.pop_front();
+                    }
+                    before.push_back(text);
+                    continue;
+                }
+                None => return Ok(None),
+            }
+        }
+
+        let mut rest_a: Vec<String> = line_a.into_iter().collect();
+        let mut rest_b: Vec<String> = line_b.into_iter().collect();
+        for text in lines_a.take(context) {
+            rest_a.push(text?);
+        }
+        for text in lines_b.take(context) {
+            rest_b.push(text?);
+        }
+
+        return Ok(Some(LineDivergence {
+            line,
+            before: before.into(),
+            a: rest_a,
+            b: rest_b,
+        }));
+    }
+}
+
+impl fmt::Display for LineDivergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "traces diverge at line {}", self.line)?;
+        for text in &self.before {
+            writeln!(f, "    {}", text)?;
+        }
+        for text in &self.a {
+            writeln!(f, "  a {}", text)?;
+        }
+        if self.a.is_empty() {
+            writeln!(f, "  a <end of trace>")?;
+        }
+        for text in &self.b {
+            writeln!(f, "  b {}", text)?;
+        }
+        if self.b.is_empty() {
+            writeln!(f, "  b <end of trace>")?;
+        }
+        Ok(())
+    }
+}
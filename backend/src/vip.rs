@@ -0,0 +1,227 @@
+// The whole COSMAC VIP: a CDP1802 with 4 KB of RAM, the CDP1861 video chip
+// and the hex keypad, running the original CHIP-8 interpreter
+//
+// The interpreter isn't included, it's loaded from an image of the 512
+// bytes the VIP kept at 0x000. The VIP's monitor ROM isn't needed either:
+// reset starts the 1802 at 0x000 with R1 pointing at the last page of RAM,
+// which is all the interpreter expects the monitor to leave behind.
+//
+// Everything runs at the VIP's speed, so a frame is always 3668 machine
+// cycles however many instructions that comes to. The 1861 interrupts
+// the 1802 two lines before the picture and then steals 8 cycles of each
+// of its 128 lines to fetch the line from memory. The CHIP-8 interpreter
+// shows each of its 32 rows on 4 lines, the first of each is what ends up
+// on the screen. Keys are scanned by OUT 2 and read back on EF3, and the
+// speaker follows Q.
+
+use crate::bus::{Bus, Ram};
+use crate::cdp1802::{Cdp1802, Io};
+use crate::constants::{NUM_KEYS, SCREEN_HEIGHT, SCREEN_WIDTH, START_ADDRESS};
+use crate::{ExecutionError, LoadError};
+
+pub const INTERPRETER_SIZE: usize = START_ADDRESS as usize;
+
+const CYCLES_PER_LINE: u32 = 14;
+const LINES_PER_FRAME: u32 = 262;
+pub const CYCLES_PER_FRAME: u32 = CYCLES_PER_LINE * LINES_PER_FRAME;
+const INTERRUPT_LINE: u32 = 78;
+const FIRST_LINE: u32 = 80;
+const PICTURE_LINES: u32 = 128;
+// from the interrupt to the first line's DMA
+const DMA_DELAY: u32 = 29;
+const DMA_BYTES: usize = SCREEN_WIDTH / 8;
+
+pub struct Vip {
+    cpu: Cdp1802,
+    ram: Ram,
+    devices: Devices,
+    interpreter: [u8; INTERPRETER_SIZE],
+    screen: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
+    overshoot: u32, // cycles the last frame ran into this one
+}
+
+// What's on the 1802's I/O lines
+struct Devices {
+    keys: [bool; NUM_KEYS],
+    latch: usize, // key OUT 2 selected
+    display: bool,
+    line: u32,
+}
+
+impl Io for Devices {
+    fn output(&mut self, port: u8, value: u8) {
+        match port {
+            1 => self.display = false,
+            2 => self.latch = (value & 0xF) as usize,
+            _ => {}
+        }
+    }
+
+    fn input(&mut self, port: u8) -> u8 {
+        if port == 1 {
+            self.display = true;
+        }
+        0
+    }
+
+    fn ef(&self, line: u8) -> bool {
+        match line {
+            // the 1861 flags the 4 lines before the picture starts and ends
+            1 => {
+                let end = FIRST_LINE + PICTURE_LINES;
+                self.display
+                    && ((FIRST_LINE - 4..FIRST_LINE).contains(&self.line)
+                        || (end - 4..end).contains(&self.line))
+            }
+            3 => self.keys[self.latch],
+            _ => false,
+        }
+    }
+}
+
+impl Vip {
+    // Refuses images that don't fit below the program
+    pub fn new(interpreter: &[u8]) -> Result<Self, LoadError> {
+        if interpreter.len() > INTERPRETER_SIZE {
+            return Err(LoadError {
+                address: 0,
+                len: interpreter.len(),
+                capacity: INTERPRETER_SIZE,
+            });
+        }
+        let mut image = [0; INTERPRETER_SIZE];
+        image[..interpreter.len()].copy_from_slice(interpreter);
+
+        let mut vip = Self {
+            cpu: Cdp1802::new(),
+            ram: Ram::new(),
+            devices: Devices {
+                keys: [false; NUM_KEYS],
+                latch: 0,
+                display: false,
+                line: 0,
+            },
+            interpreter: image,
+            screen: [false; SCREEN_WIDTH * SCREEN_HEIGHT],
+            overshoot: 0,
+        };
+        vip.reset();
+        Ok(vip)
+    }
+
+    // Power cycle, clears RAM and puts the interpreter back
+    pub fn reset(&mut self) {
+        self.ram = Ram::new();
+        self.ram.as_mut_slice()[..INTERPRETER_SIZE].copy_from_slice(&self.interpreter);
+        self.cpu = Cdp1802::new();
+        self.cpu.r[1] = (self.ram.size() - 0x100) as u16;
+        self.devices.latch = 0;
+        self.devices.display = false;
+        self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        self.overshoot = 0;
+    }
+
+    pub fn load(&mut self, data: &[u8]) {
+        if let Err(error) = self.try_load(data) {
+            panic!("{}", error);
+        }
+    }
+
+    // Copies the CHIP-8 program to 0x200, where the interpreter runs it from
+    pub fn try_load(&mut self, data: &[u8]) -> Result<(), LoadError> {
+        self.load_at(START_ADDRESS, data)
+    }
+
+    pub fn load_at(&mut self, address: u16, data: &[u8]) -> Result<(), LoadError> {
+        let address = address as usize;
+        let capacity = self.ram.size().saturating_sub(address);
+        if data.len() > capacity {
+            return Err(LoadError {
+                address,
+                len: data.len(),
+                capacity,
+            });
+        }
+        self.ram.as_mut_slice()[address..address + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    // Runs one frame at the VIP's speed, `_ticks` is only there to match
+    // Processor::run
+    pub fn run(&mut self, _ticks: usize) -> Result<(), ExecutionError> {
+        let interrupt = INTERRUPT_LINE * CYCLES_PER_LINE;
+        let first_dma = interrupt + DMA_DELAY;
+        let mut interrupted = false;
+        let mut lines = 0;
+
+        let mut cycle = self.overshoot;
+        while cycle < CYCLES_PER_FRAME {
+            self.devices.line = cycle / CYCLES_PER_LINE;
+            if self.devices.display {
+                // the interrupt is held until the picture starts
+                if !interrupted && (interrupt..first_dma).contains(&cycle) && self.cpu.interrupt() {
+                    interrupted = true;
+                    cycle += 1;
+                    continue;
+                }
+                if lines < PICTURE_LINES && cycle >= first_dma + lines * CYCLES_PER_LINE {
+                    self.fetch_line(lines as usize);
+                    lines += 1;
+                    cycle += DMA_BYTES as u32;
+                    continue;
+                }
+            }
+
+            let address = self.cpu.r[self.cpu.p as usize];
+            cycle += self
+                .cpu
+                .step(&mut self.ram, &mut self.devices)
+                .map_err(|kind| ExecutionError {
+                    address,
+                    opcode: 0,
+                    kind,
+                })?;
+        }
+        self.overshoot = cycle - CYCLES_PER_FRAME;
+        Ok(())
+    }
+
+    // The interpreter counts its own timers down in the interrupt, so
+    // there's nothing to do here. Only there to match Processor
+    pub fn tick_timers(&mut self) {}
+
+    fn fetch_line(&mut self, line: usize) {
+        let lines_per_row = PICTURE_LINES as usize / SCREEN_HEIGHT;
+        let row = line / lines_per_row;
+        let shown = line.is_multiple_of(lines_per_row);
+        for byte in 0..DMA_BYTES {
+            let pixels = self.cpu.dma_out(&mut self.ram);
+            if shown {
+                for bit in 0..8 {
+                    let x = byte * 8 + bit;
+                    self.screen[x + SCREEN_WIDTH * row] = pixels & (0x80 >> bit) != 0;
+                }
+            }
+        }
+    }
+
+    pub fn keypress(&mut self, index: usize, pressed: bool) {
+        self.devices.keys[index] = pressed;
+    }
+
+    pub fn get_display(&self) -> &[bool] {
+        &self.screen
+    }
+
+    pub fn get_sound(&self) -> bool {
+        self.cpu.q
+    }
+
+    pub fn cpu(&self) -> &Cdp1802 {
+        &self.cpu
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        self.ram.as_slice()
+    }
+}
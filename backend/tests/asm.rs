@@ -0,0 +1,124 @@
+#![cfg(feature = "std")]
+
+use backend::asm::{assemble, assemble_at, assemble_with, AsmError};
+use backend::chip8x;
+use backend::disasm::{disassemble, disassemble_chip8x};
+use backend::Processor;
+
+#[test]
+fn disassembly_assembles_back() {
+    for opcode in 0..=u16::MAX {
+        let text = disassemble(opcode).to_string();
+        let rom = assemble(&text).unwrap_or_else(|e| panic!("{:#06X} {}: {}", opcode, text, e));
+
+        // the disassembler leaves out the unused Y of shifts
+        let expected = match opcode & 0xF00F {
+            0x8006 | 0x800E => opcode & 0xFF0F,
+            _ => opcode,
+        };
+        assert_eq!(rom, expected.to_be_bytes(), "{}", text);
+    }
+}
+
+#[test]
+fn chip8x_disassembly_assembles_back() {
+    let opcodes = [0x02A0, 0x0123, 0x5AB1, 0xB123, 0xB0F0, 0xE3F2, 0xE3F5];
+    let texts: Vec<_> = opcodes
+        .iter()
+        .map(|opcode| disassemble_chip8x(*opcode).to_string())
+        .collect();
+    assert_eq!(
+        texts,
+        [
+            "BGCOL",
+            "SYS 0x123",
+            "ADDN VA, VB",
+            "COL V1, V2, 3",
+            "COL V0, VF, 0",
+            "SKP2 V3",
+            "SKNP2 V3"
+        ]
+    );
+    for (opcode, text) in opcodes.iter().zip(&texts) {
+        let rom = opcode.to_be_bytes().to_vec();
+        assert_eq!(assemble(text), Ok(rom), "{}", text);
+    }
+}
+
+#[test]
+fn labels_count_from_the_origin() {
+    let source = "start: LD I, sprite
+                         CALL draw
+                  draw:  JP start
+                  sprite: DB 0xF0";
+    assert_eq!(
+        assemble_at(source, 0x300),
+        Ok(vec![0xA3, 0x06, 0x23, 0x04, 0x13, 0x00, 0xF0])
+    );
+    assert_eq!(
+        assemble_at(source, 0x600),
+        Ok(vec![0xA6, 0x06, 0x26, 0x04, 0x16, 0x00, 0xF0])
+    );
+}
+
+#[test]
+fn symbols_are_predefined_labels() {
+    let symbols = [("result", 0xE80)];
+    assert_eq!(
+        assemble_with("LD I, RESULT", 0x200, &symbols),
+        Ok(vec![0xAE, 0x80])
+    );
+    assert!(assemble_with("result: DB 1", 0x200, &symbols).is_err());
+}
+
+#[test]
+fn chip8x_programs_jump_to_their_labels() {
+    let source = "       BGCOL
+                  loop:  ADD V0, 1
+                         JP loop";
+    let rom = assemble_at(source, chip8x::START_ADDRESS).unwrap();
+    let mut processor = Processor::with_seed(0);
+    processor.set_chip8x(true);
+    processor.load(&rom);
+    processor.run(5).unwrap();
+    assert_eq!(processor.registers().v[0], 2);
+    assert_eq!(processor.registers().pc, 0x302);
+}
+
+#[test]
+fn labels_and_data() {
+    let source = "
+        ; comment
+        start:  ld v0, 5    ; lower case is fine
+                jp end
+        data:   DB 1, 0x02
+                DW 0x1234
+        end:    LD I, data
+                JP START
+    ";
+    assert_eq!(
+        assemble(source),
+        Ok(vec![
+            0x60, 0x05, 0x12, 0x08, 0x01, 0x02, 0x12, 0x34, 0xA2, 0x04, 0x12, 0x00
+        ])
+    );
+}
+
+#[test]
+fn errors_name_the_line() {
+    let error = |source, line, reason| {
+        assert_eq!(
+            assemble(source),
+            Err(AsmError { line, reason }),
+            "{}",
+            source
+        );
+    };
+    error("CLS\nFOO V0", 2, "invalid instruction");
+    error("LD V0", 1, "invalid instruction");
+    error("JP nowhere", 1, "unknown label");
+    error("LD V0, 256", 1, "value out of range");
+    error("DRW V0, V1, 16", 1, "value out of range");
+    error("LD I, V0", 1, "expected a number or label");
+    error("a: CLS\na: CLS", 2, "duplicate label");
+}
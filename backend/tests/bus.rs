@@ -0,0 +1,110 @@
+// Runs the processor against the bundled buses
+
+use std::cell::RefCell;
+
+use backend::bus::{Access, Logged, ProtectedRom};
+use backend::{Bus, ErrorKind, Processor, Ram};
+
+// V0 = 0xAB, I = 0x202, [I] = V0: overwrites its own second instruction
+const SELF_MODIFYING: [u8; 6] = [0x60, 0xAB, 0xA2, 0x02, 0xF0, 0x55];
+
+#[test]
+fn protected_rom_rejects_self_modification() {
+    let bus = ProtectedRom::new(Ram::<4096>::new(), 0x200..0x200 + SELF_MODIFYING.len());
+    let mut processor = Processor::with_bus(bus, 0);
+    processor.load(&SELF_MODIFYING);
+
+    processor.try_tick().unwrap();
+    processor.try_tick().unwrap();
+    let error = processor.try_tick().unwrap_err();
+    assert_eq!(error.kind, ErrorKind::WriteProtected(0x202));
+    assert_eq!(error.address, 0x204);
+    assert_eq!(processor.read_ram(0x202), Some(0xA2));
+}
+
+#[test]
+fn logged_bus_sees_instruction_accesses() {
+    let accesses = RefCell::new(Vec::new());
+    let bus = Logged::new(Ram::<4096>::new(), |access| accesses.borrow_mut().push(access));
+    let mut processor = Processor::with_bus(bus, 0);
+    // loading goes through poke and isn't logged
+    processor.load(&SELF_MODIFYING);
+    assert!(accesses.borrow().is_empty());
+
+    for _ in 0..3 {
+        processor.try_tick().unwrap();
+    }
+
+    let accesses = accesses.into_inner();
+    assert_eq!(accesses.len(), 7);
+    assert_eq!(accesses[0], Access::Read(0x200, 0x60));
+    assert_eq!(accesses[6], Access::Write(0x202, 0xAB));
+}
+
+#[test]
+fn larger_memory_extends_addressing() {
+    // V0 = 0x5A, I = 0xFFF, I += 1, [I] = V0
+    let rom = [0x60, 0x5A, 0xAF, 0xFF, 0x61, 0x01, 0xF1, 0x1E, 0xF0, 0x55];
+
+    let mut small = Processor::with_seed(0);
+    small.load(&rom);
+    for _ in 0..4 {
+        small.tick();
+    }
+    assert_eq!(
+        small.try_tick().unwrap_err().kind,
+        ErrorKind::MemoryOutOfBounds(0x1000)
+    );
+
+    let mut large = Processor::with_bus(Ram::<0x10000>::new(), 0);
+    assert_eq!(large.bus().size(), 0x10000);
+    large.load(&rom);
+    for _ in 0..5 {
+        large.tick();
+    }
+    assert_eq!(large.read_ram(0x1000), Some(0x5A));
+    assert_eq!(large.read_ram(0x10000), None);
+}
+
+// PC at `pc` on a 64K bus with the opcode there
+fn at_top(pc: u16, opcode: [u8; 2]) -> Processor<Ram<0x10000>> {
+    let mut processor = Processor::with_bus(Ram::<0x10000>::new(), 0);
+    processor.write_ram(pc as usize, opcode[0]);
+    processor.write_ram(pc as usize + 1, opcode[1]);
+    let mut registers = processor.registers();
+    registers.pc = pc;
+    processor.set_registers(registers);
+    processor
+}
+
+#[test]
+fn pc_doesnt_wrap_on_a_64k_bus() {
+    // nowhere to go after the last word
+    let mut processor = at_top(0xFFFE, [0x60, 0x01]);
+    let error = processor.try_tick().unwrap_err();
+    assert_eq!(error.kind, ErrorKind::PcOutOfBounds);
+    assert_eq!(error.address, 0xFFFE);
+    assert_eq!(processor.registers().pc, 0xFFFE);
+
+    // or to skip to from the one before it, SE V0, 0x00
+    let mut processor = at_top(0xFFFC, [0x30, 0x00]);
+    let error = processor.try_tick().unwrap_err();
+    assert_eq!(error.kind, ErrorKind::PcOutOfBounds);
+    assert_eq!(error.address, 0xFFFC);
+
+    // not skipping is fine
+    let mut processor = at_top(0xFFFC, [0x30, 0x01]);
+    processor.try_tick().unwrap();
+    assert_eq!(processor.registers().pc, 0xFFFE);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn recompiled_pc_doesnt_wrap_on_a_64k_bus() {
+    for (pc, opcode) in [(0xFFFE, [0x60, 0x01]), (0xFFFC, [0x30, 0x00])] {
+        let mut processor = at_top(pc, opcode);
+        processor.set_recompiler(true);
+        let error = processor.run(2).unwrap_err();
+        assert_eq!((error.kind, error.address), (ErrorKind::PcOutOfBounds, pc));
+    }
+}
@@ -0,0 +1,190 @@
+use backend::cdp1802::{Cdp1802, Io};
+use backend::{Bus, ErrorKind, Processor, Ram};
+
+fn ram(code: &[u8]) -> Ram {
+    let mut ram = Ram::new();
+    for (address, byte) in code.iter().enumerate() {
+        ram.poke(address, *byte);
+    }
+    ram
+}
+
+// Runs 1802 code from address 0 for `steps` instructions
+fn run(code: &[u8], steps: usize) -> (Cdp1802, Ram) {
+    let mut ram = ram(code);
+    let mut cpu = Cdp1802::new();
+    for _ in 0..steps {
+        cpu.step(&mut ram, &mut ()).unwrap();
+    }
+    (cpu, ram)
+}
+
+#[test]
+fn arithmetic_sets_df() {
+    // LDI 0xF0, ADI 0x20
+    let (cpu, _) = run(&[0xF8, 0xF0, 0xFC, 0x20], 2);
+    assert_eq!((cpu.d, cpu.df), (0x10, true));
+
+    // LDI 0x05, SMI 0x06: borrow clears DF
+    let (cpu, _) = run(&[0xF8, 0x05, 0xFF, 0x06], 2);
+    assert_eq!((cpu.d, cpu.df), (0xFF, false));
+
+    // SMBI 0x01 borrows the one left by SMI
+    let (cpu, _) = run(&[0xF8, 0x05, 0xFF, 0x06, 0x7F, 0x01], 3);
+    assert_eq!((cpu.d, cpu.df), (0xFD, true));
+
+    // LDI 0x05, SDI 0x06 is 6 - 5
+    let (cpu, _) = run(&[0xF8, 0x05, 0xFD, 0x06], 2);
+    assert_eq!((cpu.d, cpu.df), (0x01, true));
+
+    // LDI 0x81, SHRC with DF clear, then SHLC
+    let (cpu, _) = run(&[0xF8, 0x81, 0x76, 0x7E], 3);
+    assert_eq!((cpu.d, cpu.df), (0x81, false));
+}
+
+#[test]
+fn registers_and_memory() {
+    // LDI 0x12, PHI R3, LDI 0x34, PLO R3, INC R3, GHI R3
+    let (cpu, _) = run(&[0xF8, 0x12, 0xB3, 0xF8, 0x34, 0xA3, 0x13, 0x93], 6);
+    assert_eq!(cpu.r[3], 0x1235);
+    assert_eq!(cpu.d, 0x12);
+
+    // LDI 0x40, PLO R4, LDI 0xAB, STR R4, SEX R4, LDI 0, LDX
+    let (cpu, ram) = run(
+        &[0xF8, 0x40, 0xA4, 0xF8, 0xAB, 0x54, 0xE4, 0xF8, 0x00, 0xF0],
+        7,
+    );
+    assert_eq!(ram.as_slice()[0x40], 0xAB);
+    assert_eq!(cpu.d, 0xAB);
+
+    // addresses past the end of RAM wrap around like on the VIP
+    let (_, ram) = run(&[0xF8, 0x10, 0xB4, 0xF8, 0x77, 0x54], 4);
+    assert_eq!(ram.as_slice()[0], 0x77);
+}
+
+#[test]
+fn branches() {
+    // LDI 0, BZ 0x06, IDL, IDL, ... lands on LDI 0x42
+    let (cpu, _) = run(&[0xF8, 0x00, 0x32, 0x06, 0x00, 0x00, 0xF8, 0x42], 3);
+    assert_eq!(cpu.d, 0x42);
+
+    // LBR 0x0010 takes 3 cycles
+    let mut ram = ram(&[0xC0, 0x00, 0x10]);
+    let mut cpu = Cdp1802::new();
+    assert_eq!(cpu.step(&mut ram, &mut ()), Ok(3));
+    assert_eq!(cpu.r[0], 0x0010);
+
+    // SEQ, LSQ skips the LBR
+    let (cpu, _) = run(&[0x7B, 0xCD, 0xC0, 0x00, 0xF8, 0x42], 3);
+    assert_eq!(cpu.d, 0x42);
+}
+
+#[test]
+fn ef_lines_come_from_io() {
+    struct Ef3;
+    impl Io for Ef3 {
+        fn ef(&self, line: u8) -> bool {
+            line == 3
+        }
+    }
+    // B3 0x04, IDL, IDL, LDI 0x42
+    let mut ram = ram(&[0x36, 0x04, 0x00, 0x00, 0xF8, 0x42]);
+    let mut cpu = Cdp1802::new();
+    cpu.step(&mut ram, &mut Ef3).unwrap();
+    cpu.step(&mut ram, &mut Ef3).unwrap();
+    assert_eq!(cpu.d, 0x42);
+}
+
+#[test]
+fn interrupts_and_dma() {
+    // IDL halts until DMA or an interrupt
+    let (mut cpu, mut ram) = run(&[0x00], 1);
+    assert!(cpu.idle);
+    assert_eq!(cpu.step(&mut ram, &mut ()), Ok(1));
+    assert_eq!(cpu.dma_out(&mut ram), 0x00);
+    assert_eq!(cpu.r[0], 2);
+    assert!(!cpu.idle);
+
+    cpu.x = 5;
+    cpu.p = 3;
+    assert!(cpu.interrupt());
+    assert_eq!((cpu.t, cpu.p, cpu.x, cpu.ie), (0x53, 1, 2, false));
+    // masked now
+    assert!(!cpu.interrupt());
+}
+
+// LD V3, 0x05; SYS 0x300; JP 0x204
+const HYBRID: [u8; 6] = [0x63, 0x05, 0x03, 0x00, 0x12, 0x04];
+
+// V3 += 1 through R6, light the top left byte of the display through RB,
+// I = 0x234 through RA, then SEP R4
+const SUBROUTINE: [u8; 14] = [
+    0x06, 0xFC, 0x01, 0x56, // LDN R6, ADI 1, STR R6
+    0xF8, 0xFF, 0x5B, // LDI 0xFF, STR RB
+    0xF8, 0x34, 0xAA, 0xF8, 0x02, 0xBA, // LDI 0x34, PLO RA, LDI 0x02, PHI RA
+    0xD4,
+];
+
+fn hybrid(setup: fn(&mut Processor)) -> Processor {
+    let mut processor = Processor::with_seed(0);
+    setup(&mut processor);
+    processor.set_cdp1802(true);
+    processor.load(&HYBRID);
+    processor.load_at(0x300, &SUBROUTINE).unwrap();
+    processor.run(3).unwrap();
+    processor
+}
+
+#[test]
+fn machine_code_sees_the_vip_layout() {
+    let processor = hybrid(|_| {});
+    let registers = processor.registers();
+    assert_eq!(registers.v[3], 6);
+    assert_eq!(registers.i, 0x234);
+    assert_eq!(registers.pc, 0x204);
+    assert_eq!(
+        processor.get_display()[..9],
+        [true, true, true, true, true, true, true, true, false]
+    );
+    assert_eq!(processor.read_ram(0xEF3), Some(6));
+    assert_eq!(
+        processor.cdp1802().unwrap().r[3],
+        0x300 + SUBROUTINE.len() as u16
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn machine_code_runs_under_the_cache_and_recompiler() {
+    let expected = hybrid(|_| {});
+    for setup in [
+        (|processor: &mut Processor| processor.set_decode_cache(true)) as fn(&mut Processor),
+        |processor| processor.set_recompiler(true),
+    ] {
+        let processor = hybrid(setup);
+        assert_eq!(processor.registers(), expected.registers());
+        assert_eq!(processor.get_display(), expected.get_display());
+    }
+}
+
+#[test]
+fn sys_needs_the_cdp1802() {
+    let mut processor = Processor::with_seed(0);
+    processor.load(&HYBRID);
+    processor.tick();
+    assert_eq!(
+        processor.try_tick().unwrap_err().kind,
+        ErrorKind::InvalidOpcode
+    );
+}
+
+#[test]
+fn machine_code_that_never_returns_faults() {
+    let mut processor = Processor::with_seed(0);
+    processor.set_cdp1802(true);
+    processor.load(&[0x03, 0x00]);
+    processor.load_at(0x300, &[0x30, 0x00]).unwrap(); // BR 0x00
+    let error = processor.try_tick().unwrap_err();
+    assert_eq!(error.kind, ErrorKind::MachineCodeTimeout(0x300));
+    assert_eq!(error.address, 0x200);
+}
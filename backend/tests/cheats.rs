@@ -0,0 +1,179 @@
+#![cfg(feature = "std")]
+
+use std::env;
+use std::fs;
+use std::process;
+
+use backend::cheats::{rom_hash, Cheat, CheatList, Comparison, MemorySearch, ParseError};
+use backend::Processor;
+
+const CHEATS: &str =
+    "# infinite lives\nfreeze 0x2A4 0x03\n\n  patch 0x200 0x1260A0  \nfreeze 100 7\n";
+
+#[test]
+fn parses_freezes_and_patches() {
+    let list = CheatList::parse(CHEATS).unwrap();
+    assert_eq!(
+        list.cheats,
+        [
+            Cheat::Freeze {
+                address: 0x2A4,
+                value: 3
+            },
+            Cheat::Patch {
+                address: 0x200,
+                bytes: vec![0x12, 0x60, 0xA0]
+            },
+            Cheat::Freeze {
+                address: 100,
+                value: 7
+            },
+        ]
+    );
+}
+
+// The number parser shared with the manifest, test ROM and assembler formats
+#[test]
+fn numbers_are_hex_or_decimal() {
+    let freeze = |text: &str| match CheatList::parse(text).ok()?.cheats.as_slice() {
+        [Cheat::Freeze { address, value }] => Some((*address, *value)),
+        _ => None,
+    };
+    assert_eq!(freeze("freeze 0xabcd 0XFF"), Some((0xABCD, 0xFF)));
+    assert_eq!(freeze("freeze 0XaBcD 255"), Some((0xABCD, 255)));
+    assert_eq!(freeze("\tfreeze  0x0200\t007  "), Some((0x200, 7)));
+    assert_eq!(freeze("freeze 0xFFFF 0"), Some((0xFFFF, 0)));
+    for text in [
+        "freeze 0x10000 0",
+        "freeze 65536 0",
+        "freeze 0x200 0x100",
+        "freeze 0x200 256",
+        "freeze 0x200 0x10000000000000000",
+        "freeze 0x 1",
+        "freeze 0x200 +1",
+        "freeze 0x200 0x+1",
+        "freeze 0x200 -1",
+        "freeze 0x200 1h",
+        "freeze 0x200 0x1G",
+    ] {
+        assert_eq!(freeze(text), None, "{}", text);
+    }
+}
+
+#[test]
+fn parse_errors_give_the_line() {
+    for (text, line) in [
+        ("freeze 0x200", 1),
+        ("# ok\nfreeze 0x200 0x100", 2),
+        ("\n\npatch 0x200 0x123", 3),
+        ("patch 0x200 1260", 1),
+        ("patch 0x200 0x", 1),
+        ("freeze 0x10000 0", 1),
+        ("poke 0x200 0", 1),
+    ] {
+        assert_eq!(CheatList::parse(text), Err(ParseError { line }), "{}", text);
+    }
+    assert_eq!(
+        ParseError { line: 4 }.to_string(),
+        "invalid cheat on line 4"
+    );
+}
+
+#[test]
+fn display_round_trips() {
+    let list = CheatList::parse(CHEATS).unwrap();
+    let text = list.to_string();
+    assert_eq!(
+        text,
+        "freeze 0x2A4 0x03\npatch 0x200 0x1260A0\nfreeze 0x064 0x07\n"
+    );
+    assert_eq!(CheatList::parse(&text).unwrap(), list);
+}
+
+#[test]
+fn apply_writes_every_cheat() {
+    let mut processor = Processor::with_seed(0);
+    let list = CheatList::parse(CHEATS).unwrap();
+    list.apply(&mut processor);
+    let ram = processor.ram();
+    assert_eq!(ram[0x2A4], 3);
+    assert_eq!(ram[0x200..0x203], [0x12, 0x60, 0xA0]);
+    assert_eq!(ram[100], 7);
+
+    // freezes hold against the program writing over them
+    processor.write_ram(0x2A4, 0);
+    list.apply(&mut processor);
+    assert_eq!(processor.ram()[0x2A4], 3);
+}
+
+#[test]
+fn narrowing_finds_a_counter() {
+    let mut processor = Processor::with_seed(0);
+    processor.write_ram(0x300, 3);
+    processor.write_ram(0x301, 3);
+    let mut search = MemorySearch::new(&processor);
+    assert_eq!(search.candidates().len(), processor.ram().len());
+
+    search.narrow(&processor, Comparison::Equal(3));
+    // the font has no 3s in it
+    assert_eq!(search.candidates(), [0x300, 0x301]);
+
+    // lose a life
+    processor.write_ram(0x300, 2);
+    let mut changed = MemorySearch::new(&processor);
+    search.narrow(&processor, Comparison::Decreased);
+    assert_eq!(search.candidates(), [0x300]);
+
+    processor.write_ram(0x301, 4);
+    changed.narrow(&processor, Comparison::Changed);
+    assert_eq!(changed.candidates(), [0x301]);
+}
+
+#[test]
+fn narrowing_compares_with_the_last_narrow() {
+    let mut processor = Processor::with_seed(0);
+    let mut search = MemorySearch::new(&processor);
+    processor.write_ram(0x300, 1);
+    processor.write_ram(0x400, 1);
+    search.narrow(&processor, Comparison::Increased);
+    assert_eq!(search.candidates(), [0x300, 0x400]);
+
+    // nothing changed since then
+    search.narrow(&processor, Comparison::Unchanged);
+    assert_eq!(search.candidates(), [0x300, 0x400]);
+    processor.write_ram(0x400, 2);
+    search.narrow(&processor, Comparison::Increased);
+    assert_eq!(search.candidates(), [0x400]);
+}
+
+#[test]
+fn saved_by_rom_hash() {
+    let dir = env::temp_dir().join(format!("chip8-cheats-{}", process::id()));
+    let rom = [0x12, 0x00];
+    let other = [0x12, 0x02];
+    assert_eq!(
+        CheatList::load_for_rom(&dir, &rom).unwrap(),
+        CheatList::new()
+    );
+
+    let list = CheatList::parse(CHEATS).unwrap();
+    list.save_for_rom(&dir, &rom).unwrap();
+    let path = dir.join(format!("{:016x}.cht", rom_hash(&rom)));
+    assert!(path.exists());
+    assert_eq!(CheatList::load_for_rom(&dir, &rom).unwrap(), list);
+    assert!(CheatList::load_for_rom(&dir, &other)
+        .unwrap()
+        .cheats
+        .is_empty());
+
+    fs::write(&path, "freeze").unwrap();
+    let error = CheatList::load_for_rom(&dir, &rom).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn rom_hash_is_fnv1a() {
+    assert_eq!(rom_hash(&[]), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(rom_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+}
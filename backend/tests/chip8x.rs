@@ -0,0 +1,223 @@
+use backend::chip8x::{Chip8x, Color};
+use backend::machine::{Control, InputSource, Machine, VideoSink};
+use backend::{ErrorKind, Processor};
+
+fn chip8x(rom: &[u8]) -> Processor {
+    let mut processor = Processor::with_seed(0);
+    processor.set_chip8x(true);
+    processor.load(rom);
+    processor
+}
+
+fn run(rom: &[u8], ticks: usize) -> Processor {
+    let mut processor = chip8x(rom);
+    processor.run(ticks).unwrap();
+    processor
+}
+
+fn colors(processor: &Processor) -> &Chip8x {
+    processor.chip8x().unwrap()
+}
+
+#[test]
+fn starts_red_on_blue() {
+    let processor = chip8x(&[]);
+    assert_eq!(colors(&processor).background(), Color::Blue);
+    assert_eq!(colors(&processor).foreground(63, 31), Color::Red);
+    assert_eq!(colors(&processor).pixel(0, false), Color::Blue);
+    assert_eq!(colors(&processor).pixel(0, true), Color::Red);
+}
+
+#[test]
+fn programs_start_at_0x300() {
+    let processor = chip8x(&[0x13, 0x00]);
+    assert_eq!(processor.start_address(), 0x300);
+    assert_eq!(processor.registers().pc, 0x300);
+    assert_eq!(processor.ram()[0x300..0x302], [0x13, 0x00]);
+
+    // a start address set afterwards wins, and turning it off goes back
+    let mut processor = Processor::with_seed(0);
+    processor.set_chip8x(true);
+    processor.set_start_address(0x600);
+    assert_eq!(processor.start_address(), 0x600);
+    processor.set_chip8x(false);
+    assert_eq!(processor.start_address(), 0x200);
+}
+
+#[test]
+fn background_cycles() {
+    let cycle = [0x02, 0xA0];
+    let expected = [Color::Black, Color::Green, Color::Red, Color::Blue];
+    let mut processor = chip8x(&cycle.repeat(4));
+    for color in expected {
+        processor.tick();
+        assert_eq!(colors(&processor).background(), color);
+    }
+}
+
+#[test]
+fn blocks_of_zones() {
+    // V0 = 0x12 is columns 2 to 3, V1 = 0x01 the second block of 4 lines
+    // and V2 green. BXY0 with X = 0, Y = 2
+    let processor = run(&[0x60, 0x12, 0x61, 0x01, 0x62, 0x04, 0xB0, 0x20], 4);
+    let colors = colors(&processor);
+    for y in 0..32 {
+        for x in 0..64 {
+            let inside = (16..32).contains(&x) && (4..8).contains(&y);
+            let expected = if inside { Color::Green } else { Color::Red };
+            assert_eq!(colors.foreground(x, y), expected, "({}, {})", x, y);
+        }
+    }
+}
+
+#[test]
+fn lines_of_zones_wrap() {
+    // V4 = 0x07 is the last column, V5 = 30 the second to last line, V6
+    // white. BXYN with X = 4, Y = 6 and N = 3 wraps to the top
+    let processor = run(&[0x64, 0x07, 0x65, 0x1E, 0x66, 0x07, 0xB4, 0x63], 4);
+    let colors = colors(&processor);
+    for y in [30, 31, 0] {
+        assert_eq!(colors.foreground(56, y), Color::White);
+        assert_eq!(colors.foreground(55, y), Color::Red);
+    }
+    assert_eq!(colors.foreground(56, 1), Color::Red);
+}
+
+#[test]
+fn nibbles_add_without_carrying() {
+    // V0 = 0x9C, V1 = 0x85, V0 += V1 a nibble at a time
+    let processor = run(&[0x60, 0x9C, 0x61, 0x85, 0x6F, 0x55, 0x50, 0x11], 4);
+    let registers = processor.registers();
+    assert_eq!(registers.v[0], 0x11);
+    assert_eq!(registers.v[0xF], 0x55);
+}
+
+#[test]
+fn second_keypad() {
+    // V3 = 0x0A, skip if key A is down on keypad 2, V0 = 1, skip if it's
+    // up, V1 = 1
+    let rom = [0x63, 0x0A, 0xE3, 0xF2, 0x60, 0x01, 0xE3, 0xF5, 0x61, 0x01];
+    let mut processor = chip8x(&rom);
+    processor.keypress(0xA, true);
+    processor.run(4).unwrap();
+    assert_eq!(processor.registers().v[..2], [1, 0]);
+
+    let mut processor = chip8x(&rom);
+    processor.second_keypress(0xA, true);
+    processor.run(4).unwrap();
+    assert_eq!(processor.registers().v[..2], [0, 1]);
+}
+
+#[test]
+fn off_by_default() {
+    let mut processor = Processor::with_seed(0);
+    processor.second_keypress(0, true);
+    assert!(processor.chip8x().is_none());
+
+    // BNNN still jumps, and the CHIP-8X-only opcodes are invalid
+    processor.load(&[0x60, 0x02, 0xB2, 0x00]);
+    processor.run(2).unwrap();
+    assert_eq!(processor.registers().pc, 0x202);
+
+    for opcode in [[0x50, 0x11], [0xE0, 0xF2], [0xE0, 0xF5], [0x02, 0xA0]] {
+        let mut processor = Processor::with_seed(0);
+        processor.load(&opcode);
+        assert_eq!(processor.try_tick().unwrap_err().kind, ErrorKind::InvalidOpcode);
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn same_through_the_recompiler() {
+    let rom = [
+        0x60, 0x12, 0x61, 0x01, 0x62, 0x04, 0xB0, 0x20, // green block
+        0x02, 0xA0, // black background
+        0x50, 0x11, // nibble add
+        0x13, 0x0C, // loop
+    ];
+    let mut interpreted = chip8x(&rom);
+    interpreted.run(20).unwrap();
+    let mut compiled = chip8x(&rom);
+    compiled.set_recompiler(true);
+    compiled.run(20).unwrap();
+    assert_eq!(compiled.chip8x(), interpreted.chip8x());
+    assert_eq!(compiled.registers(), interpreted.registers());
+}
+
+#[test]
+fn reset_restores_the_colors() {
+    let mut processor = run(&[0x02, 0xA0], 1);
+    processor.reset();
+    assert_eq!(colors(&processor), &Chip8x::new());
+}
+
+#[derive(Default)]
+struct Colored(Vec<Color>);
+
+impl VideoSink for Colored {
+    fn present(&mut self, _display: &[bool]) {
+        panic!("colors weren't passed on");
+    }
+
+    fn present_colors(&mut self, display: &[bool], colors: &Chip8x) {
+        self.0 = display
+            .iter()
+            .enumerate()
+            .map(|(i, lit)| colors.pixel(i, *lit))
+            .collect();
+    }
+}
+
+struct NoKeys;
+
+impl InputSource for NoKeys {
+    fn poll(&mut self, _keys: &mut [bool; 16]) -> Control {
+        Control::Continue
+    }
+}
+
+#[test]
+fn machine_presents_colors() {
+    // font sprite for 0 at the top left, on black
+    let mut machine = Machine::new(
+        chip8x(&[0x02, 0xA0, 0xD0, 0x05, 0x13, 0x04]),
+        Colored::default(),
+        (),
+        NoKeys,
+    );
+    machine.run_frame().unwrap();
+    let pixels = &machine.video().0;
+    let red = Color::Red;
+    assert_eq!(pixels[..5], [red, red, red, red, Color::Black]);
+    assert_eq!(pixels[64..66], [red, Color::Black]);
+}
+
+// Holds key A on the second keypad only
+struct SecondKeypad;
+
+impl InputSource for SecondKeypad {
+    fn poll(&mut self, _keys: &mut [bool; 16]) -> Control {
+        Control::Continue
+    }
+
+    fn poll_second(&mut self, keys: &mut [bool; 16]) {
+        keys[0xA] = true;
+    }
+}
+
+#[test]
+fn machine_passes_on_the_second_keypad() {
+    // V3 = 0x0A, skip if key A is up on keypad 2, V0 = 1, then spin
+    let rom = [0x63, 0x0A, 0xE3, 0xF5, 0x60, 0x01, 0x13, 0x06];
+    let mut machine = Machine::new(chip8x(&rom), Colored::default(), (), SecondKeypad);
+    machine.run_frame().unwrap();
+    assert_eq!(machine.processor().registers().v[0], 1);
+}
+
+#[test]
+fn color_bits() {
+    assert_eq!(Color::from_bits(0x0D), Color::Yellow);
+    assert_eq!(Color::Yellow.rgb(), [0xFF, 0xFF, 0]);
+    assert_eq!(Color::Violet.rgb(), [0xFF, 0, 0xFF]);
+    assert_eq!(Color::Aqua.rgb(), [0, 0xFF, 0xFF]);
+}
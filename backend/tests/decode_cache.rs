@@ -0,0 +1,82 @@
+// Cached and uncached processors have to agree, including when programs
+// rewrite code that has already been decoded
+#![cfg(feature = "std")]
+
+use backend::Processor;
+
+// Calls a subroutine at 0x20A, then stores V0 and V1 over its first
+// instruction and calls it again. A stale cache entry would run the old
+// LD VB, 0x01 the second time
+const SELF_MODIFYING: [u8; 20] = [
+    0xA2, 0x0A, // LD I, 0x20A
+    0x60, 0x6A, // LD V0, 0x6A
+    0x61, 0x42, // LD V1, 0x42
+    0x22, 0x0A, // CALL 0x20A
+    0x12, 0x0E, // JP 0x20E
+    0x6B, 0x01, // LD VB, 0x01
+    0x00, 0xEE, // RET
+    0xF1, 0x55, // LD [I], V1 -> LD VA, 0x42 at 0x20A
+    0x22, 0x0A, // CALL 0x20A
+    0x00, 0x00, // NOP
+];
+
+fn run(processor: &mut Processor, ticks: usize) {
+    for _ in 0..ticks {
+        processor.try_tick().unwrap();
+    }
+}
+
+#[test]
+fn cache_sees_self_modifying_writes() {
+    let mut processor = Processor::with_seed(0);
+    processor.set_decode_cache(true);
+    processor.load(&SELF_MODIFYING);
+    run(&mut processor, 10);
+    let registers = processor.registers();
+    assert_eq!(registers.pc, 0x20C);
+    assert_eq!(registers.v[0xA], 0x42);
+    assert_eq!(registers.v[0xB], 0x01);
+}
+
+#[test]
+fn cache_invalidates_rewritten_instruction() {
+    // LD V0, 0x01, JP 0x204, then JP 0x204 forever
+    let rom = [0x60, 0x01, 0x12, 0x04, 0x12, 0x04];
+    let mut processor = Processor::with_seed(0);
+    processor.set_decode_cache(true);
+    processor.load(&rom);
+    run(&mut processor, 4);
+    assert_eq!(processor.registers().pc, 0x204);
+
+    // only the second byte, the entry starting a byte earlier has to go
+    // too
+    processor.write_ram(0x205, 0x00);
+    run(&mut processor, 1);
+    assert_eq!(processor.registers().pc, 0x200);
+}
+
+#[test]
+fn cached_and_uncached_agree() {
+    let rom = [
+        0x60, 0x05, // LD V0, 0x05
+        0xA3, 0x00, // LD I, 0x300
+        0xF0, 0x33, // LD B, V0
+        0xF2, 0x65, // LD V2, [I]
+        0x70, 0xFF, // ADD V0, 0xFF
+        0x81, 0x04, // ADD V1, V0
+        0x30, 0x00, // SE V0, 0x00
+        0x12, 0x02, // JP 0x202
+        0x12, 0x10, // JP 0x210
+    ];
+    let mut uncached = Processor::with_seed(0);
+    let mut cached = Processor::with_seed(0);
+    cached.set_decode_cache(true);
+    uncached.load(&rom);
+    cached.load(&rom);
+
+    for _ in 0..100 {
+        uncached.try_tick().unwrap();
+        cached.try_tick().unwrap();
+        assert_eq!(uncached.registers(), cached.registers());
+    }
+}
@@ -0,0 +1,175 @@
+#![cfg(feature = "std")]
+
+use backend::env::{Env, OBSERVATION_SIZE};
+
+// LD I, 0x300, then count frames into 0x300 at three instructions a frame
+const COUNTER: [u8; 8] = [0xA3, 0x00, 0x70, 0x01, 0xF0, 0x55, 0x12, 0x02];
+
+// Draws random bytes across the top of the screen forever
+const NOISE: [u8; 12] = [
+    0xA3, 0x00, // LD I, 0x300
+    0xC0, 0xFF, // RND V0, 0xFF
+    0xF0, 0x55, // LD [I], V0
+    0xD1, 0x21, // DRW V1, V2, 1
+    0x71, 0x08, // ADD V1, 0x08
+    0x12, 0x02, // JP 0x202
+];
+
+fn counter() -> Env {
+    Env::new(&COUNTER, 0, |ram| ram[0x300] as f32)
+        .unwrap()
+        .with_ticks_per_frame(3)
+}
+
+fn noise(seed: u64) -> Env {
+    Env::new(&NOISE, seed, |_| 0.0).unwrap()
+}
+
+#[test]
+fn rom_must_fit() {
+    assert!(Env::new(&[0; 0x1000], 0, |_| 0.0).is_none());
+}
+
+#[test]
+fn reward_is_the_change_in_score() {
+    let mut env = counter();
+    for frame in 1..=3 {
+        let step = env.step(0);
+        assert_eq!(step.reward, 1.0);
+        assert!(!step.done);
+        assert_eq!(env.frames(), frame);
+    }
+    assert_eq!(env.processor().ram()[0x300], 3);
+}
+
+#[test]
+fn frame_skip_holds_the_action() {
+    let mut env = counter().with_frame_skip(4);
+    let step = env.step(0);
+    assert_eq!(step.reward, 4.0);
+    assert_eq!(env.frames(), 4);
+
+    // 0 still runs a frame
+    let mut env = counter().with_frame_skip(0);
+    assert_eq!(env.step(0).reward, 1.0);
+}
+
+#[test]
+fn done_ends_the_episode() {
+    let mut env = counter()
+        .with_frame_skip(2)
+        .with_done(|ram| ram[0x300] >= 5);
+    assert!(!env.step(0).done);
+    assert!(!env.step(0).done);
+    // stops partway through the frames it was asked for
+    let step = env.step(0);
+    assert!(step.done);
+    assert_eq!((step.reward, env.frames()), (1.0, 5));
+
+    // further steps leave it be
+    let step = env.step(0);
+    assert!(step.done);
+    assert_eq!((step.reward, env.frames()), (0.0, 5));
+
+    env.reset();
+    assert!(!env.is_done());
+    assert_eq!(env.frames(), 0);
+}
+
+#[test]
+fn max_frames_and_crashes_end_the_episode() {
+    let mut env = counter().with_max_frames(2);
+    assert!(!env.step(0).done);
+    assert!(env.step(0).done);
+
+    // RET with nothing to return to
+    let mut env = Env::new(&[0x00, 0xEE], 0, |_| 0.0).unwrap();
+    assert!(env.step(0).done);
+    assert_eq!(env.frames(), 0);
+}
+
+#[test]
+fn actions_are_key_bitmasks() {
+    // LD V0, K, then store it at 0x300
+    let rom = [0xF0, 0x0A, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x06];
+    let mut env = Env::new(&rom, 0, |ram| ram[0x300] as f32).unwrap();
+    assert_eq!(env.step(0).reward, 0.0);
+    assert_eq!(env.step(1 << 7).reward, 7.0);
+}
+
+#[test]
+fn observations_pack_eight_pixels_a_byte() {
+    // the 0 from the font at the top left
+    let mut env = Env::new(&[0xD0, 0x05, 0x12, 0x02], 0, |_| 0.0).unwrap();
+    let blank = env.reset();
+    assert_eq!(blank, [0; OBSERVATION_SIZE]);
+    let observation = env.step(0).observation;
+    let rows: Vec<_> = (0..5).map(|row| observation[row * 8]).collect();
+    assert_eq!(rows, [0xF0, 0x90, 0x90, 0x90, 0xF0]);
+    assert_eq!(observation, env.observation());
+}
+
+#[test]
+fn same_seed_same_rollout() {
+    let mut a = noise(42);
+    let mut b = noise(42);
+    let mut c = noise(43);
+    let mut differs = false;
+    for _ in 0..4 {
+        let step = a.step(0);
+        assert_eq!(step, b.step(0));
+        differs |= step != c.step(0);
+    }
+    assert!(differs);
+
+    // reseeding the start snapshot
+    c.seed(42);
+    c.reset();
+    a.reset();
+    for _ in 0..4 {
+        assert_eq!(a.step(0), c.step(0));
+    }
+}
+
+#[test]
+fn clones_roll_out_identically() {
+    let mut env = noise(7);
+    env.step(0);
+    let mut clone = env.clone();
+    for _ in 0..8 {
+        assert_eq!(env.step(0), clone.step(0));
+    }
+
+    // and start from the same snapshot
+    assert_eq!(env.reset(), clone.reset());
+    assert_eq!(env.step(0), clone.step(0));
+}
+
+#[test]
+fn set_start_moves_the_reset_point() {
+    let mut env = counter();
+    env.step(0);
+    env.step(0);
+    env.set_start();
+    env.step(0);
+    env.reset();
+    assert_eq!(env.processor().ram()[0x300], 2);
+    assert_eq!(env.step(0).reward, 1.0);
+}
+
+#[test]
+fn recompiled_rollouts_match() {
+    let mut interpreted = noise(3);
+    let mut compiled = noise(3).with_recompiler(true);
+    for _ in 0..8 {
+        assert_eq!(interpreted.step(0), compiled.step(0));
+    }
+
+    // a clone keeps the compiled blocks, and reset goes back to a
+    // snapshot with the recompiler on
+    let mut clone = compiled.clone();
+    assert_eq!(clone.step(0), interpreted.step(0));
+    clone.reset();
+    interpreted.reset();
+    assert_eq!(clone.step(0), interpreted.step(0));
+}
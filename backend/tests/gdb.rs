@@ -0,0 +1,151 @@
+#![cfg(feature = "std")]
+
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::time::Duration;
+
+use backend::gdb::{GdbStub, Status};
+use backend::Processor;
+
+// LD V0, 0x01, then ADD V0, 0x01 forever
+const ROM: [u8; 6] = [0x60, 0x01, 0x70, 0x01, 0x12, 0x02];
+
+// A stub and the client end of a loopback connection to it
+fn connect() -> (GdbStub, TcpStream) {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let timeout = Some(Duration::from_secs(5));
+    client.set_read_timeout(timeout).unwrap();
+    let (stream, _) = listener.accept().unwrap();
+    (GdbStub::new(stream).unwrap(), client)
+}
+
+struct Session {
+    stub: GdbStub,
+    client: TcpStream,
+    processor: Processor,
+}
+
+impl Session {
+    fn new() -> Self {
+        let (stub, client) = connect();
+        let mut processor = Processor::with_seed(0);
+        processor.load(&ROM);
+        Self {
+            stub,
+            client,
+            processor,
+        }
+    }
+
+    fn send(&mut self, command: &str, ticks: usize) -> Status {
+        let sum = command.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+        let packet = format!("${}#{:02x}", command, sum);
+        self.client.write_all(packet.as_bytes()).unwrap();
+        self.stub.run_frame(&mut self.processor, ticks).unwrap()
+    }
+
+    // Reads the ack and the reply packet, and returns the reply's data
+    fn reply(&mut self) -> String {
+        let mut received = Vec::new();
+        let mut byte = [0];
+        // up to the '#' and the two checksum digits
+        while received.len() < 3 || received[received.len() - 3] != b'#' {
+            self.client.read_exact(&mut byte).unwrap();
+            received.push(byte[0]);
+        }
+        let text = String::from_utf8(received).unwrap();
+        let start = text.find('$').unwrap();
+        assert_eq!(&text[..start], "+");
+        let (data, sum) = text[start + 1..].split_once('#').unwrap();
+        let expected = data.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+        assert_eq!(sum, format!("{:02x}", expected));
+        data.to_string()
+    }
+
+    fn ack(&mut self) {
+        let mut ack = [0];
+        self.client.read_exact(&mut ack).unwrap();
+        assert_eq!(&ack, b"+");
+    }
+
+    fn command(&mut self, command: &str) -> String {
+        self.send(command, 0);
+        self.reply()
+    }
+}
+
+#[test]
+fn scripted_session() {
+    let mut session = Session::new();
+    assert_eq!(session.stub.status(), Status::Halted);
+    assert_eq!(session.command("?"), "S05");
+
+    // V0-VF, I, PC, SP, DT and ST, little endian
+    let registers = format!("{}00000002000000", "00".repeat(16));
+    assert_eq!(session.command("g"), registers);
+    let written = format!("G0042{}00030002000000", "00".repeat(14));
+    assert_eq!(session.command(&written), "OK");
+    assert_eq!(session.processor.registers().v[1], 0x42);
+    assert_eq!(session.processor.registers().i, 0x300);
+    assert_eq!(session.command("G00"), "E01");
+
+    assert_eq!(session.command("p11"), "0002");
+    assert_eq!(session.command("P0=07"), "OK");
+    assert_eq!(session.command("p0"), "07");
+    assert_eq!(session.command("p15"), "E01");
+
+    assert_eq!(session.command("m200,4"), "60017001");
+    assert_eq!(session.command("M300,2:abcd"), "OK");
+    assert_eq!(session.processor.ram()[0x300..0x302], [0xAB, 0xCD]);
+    assert_eq!(session.command("m300,2"), "abcd");
+    assert_eq!(session.command("MFFF,2:abcd"), "E14");
+    assert_eq!(session.command("Mffffffffffffffff,2:abcd"), "E01");
+
+    // one step runs LD V0, 0x01
+    assert_eq!(session.command("Z0,204,2"), "OK");
+    assert_eq!(session.command("s"), "S05");
+    assert_eq!(session.processor.registers().pc, 0x202);
+    assert_eq!(session.processor.registers().v[0], 1);
+
+    // continuing stops on the breakpoint, and again after going round
+    // the loop once
+    for v0 in [2, 3] {
+        assert_eq!(session.send("c", 10), Status::Halted);
+        assert_eq!(session.reply(), "S05");
+        assert_eq!(session.processor.registers().pc, 0x204);
+        assert_eq!(session.processor.registers().v[0], v0);
+    }
+
+    assert_eq!(session.command("z0,204,2"), "OK");
+    assert_eq!(session.send("c", 10), Status::Running);
+    session.ack();
+    assert_eq!(session.processor.registers().v[0], 8);
+
+    assert_eq!(session.send("D", 0), Status::Detached);
+    assert_eq!(session.reply(), "OK");
+}
+
+#[test]
+fn interrupt_halts_a_running_target() {
+    let mut session = Session::new();
+    assert_eq!(session.send("c", 4), Status::Running);
+    session.ack();
+
+    session.client.write_all(&[0x03]).unwrap();
+    let pc = session.processor.registers().pc;
+    let status = session.stub.run_frame(&mut session.processor, 4).unwrap();
+    assert_eq!(status, Status::Halted);
+    assert_eq!(session.processor.registers().pc, pc);
+    let mut reply = [0; 7];
+    session.client.read_exact(&mut reply).unwrap();
+    assert_eq!(&reply, b"$S05#b8");
+}
+
+#[test]
+fn closing_the_connection_detaches() {
+    let mut session = Session::new();
+    session.client.shutdown(std::net::Shutdown::Write).unwrap();
+    let status = session.stub.run_frame(&mut session.processor, 4).unwrap();
+    assert_eq!(status, Status::Detached);
+}
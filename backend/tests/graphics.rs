@@ -0,0 +1,146 @@
+// Draws the framebuffer onto an in-memory panel
+
+#![cfg(feature = "embedded-graphics")]
+
+use std::convert::Infallible;
+
+use backend::graphics::{scan_keypad, Framebuffer, VIP_KEYPAD};
+use backend::{Processor, Ram};
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::{OriginDimensions, Point, Size};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::{Drawable, Pixel};
+
+const PANEL_WIDTH: usize = 160;
+const PANEL_HEIGHT: usize = 80;
+
+// None where nothing was drawn, to catch writes outside the framebuffer
+struct MockPanel {
+    pixels: Vec<Option<BinaryColor>>,
+}
+
+impl MockPanel {
+    fn new() -> Self {
+        Self {
+            pixels: vec![None; PANEL_WIDTH * PANEL_HEIGHT],
+        }
+    }
+
+    fn get(&self, x: usize, y: usize) -> Option<BinaryColor> {
+        self.pixels[x + PANEL_WIDTH * y]
+    }
+}
+
+impl OriginDimensions for MockPanel {
+    fn size(&self) -> Size {
+        Size::new(PANEL_WIDTH as u32, PANEL_HEIGHT as u32)
+    }
+}
+
+impl DrawTarget for MockPanel {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if point.x >= 0
+                && point.y >= 0
+                && (point.x as usize) < PANEL_WIDTH
+                && (point.y as usize) < PANEL_HEIGHT
+            {
+                self.pixels[point.x as usize + PANEL_WIDTH * point.y as usize] = Some(color);
+            }
+        }
+        Ok(())
+    }
+}
+
+// draws the font sprite for 0 at (0, 0)
+fn draw_zero() -> Processor {
+    let mut processor = Processor::with_seed(0);
+    processor.load(&[0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05]);
+    for _ in 0..3 {
+        processor.tick();
+    }
+    processor
+}
+
+#[test]
+fn draws_unscaled() {
+    let processor = draw_zero();
+    let mut panel = MockPanel::new();
+    Framebuffer::new(&processor, BinaryColor::On, BinaryColor::Off)
+        .draw(&mut panel)
+        .unwrap();
+
+    // 0xF0, 0x90 rows of the glyph
+    assert_eq!(panel.get(0, 0), Some(BinaryColor::On));
+    assert_eq!(panel.get(3, 0), Some(BinaryColor::On));
+    assert_eq!(panel.get(4, 0), Some(BinaryColor::Off));
+    assert_eq!(panel.get(1, 1), Some(BinaryColor::Off));
+    assert_eq!(panel.get(63, 31), Some(BinaryColor::Off));
+    assert_eq!(panel.get(64, 0), None);
+    assert_eq!(panel.get(0, 32), None);
+}
+
+#[test]
+fn draws_scaled_with_offset_and_palette() {
+    let processor = draw_zero();
+    let mut panel = MockPanel::new();
+    // inverted palette, as on a panel that's lit by default
+    Framebuffer::new(&processor, BinaryColor::Off, BinaryColor::On)
+        .scale(2)
+        .offset(Point::new(10, 5))
+        .draw(&mut panel)
+        .unwrap();
+
+    assert_eq!(panel.get(9, 5), None);
+    assert_eq!(panel.get(10, 4), None);
+    for (x, y) in [(10, 5), (11, 5), (10, 6), (11, 6), (17, 5)] {
+        assert_eq!(panel.get(x, y), Some(BinaryColor::Off), "({}, {})", x, y);
+    }
+    // second pixel of the second row is off in the glyph
+    assert_eq!(panel.get(12, 7), Some(BinaryColor::On));
+    assert_eq!(panel.get(18, 5), Some(BinaryColor::On));
+    assert_eq!(panel.get(137, 68), Some(BinaryColor::On));
+    assert_eq!(panel.get(138, 68), None);
+    assert_eq!(panel.get(137, 69), None);
+}
+
+#[test]
+fn scans_keypad_matrix() {
+    // FX0A stores the first pressed key
+    let mut processor = Processor::with_seed(0);
+    processor.load(&[0xF3, 0x0A, 0xF4, 0x0A]);
+
+    // third row, second column is 8 on the VIP keypad
+    scan_keypad(&mut processor, &VIP_KEYPAD, |row, column| (row, column) == (2, 1));
+    processor.tick();
+    assert_eq!(processor.registers().v[3], 0x8);
+
+    // released keys are cleared, so the second wait keeps waiting
+    scan_keypad(&mut processor, &VIP_KEYPAD, |_, _| false);
+    processor.tick();
+    assert_eq!(processor.registers().pc, 0x202);
+}
+
+#[test]
+fn works_with_any_bus() {
+    let mut processor = Processor::with_bus(Ram::<0x10000>::new(), 0);
+    processor.load(&[0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0xF1, 0x0A]);
+    scan_keypad(&mut processor, &VIP_KEYPAD, |row, column| (row, column) == (0, 3));
+    for _ in 0..4 {
+        processor.tick();
+    }
+    assert_eq!(processor.registers().v[1], 0xC);
+
+    let mut panel = MockPanel::new();
+    Framebuffer::new(&processor, BinaryColor::On, BinaryColor::Off)
+        .draw(&mut panel)
+        .unwrap();
+    assert_eq!(panel.get(0, 0), Some(BinaryColor::On));
+    assert_eq!(panel.get(1, 1), Some(BinaryColor::Off));
+}
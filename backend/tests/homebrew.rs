@@ -0,0 +1,105 @@
+// Assembles the self-checking ROMs in tests/homebrew and runs each one on
+// the interpreter, the decode cache and the recompiler. A ROM's comments
+// can hold key presses for the runner, e.g. `; press A from 20 to 25`
+
+#![cfg(feature = "std")]
+
+use std::fs;
+use std::path::Path;
+
+use backend::testrom::{assemble_self_check, run_self_check, Verdict};
+use backend::Processor;
+
+const FRAMES: usize = 120;
+
+type Setup = fn(&mut Processor);
+
+struct Press {
+    key: usize,
+    frames: std::ops::Range<usize>,
+}
+
+fn presses(source: &str) -> Vec<Press> {
+    source
+        .lines()
+        .filter_map(|line| {
+            let words: Vec<_> = line
+                .trim_start_matches([';', ' '])
+                .split_whitespace()
+                .collect();
+            match words.as_slice() {
+                ["press", key, "from", start, "to", end] => Some(Press {
+                    key: usize::from_str_radix(key, 16).unwrap(),
+                    frames: start.parse().unwrap()..end.parse().unwrap(),
+                }),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn run(rom: &[u8], presses: &[Press], setup: Setup) -> Verdict {
+    let input = |frame, processor: &mut Processor| {
+        if frame == 0 {
+            setup(processor);
+        }
+        for key in 0..16 {
+            let pressed = presses
+                .iter()
+                .any(|press| press.key == key && press.frames.contains(&frame));
+            processor.keypress(key, pressed);
+        }
+    };
+    run_self_check(rom, FRAMES, input).unwrap()
+}
+
+#[test]
+fn homebrew_roms_pass() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/homebrew");
+    let mut sources: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "asm"))
+        .collect();
+    sources.sort();
+    assert!(!sources.is_empty(), "no ROMs in {}", dir.display());
+
+    let setups: [(&str, Setup); 3] = [
+        ("interpreter", |_| {}),
+        ("decode cache", |processor| processor.set_decode_cache(true)),
+        ("recompiler", |processor| processor.set_recompiler(true)),
+    ];
+    let mut failures = Vec::new();
+    for path in sources {
+        let name = path.file_stem().unwrap().to_string_lossy();
+        let source = fs::read_to_string(&path).unwrap();
+        let rom = assemble_self_check(&source).unwrap_or_else(|e| panic!("{}: {}", name, e));
+        let presses = presses(&source);
+        for (core, setup) in setups {
+            match run(&rom, &presses, setup) {
+                Verdict::Pass => {}
+                Verdict::Fail(check) => {
+                    failures.push(format!("{} ({}): check {} failed", name, core, check))
+                }
+                Verdict::Unfinished => failures.push(format!("{} ({}): no verdict", name, core)),
+            }
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn failed_check_is_reported() {
+    let rom = assemble_self_check(
+        "        LD V0, 0x46
+                 LD V1, 3
+                 LD I, signature
+                 LD [I], V1
+         done:   JP done",
+    )
+    .unwrap();
+    assert_eq!(run(&rom, &[], |_| {}), Verdict::Fail(3));
+
+    let rom = assemble_self_check("done: JP done").unwrap();
+    assert_eq!(run(&rom, &[], |_| {}), Verdict::Unfinished);
+}
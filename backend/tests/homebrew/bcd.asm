@@ -0,0 +1,95 @@
+; FX33 across the digit boundaries, read back with FX65
+
+        LD VE, 1
+        LD V3, 0
+        CALL digits
+        SE V0, 0
+        JP fail
+        SE V1, 0
+        JP fail
+        SE V2, 0
+        JP fail
+
+        LD VE, 2
+        LD V3, 9
+        CALL digits
+        SE V0, 0
+        JP fail
+        SE V1, 0
+        JP fail
+        SE V2, 9
+        JP fail
+
+        LD VE, 3
+        LD V3, 10
+        CALL digits
+        SE V0, 0
+        JP fail
+        SE V1, 1
+        JP fail
+        SE V2, 0
+        JP fail
+
+        LD VE, 4
+        LD V3, 99
+        CALL digits
+        SE V0, 0
+        JP fail
+        SE V1, 9
+        JP fail
+        SE V2, 9
+        JP fail
+
+        LD VE, 5
+        LD V3, 100
+        CALL digits
+        SE V0, 1
+        JP fail
+        SE V1, 0
+        JP fail
+        SE V2, 0
+        JP fail
+
+        LD VE, 6
+        LD V3, 128
+        CALL digits
+        SE V0, 1
+        JP fail
+        SE V1, 2
+        JP fail
+        SE V2, 8
+        JP fail
+
+        LD VE, 7
+        LD V3, 255
+        CALL digits
+        SE V0, 2
+        JP fail
+        SE V1, 5
+        JP fail
+        SE V2, 5
+        JP fail
+
+        LD VE, 8        ; the register itself is left alone
+        SE V3, 255
+        JP fail
+
+pass:   LD V0, 0x4F     ; "OK"
+        LD V1, 0x4B
+        LD I, signature
+        LD [I], V1
+done:   JP done
+
+fail:   LD V0, 0x46     ; "F" and the check
+        LD V1, VE
+        LD I, signature
+        LD [I], V1
+        JP done
+
+; V0 to V2 = digits of V3
+digits: LD I, buffer
+        LD B, V3
+        LD V2, [I]
+        RET
+
+buffer: DB 0xAA, 0xAA, 0xAA
@@ -0,0 +1,85 @@
+; VF after DXYN: set when a lit pixel is turned off, cleared otherwise
+
+        LD V0, 0
+        LD V1, 0
+
+        LD VE, 1        ; drawing on a blank screen
+        CLS
+        LD I, full
+        DRW V0, V1, 1
+        SE VF, 0
+        JP fail
+
+        LD VE, 2        ; the same sprite again erases it
+        DRW V0, V1, 1
+        SE VF, 1
+        JP fail
+
+        LD VE, 3        ; and leaves the screen blank
+        DRW V0, V1, 1
+        SE VF, 0
+        JP fail
+
+        LD VE, 4        ; a single shared pixel is enough
+        CLS
+        DRW V0, V1, 1
+        LD V0, 7
+        DRW V0, V1, 1
+        SE VF, 1
+        JP fail
+
+        LD VE, 5        ; touching isn't overlapping
+        CLS
+        LD I, half
+        LD V0, 0
+        DRW V0, V1, 1
+        LD V0, 4
+        DRW V0, V1, 1
+        SE VF, 0
+        JP fail
+
+        LD VE, 6        ; a lower row collides too
+        CLS
+        LD I, tall
+        LD V0, 20
+        LD V1, 10
+        DRW V0, V1, 3
+        LD I, full
+        LD V1, 12
+        DRW V0, V1, 1
+        SE VF, 1
+        JP fail
+
+        LD VE, 7        ; a later sprite without overlap clears it again
+        LD V1, 20
+        DRW V0, V1, 1
+        SE VF, 0
+        JP fail
+
+        LD VE, 8        ; drawing with VF as a coordinate
+        CLS
+        LD VF, 30
+        LD V0, 30
+        DRW VF, V0, 1
+        SE VF, 0
+        JP fail
+        LD VF, 30
+        DRW VF, V0, 1
+        SE VF, 1
+        JP fail
+
+pass:   LD V0, 0x4F     ; "OK"
+        LD V1, 0x4B
+        LD I, signature
+        LD [I], V1
+done:   JP done
+
+fail:   LD V0, 0x46     ; "F" and the check
+        LD V1, VE
+        LD I, signature
+        LD [I], V1
+        JP done
+
+full:   DB 0xFF
+half:   DB 0xF0
+tall:   DB 0x80, 0x80, 0x80
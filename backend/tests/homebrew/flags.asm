@@ -0,0 +1,127 @@
+; VF after arithmetic and shifts
+;
+; Like every ROM here, VE holds the number of the running check and the
+; result goes to signature: "OK" if everything passed, "F" and VE if not
+
+        LD VE, 1        ; 0xFF + 1 carries
+        LD V0, 0xFF
+        LD V1, 0x01
+        ADD V0, V1
+        SE V0, 0x00
+        JP fail
+        SE VF, 1
+        JP fail
+
+        LD VE, 2        ; 1 + 1 doesn't
+        LD V0, 0x01
+        ADD V0, V1
+        SE V0, 0x02
+        JP fail
+        SE VF, 0
+        JP fail
+
+        LD VE, 3        ; 5 - 3 doesn't borrow
+        LD V0, 5
+        LD V1, 3
+        SUB V0, V1
+        SE V0, 2
+        JP fail
+        SE VF, 1
+        JP fail
+
+        LD VE, 4        ; 3 - 5 does
+        LD V0, 3
+        LD V1, 5
+        SUB V0, V1
+        SE V0, 0xFE
+        JP fail
+        SE VF, 0
+        JP fail
+
+        LD VE, 5        ; equal values don't borrow
+        LD V0, 7
+        LD V1, 7
+        SUB V0, V1
+        SE V0, 0
+        JP fail
+        SE VF, 1
+        JP fail
+
+        LD VE, 6        ; SUBN is V1 - V0, 5 - 3 doesn't borrow
+        LD V0, 3
+        LD V1, 5
+        SUBN V0, V1
+        SE V0, 2
+        JP fail
+        SE VF, 1
+        JP fail
+
+        LD VE, 7        ; 3 - 5 does
+        LD V0, 5
+        LD V1, 3
+        SUBN V0, V1
+        SE V0, 0xFE
+        JP fail
+        SE VF, 0
+        JP fail
+
+        LD VE, 8        ; SHR drops the low bit into VF
+        LD V0, 0x03
+        SHR V0
+        SE V0, 0x01
+        JP fail
+        SE VF, 1
+        JP fail
+        SHR V0
+        SHR V0
+        SE V0, 0x00
+        JP fail
+        SE VF, 0
+        JP fail
+
+        LD VE, 9        ; SHL drops the high bit into VF
+        LD V0, 0x81
+        SHL V0
+        SE V0, 0x02
+        JP fail
+        SE VF, 1
+        JP fail
+        LD V0, 0x40
+        SHL V0
+        SE V0, 0x80
+        JP fail
+        SE VF, 0
+        JP fail
+
+        LD VE, 10       ; the flag wins when VF is also the result
+        LD VF, 0xFF
+        LD V0, 0x01
+        ADD VF, V0
+        SE VF, 1
+        JP fail
+        LD VF, 0x02
+        LD V0, 0x01
+        SUB VF, V0
+        SE VF, 1
+        JP fail
+
+        LD VE, 11       ; ADD with a byte never touches VF
+        LD VF, 5
+        LD V0, 0xFF
+        ADD V0, 2
+        SE V0, 1
+        JP fail
+        SE VF, 5
+        JP fail
+
+pass:   LD V0, 0x4F     ; "OK"
+        LD V1, 0x4B
+        LD I, signature
+        LD [I], V1
+done:   JP done
+
+fail:   LD V0, 0x46     ; "F" and the check
+        LD V1, VE
+        LD I, signature
+        LD [I], V1
+        JP done
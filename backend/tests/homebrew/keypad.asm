@@ -0,0 +1,56 @@
+; FX0A waits for a key, EX9E and EXA1 see it held. The runner presses
+; keys as these lines say, counting frames from 0
+;
+; press 7 from 5 to 10
+; press A from 20 to 25
+; press 3 from 30 to 35
+; press C from 30 to 35
+
+        LD VE, 1        ; nothing is held at the start
+        LD V0, 7
+        SKNP V0
+        JP fail
+
+        LD VE, 2        ; FX0A waits for 7
+        LD V1, K
+        SE V1, 7
+        JP fail
+
+        LD VE, 3        ; which is still held
+        SKP V0
+        JP fail
+
+        LD VE, 4        ; but no other key is
+        LD V0, 8
+        SKNP V0
+        JP fail
+
+        LD VE, 5        ; until 7 is let go
+        LD V0, 7
+held:   SKNP V0
+        JP held
+
+        LD VE, 6        ; the next wait gets A
+        LD V1, K
+        SE V1, 0xA
+        JP fail
+
+        LD VE, 7        ; and with two keys the lower one
+        LD V0, 0xA
+let_go: SKNP V0
+        JP let_go
+        LD V1, K
+        SE V1, 3
+        JP fail
+
+pass:   LD V0, 0x4F     ; "OK"
+        LD V1, 0x4B
+        LD I, signature
+        LD [I], V1
+done:   JP done
+
+fail:   LD V0, 0x46     ; "F" and the check
+        LD V1, VE
+        LD I, signature
+        LD [I], V1
+        JP done
@@ -0,0 +1,107 @@
+; Pins down which side of each quirk in the chip8-test-suite's list this
+; interpreter is on, so a change to any of them is deliberate
+
+        LD VE, 1        ; shifts work on VX and ignore VY
+        LD V0, 0x04
+        LD V1, 0xFF
+        SHR V0, V1
+        SE V0, 0x02
+        JP fail
+        SHL V0, V1
+        SE V0, 0x04
+        JP fail
+
+        LD VE, 2        ; OR, AND and XOR leave VF alone
+        LD VF, 5
+        OR V0, V1
+        SE VF, 5
+        JP fail
+        AND V0, V1
+        SE VF, 5
+        JP fail
+        XOR V0, V1
+        SE VF, 5
+        JP fail
+
+        LD VE, 3        ; FX55 leaves I where it was
+        LD I, buffer
+        LD V0, 0x11
+        LD V1, 0x22
+        LD [I], V1
+        LD V0, 0
+        LD V0, [I]
+        SE V0, 0x11
+        JP fail
+
+        LD VE, 4        ; and so does FX65
+        LD V1, [I]
+        LD V0, [I]
+        SE V0, 0x11
+        JP fail
+
+        LD VE, 5        ; BNNN adds V0, not the VX named by the address
+        LD V0, 4
+        LD V1, 0
+        LD V2, 0
+        LD V3, 0
+        JP V0, table
+
+table:  JP fail
+        JP fail
+
+        LD VE, 6        ; sprites wrap around the right edge
+        CLS
+        LD I, full
+        LD V0, 60
+        LD V1, 0
+        DRW V0, V1, 1
+        LD V0, 0
+        DRW V0, V1, 1
+        SE VF, 1
+        JP fail
+
+        LD VE, 7        ; and the bottom one
+        CLS
+        LD I, tall
+        LD V0, 10
+        LD V1, 31
+        DRW V0, V1, 2
+        LD I, full
+        LD V1, 0
+        DRW V0, V1, 1
+        SE VF, 1
+        JP fail
+
+        LD VE, 8        ; DXYN doesn't wait for the next frame, so ten
+        LD V0, 3        ; of them fit well inside three
+        LD DT, V0
+        LD I, full
+        DRW V0, V1, 1
+        DRW V0, V1, 1
+        DRW V0, V1, 1
+        DRW V0, V1, 1
+        DRW V0, V1, 1
+        DRW V0, V1, 1
+        DRW V0, V1, 1
+        DRW V0, V1, 1
+        DRW V0, V1, 1
+        DRW V0, V1, 1
+        LD V2, DT
+        SNE V2, 0
+        JP fail
+
+pass:   LD V0, 0x4F     ; "OK"
+        LD V1, 0x4B
+        LD I, signature
+        LD [I], V1
+done:   JP done
+
+fail:   LD V0, 0x46     ; "F" and the check
+        LD V1, VE
+        LD I, signature
+        LD [I], V1
+        JP done
+
+buffer: DB 0, 0, 0, 0
+full:   DB 0xFF
+tall:   DB 0x80, 0x80
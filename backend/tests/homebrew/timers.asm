@@ -0,0 +1,64 @@
+; The delay timer counts down once a frame and stops at zero. The sound
+; timer can't be read back, so only the delay timer is checked
+
+        LD VE, 1        ; zero reads back as zero
+        LD V0, 0
+        LD DT, V0
+        LD V1, DT
+        SE V1, 0
+        JP fail
+
+        LD VE, 2        ; a set timer hasn't run out straight away
+        LD V0, 5
+        LD DT, V0
+        LD V1, DT
+        SNE V1, 0
+        JP fail
+
+        LD VE, 3        ; and runs out eventually
+wait:   LD V1, DT
+        SE V1, 0
+        JP wait
+
+        LD VE, 4        ; then stays at zero rather than wrapping
+        LD V2, 40
+idle:   ADD V2, 0xFF
+        SE V2, 0
+        JP idle
+        LD V1, DT
+        SE V1, 0
+        JP fail
+
+        LD VE, 5        ; counting 3 frames takes more than 2 frames of
+        LD V0, 3        ; 10 instructions, so at least 4 trips of 4
+        LD DT, V0
+        LD V2, 0
+count:  ADD V2, 1
+        LD V1, DT
+        SE V1, 0
+        JP count
+        LD V3, 4
+        SUBN V3, V2     ; V2 - 4 doesn't borrow
+        SE VF, 1
+        JP fail
+
+        LD VE, 6        ; writing the timer restarts it
+        LD V0, 200
+        LD DT, V0
+        LD V0, 0
+        LD DT, V0
+        LD V1, DT
+        SE V1, 0
+        JP fail
+
+pass:   LD V0, 0x4F     ; "OK"
+        LD V1, 0x4B
+        LD I, signature
+        LD [I], V1
+done:   JP done
+
+fail:   LD V0, 0x46     ; "F" and the check
+        LD V1, VE
+        LD I, signature
+        LD [I], V1
+        JP done
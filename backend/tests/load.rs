@@ -0,0 +1,62 @@
+use backend::memory_constants::{RAM_SIZE, START_ADDRESS};
+use backend::{LoadError, Processor};
+
+#[test]
+fn rom_that_fills_memory_loads() {
+    let mut processor = Processor::with_seed(0);
+    let rom = vec![0x12; RAM_SIZE - START_ADDRESS as usize];
+    assert_eq!(processor.try_load(&rom), Ok(()));
+    assert_eq!(processor.read_ram(RAM_SIZE - 1), Some(0x12));
+}
+
+#[test]
+fn oversized_rom_is_refused() {
+    let mut processor = Processor::with_seed(0);
+    let rom = vec![0x12; RAM_SIZE - START_ADDRESS as usize + 1];
+    assert_eq!(
+        processor.try_load(&rom),
+        Err(LoadError {
+            address: START_ADDRESS as usize,
+            len: rom.len(),
+            capacity: rom.len() - 1,
+        })
+    );
+    // nothing was copied
+    assert_eq!(processor.read_ram(START_ADDRESS as usize), Some(0));
+}
+
+#[test]
+#[should_panic(expected = "only 3584 bytes of memory are left")]
+fn load_panics_on_oversized_rom() {
+    Processor::with_seed(0).load(&[0; RAM_SIZE]);
+}
+
+#[test]
+fn eti_660_programs_start_at_0x600() {
+    let mut processor = Processor::with_seed(0);
+    processor.set_start_address(0x600);
+    processor.load(&[0x60, 0x2A]); // LD V0, 0x2A
+    assert_eq!(processor.read_ram(0x200), Some(0));
+    processor.tick();
+    assert_eq!(processor.registers().v[0], 0x2A);
+    assert_eq!(processor.registers().pc, 0x602);
+
+    // and start there again after a reset
+    processor.reset();
+    assert_eq!(processor.registers().pc, 0x600);
+    assert_eq!(processor.start_address(), 0x600);
+}
+
+#[test]
+fn load_at_leaves_the_pc() {
+    let mut processor = Processor::with_seed(0);
+    processor.load(&[0xAE, 0x00, 0xF1, 0x65]); // LD I, 0xE00; LD V1, [I]
+    assert_eq!(processor.load_at(0xE00, &[0x11, 0x22]), Ok(()));
+    processor.tick();
+    processor.tick();
+    assert_eq!(processor.registers().v[..2], [0x11, 0x22]);
+
+    assert!(processor.load_at(0xFFF, &[1, 2]).is_err());
+    assert!(processor.load_at(0x2000, &[]).is_ok());
+    assert!(processor.load_at(0x2000, &[1]).is_err());
+}
@@ -0,0 +1,111 @@
+// Drives Machine with a headless frontend
+
+#![cfg(feature = "std")]
+
+use backend::input_constants::NUM_KEYS;
+use backend::machine::{AudioSink, Control, InputSource, Machine, VideoSink};
+use backend::{ErrorKind, Processor};
+
+#[derive(Default)]
+struct Frames {
+    count: usize,
+    lit: usize, // lit pixels in the last frame
+}
+
+impl VideoSink for Frames {
+    fn present(&mut self, display: &[bool]) {
+        self.count += 1;
+        self.lit = display.iter().filter(|pixel| **pixel).count();
+    }
+}
+
+#[derive(Default)]
+struct Beeps(Vec<bool>);
+
+impl AudioSink for Beeps {
+    fn set_beeping(&mut self, beeping: bool) {
+        self.0.push(beeping);
+    }
+}
+
+// Holds `key` from frame `press_at` on and quits after `frames` frames
+struct Script {
+    frame: usize,
+    frames: usize,
+    press_at: usize,
+    key: usize,
+}
+
+impl InputSource for Script {
+    fn poll(&mut self, keys: &mut [bool; NUM_KEYS]) -> Control {
+        if self.frame == self.frames {
+            return Control::Quit;
+        }
+        keys[self.key] = self.frame >= self.press_at;
+        self.frame += 1;
+        Control::Continue
+    }
+}
+
+fn machine(rom: &[u8], frames: usize, press_at: usize) -> Machine<Frames, Beeps, Script> {
+    let mut processor = Processor::with_seed(0);
+    processor.load(rom);
+    let script = Script {
+        frame: 0,
+        frames,
+        press_at,
+        key: 0x5,
+    };
+    Machine::new(processor, Frames::default(), Beeps::default(), script)
+}
+
+#[test]
+fn waits_for_key_then_draws() {
+    // wait for a key, draw its font sprite, loop forever
+    let rom = [0xF0, 0x0A, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x06];
+    let mut machine = machine(&rom, 4, 2);
+
+    machine.run_frame().unwrap();
+    machine.run_frame().unwrap();
+    assert_eq!(machine.video().lit, 0);
+
+    machine.run_frame().unwrap();
+    assert_eq!(machine.processor().registers().v[0], 0x5);
+    assert_eq!(machine.video().lit, 14);
+
+    machine.run_frame().unwrap();
+    assert_eq!(machine.run_frame(), Ok(Control::Quit));
+    assert_eq!(machine.video().count, 4);
+}
+
+#[test]
+fn reports_beeper_changes() {
+    // sound timer = 2, loop forever
+    let rom = [0x60, 0x02, 0xF0, 0x18, 0x12, 0x04];
+    let mut machine = machine(&rom, 5, 0);
+    machine.run().unwrap();
+
+    let (_, video, audio, _) = machine.into_parts();
+    assert_eq!(video.count, 5);
+    assert_eq!(audio.0, vec![true, false]);
+}
+
+#[test]
+fn frozen_timers_while_not_running() {
+    // delay timer = 0x10, loop forever
+    let rom = [0x60, 0x10, 0xF0, 0x15, 0x12, 0x04];
+    let mut machine = machine(&rom, 10, 0);
+    machine.run_frame().unwrap();
+    assert_eq!(machine.processor().registers().dt, 0x0F);
+
+    machine.run_frame_with(|_, _| Ok(false)).unwrap();
+    assert_eq!(machine.processor().registers().dt, 0x0F);
+}
+
+#[test]
+fn stops_on_execution_error() {
+    let mut machine = machine(&[0x00, 0xEE], 10, 0);
+    let error = machine.run().unwrap_err();
+    assert_eq!(error.kind, ErrorKind::StackUnderflow);
+    assert_eq!(machine.video().count, 0);
+}
@@ -0,0 +1,76 @@
+#![cfg(feature = "std")]
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+use backend::manifest::{Manifest, Segment};
+use backend::Processor;
+
+// Fresh directory per test, since they run in parallel
+fn scratch(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("chip8-manifest-{}-{}", process::id(), name));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn segments_load_at_their_addresses() {
+    let dir = scratch("segments");
+    fs::write(dir.join("game.ch8"), [0xAA, 0x00, 0xF0, 0x65]).unwrap(); // LD I, 0xA00; LD V0, [I]
+    fs::write(dir.join("levels.bin"), [0x42]).unwrap();
+    fs::write(
+        dir.join("game.manifest"),
+        "# ETI 660 game\nstart 0x600\nload 0x600 game.ch8\nload 0xA00 levels.bin\n",
+    )
+    .unwrap();
+
+    let manifest = Manifest::load(dir.join("game.manifest")).unwrap();
+    assert_eq!(manifest.start, 0x600);
+    assert_eq!(
+        manifest.segments[1],
+        Segment {
+            address: 0xA00,
+            data: vec![0x42]
+        }
+    );
+
+    let mut processor = Processor::with_seed(0);
+    manifest.apply(&mut processor).unwrap();
+    processor.tick();
+    processor.tick();
+    assert_eq!(processor.registers().v[0], 0x42);
+    assert_eq!(processor.start_address(), 0x600);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn start_defaults_to_the_first_segment() {
+    let dir = scratch("start");
+    fs::write(dir.join("rom"), [0x00, 0xE0]).unwrap();
+    let manifest = Manifest::parse("load 0x300 rom", &dir).unwrap();
+    assert_eq!(manifest.start, 0x300);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn bad_manifests_are_refused() {
+    let dir = scratch("bad");
+    fs::write(dir.join("rom"), [0; 0x100]).unwrap();
+    for text in [
+        "",
+        "start 0x200",
+        "load rom",
+        "load 0x200",
+        "boot 0x200 rom",
+        "load 0x200 missing",
+    ] {
+        assert!(Manifest::parse(text, &dir).is_err(), "{:?}", text);
+    }
+
+    // parses, but doesn't fit
+    let manifest = Manifest::parse("load 0xF80 rom", &dir).unwrap();
+    assert!(manifest.apply(&mut Processor::with_seed(0)).is_err());
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,554 @@
+// One or more tests per opcode, each running a few hand-assembled
+// instructions and checking registers, memory and the screen afterwards.
+// There are no quirk modes yet, so these pin down the one behaviour each
+// opcode has
+
+use backend::display_constants::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use backend::{ErrorKind, Processor, Registers};
+
+// Loads the ROM with registers preset by `setup` and runs `ticks`
+// instructions
+fn run_with(rom: &[u8], ticks: usize, setup: impl FnOnce(&mut Registers)) -> Processor {
+    let mut processor = Processor::with_seed(0);
+    processor.load(rom);
+    let mut registers = processor.registers();
+    setup(&mut registers);
+    processor.set_registers(registers);
+    for _ in 0..ticks {
+        processor.try_tick().unwrap();
+    }
+    processor
+}
+
+fn run(rom: &[u8], ticks: usize) -> Processor {
+    run_with(rom, ticks, |_| {})
+}
+
+fn fault(rom: &[u8], setup: impl FnOnce(&mut Registers)) -> ErrorKind {
+    let mut processor = run_with(rom, 0, setup);
+    processor.try_tick().unwrap_err().kind
+}
+
+fn pixel(processor: &Processor, x: usize, y: usize) -> bool {
+    processor.get_display()[x + SCREEN_WIDTH * y]
+}
+
+fn lit(processor: &Processor) -> usize {
+    processor
+        .get_display()
+        .iter()
+        .filter(|pixel| **pixel)
+        .count()
+}
+
+fn v(processor: &Processor, x: usize) -> u8 {
+    processor.registers().v[x]
+}
+
+fn pc(processor: &Processor) -> u16 {
+    processor.registers().pc
+}
+
+#[test]
+fn nop_only_advances() {
+    let processor = run(&[0x00, 0x00], 1);
+    assert_eq!(pc(&processor), 0x202);
+    assert_eq!(processor.registers().v, [0; 16]);
+}
+
+#[test]
+fn cls_clears_screen() {
+    // LD F, V0 then DRW V0, V0, 5 draws a 0 at the corner
+    let processor = run(&[0xF0, 0x29, 0xD0, 0x05, 0x00, 0xE0], 2);
+    assert!(lit(&processor) > 0);
+    let processor = run(&[0xF0, 0x29, 0xD0, 0x05, 0x00, 0xE0], 3);
+    assert_eq!(lit(&processor), 0);
+}
+
+#[test]
+fn call_and_ret() {
+    // CALL 0x206, then RET comes back to 0x202
+    let rom = [0x22, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0xEE];
+    let processor = run(&rom, 1);
+    assert_eq!(pc(&processor), 0x206);
+    assert_eq!(processor.stack(), &[0x202]);
+
+    let processor = run(&rom, 2);
+    assert_eq!(pc(&processor), 0x202);
+    assert!(processor.stack().is_empty());
+}
+
+#[test]
+fn ret_on_empty_stack_underflows() {
+    assert_eq!(fault(&[0x00, 0xEE], |_| {}), ErrorKind::StackUnderflow);
+}
+
+#[test]
+fn call_on_full_stack_overflows() {
+    // calls itself until the stack runs out
+    let mut processor = run(&[0x22, 0x00], 0);
+    let error = loop {
+        if let Err(error) = processor.try_tick() {
+            break error;
+        }
+    };
+    assert_eq!(error.kind, ErrorKind::StackOverflow);
+    assert_eq!(processor.stack().len(), 16);
+}
+
+#[test]
+fn jp_jumps() {
+    assert_eq!(pc(&run(&[0x1A, 0xBC], 1)), 0xABC);
+}
+
+#[test]
+fn se_imm_skips_when_equal() {
+    assert_eq!(pc(&run_with(&[0x33, 0x42], 1, |r| r.v[3] = 0x42)), 0x204);
+    assert_eq!(pc(&run_with(&[0x33, 0x42], 1, |r| r.v[3] = 0x41)), 0x202);
+}
+
+#[test]
+fn sne_imm_skips_when_different() {
+    assert_eq!(pc(&run_with(&[0x43, 0x42], 1, |r| r.v[3] = 0x42)), 0x202);
+    assert_eq!(pc(&run_with(&[0x43, 0x42], 1, |r| r.v[3] = 0x41)), 0x204);
+}
+
+#[test]
+fn se_reg_skips_when_equal() {
+    let equal = |r: &mut Registers| {
+        r.v[1] = 7;
+        r.v[2] = 7;
+    };
+    assert_eq!(pc(&run_with(&[0x51, 0x20], 1, equal)), 0x204);
+    assert_eq!(pc(&run_with(&[0x51, 0x20], 1, |r| r.v[1] = 7)), 0x202);
+}
+
+#[test]
+fn sne_reg_skips_when_different() {
+    let equal = |r: &mut Registers| {
+        r.v[1] = 7;
+        r.v[2] = 7;
+    };
+    assert_eq!(pc(&run_with(&[0x91, 0x20], 1, equal)), 0x202);
+    assert_eq!(pc(&run_with(&[0x91, 0x20], 1, |r| r.v[1] = 7)), 0x204);
+}
+
+#[test]
+fn ld_imm_loads() {
+    assert_eq!(v(&run(&[0x6C, 0x9A], 1), 0xC), 0x9A);
+}
+
+#[test]
+fn add_imm_wraps_without_touching_vf() {
+    let processor = run_with(&[0x75, 0x10], 1, |r| {
+        r.v[5] = 0xF8;
+        r.v[0xF] = 0xAA;
+    });
+    assert_eq!(v(&processor, 5), 0x08);
+    assert_eq!(v(&processor, 0xF), 0xAA);
+}
+
+#[test]
+fn ld_reg_copies() {
+    assert_eq!(v(&run_with(&[0x81, 0x20], 1, |r| r.v[2] = 0x33), 1), 0x33);
+}
+
+#[test]
+fn bitwise_ops() {
+    let setup = |r: &mut Registers| {
+        r.v[1] = 0b1100;
+        r.v[2] = 0b1010;
+    };
+    assert_eq!(v(&run_with(&[0x81, 0x21], 1, setup), 1), 0b1110);
+    assert_eq!(v(&run_with(&[0x81, 0x22], 1, setup), 1), 0b1000);
+    assert_eq!(v(&run_with(&[0x81, 0x23], 1, setup), 1), 0b0110);
+}
+
+#[test]
+fn add_sets_carry() {
+    let processor = run_with(&[0x81, 0x24], 1, |r| {
+        r.v[1] = 0xF0;
+        r.v[2] = 0x20;
+    });
+    assert_eq!(v(&processor, 1), 0x10);
+    assert_eq!(v(&processor, 0xF), 1);
+
+    let processor = run_with(&[0x81, 0x24], 1, |r| {
+        r.v[1] = 0x10;
+        r.v[2] = 0x20;
+        r.v[0xF] = 1;
+    });
+    assert_eq!(v(&processor, 1), 0x30);
+    assert_eq!(v(&processor, 0xF), 0);
+}
+
+#[test]
+fn add_into_vf_keeps_carry() {
+    // the flag is written after the result, so VF ends up as the carry
+    let processor = run_with(&[0x8F, 0x24], 1, |r| {
+        r.v[0xF] = 0xFF;
+        r.v[2] = 0x02;
+    });
+    assert_eq!(v(&processor, 0xF), 1);
+
+    let processor = run_with(&[0x8F, 0x24], 1, |r| {
+        r.v[0xF] = 0x01;
+        r.v[2] = 0x02;
+    });
+    assert_eq!(v(&processor, 0xF), 0);
+}
+
+#[test]
+fn sub_clears_vf_on_borrow() {
+    let processor = run_with(&[0x81, 0x25], 1, |r| {
+        r.v[1] = 0x10;
+        r.v[2] = 0x20;
+    });
+    assert_eq!(v(&processor, 1), 0xF0);
+    assert_eq!(v(&processor, 0xF), 0);
+
+    let processor = run_with(&[0x81, 0x25], 1, |r| {
+        r.v[1] = 0x20;
+        r.v[2] = 0x10;
+    });
+    assert_eq!(v(&processor, 1), 0x10);
+    assert_eq!(v(&processor, 0xF), 1);
+
+    // no borrow when equal
+    let processor = run_with(&[0x81, 0x25], 1, |r| {
+        r.v[1] = 0x20;
+        r.v[2] = 0x20;
+    });
+    assert_eq!(v(&processor, 1), 0);
+    assert_eq!(v(&processor, 0xF), 1);
+}
+
+#[test]
+fn subn_clears_vf_on_borrow() {
+    let processor = run_with(&[0x81, 0x27], 1, |r| {
+        r.v[1] = 0x20;
+        r.v[2] = 0x10;
+    });
+    assert_eq!(v(&processor, 1), 0xF0);
+    assert_eq!(v(&processor, 0xF), 0);
+
+    let processor = run_with(&[0x81, 0x27], 1, |r| {
+        r.v[1] = 0x10;
+        r.v[2] = 0x20;
+    });
+    assert_eq!(v(&processor, 1), 0x10);
+    assert_eq!(v(&processor, 0xF), 1);
+
+    let processor = run_with(&[0x81, 0x27], 1, |r| {
+        r.v[1] = 0x20;
+        r.v[2] = 0x20;
+    });
+    assert_eq!(v(&processor, 1), 0);
+    assert_eq!(v(&processor, 0xF), 1);
+}
+
+// Regression test: 8XY7 used to set VF on a borrow, the opposite of 8XY5
+#[test]
+fn subn_flag_matches_sub_with_operands_swapped() {
+    for a in (0..=255).step_by(5) {
+        for b in (0..=255).step_by(3) {
+            let subn = run_with(&[0x81, 0x27], 1, |r| {
+                r.v[1] = a;
+                r.v[2] = b;
+            });
+            let sub = run_with(&[0x82, 0x15], 1, |r| {
+                r.v[1] = a;
+                r.v[2] = b;
+            });
+            assert_eq!(v(&subn, 1), v(&sub, 2), "{} - {}", b, a);
+            assert_eq!(v(&subn, 0xF), v(&sub, 0xF), "{} - {}", b, a);
+            assert_eq!(v(&subn, 0xF), (b >= a) as u8, "{} - {}", b, a);
+        }
+    }
+}
+
+#[test]
+fn shr_moves_dropped_bit_to_vf() {
+    let processor = run_with(&[0x81, 0x26], 1, |r| r.v[1] = 0b101);
+    assert_eq!(v(&processor, 1), 0b10);
+    assert_eq!(v(&processor, 0xF), 1);
+
+    let processor = run_with(&[0x81, 0x26], 1, |r| r.v[1] = 0b100);
+    assert_eq!(v(&processor, 1), 0b10);
+    assert_eq!(v(&processor, 0xF), 0);
+}
+
+#[test]
+fn shl_moves_dropped_bit_to_vf() {
+    let processor = run_with(&[0x81, 0x2E], 1, |r| r.v[1] = 0b1000_0001);
+    assert_eq!(v(&processor, 1), 0b10);
+    assert_eq!(v(&processor, 0xF), 1);
+
+    let processor = run_with(&[0x81, 0x2E], 1, |r| r.v[1] = 0b0100_0001);
+    assert_eq!(v(&processor, 1), 0b1000_0010);
+    assert_eq!(v(&processor, 0xF), 0);
+}
+
+#[test]
+fn ld_i_loads() {
+    assert_eq!(run(&[0xAF, 0xED], 1).registers().i, 0xFED);
+}
+
+#[test]
+fn jp_v0_adds_offset() {
+    assert_eq!(pc(&run_with(&[0xB3, 0x00], 1, |r| r.v[0] = 0x12)), 0x312);
+}
+
+#[test]
+fn rnd_is_masked_and_seeded() {
+    assert_eq!(v(&run_with(&[0xC1, 0x00], 1, |r| r.v[1] = 0xFF), 1), 0);
+
+    let values: Vec<u8> = (0..32).map(|_| v(&run(&[0xC1, 0x0F], 1), 1)).collect();
+    assert!(values.iter().all(|value| *value <= 0x0F));
+    // same seed, same number
+    assert!(values.windows(2).all(|pair| pair[0] == pair[1]));
+}
+
+#[test]
+fn drw_draws_font_glyph() {
+    // I = glyph for 0, DRW V0, V1, 5 at (3, 2)
+    let processor = run_with(&[0xF2, 0x29, 0xD0, 0x15], 2, |r| {
+        r.v[0] = 3;
+        r.v[1] = 2;
+    });
+    // 0xF0, 0x90, 0x90, 0x90, 0xF0
+    assert_eq!(lit(&processor), 14);
+    assert!(pixel(&processor, 3, 2));
+    assert!(pixel(&processor, 6, 2));
+    assert!(!pixel(&processor, 7, 2));
+    assert!(pixel(&processor, 3, 3));
+    assert!(!pixel(&processor, 4, 3));
+    assert!(pixel(&processor, 6, 6));
+    assert_eq!(v(&processor, 0xF), 0);
+}
+
+#[test]
+fn drw_twice_erases_and_sets_vf() {
+    let rom = [0xF2, 0x29, 0xD0, 0x15, 0xD0, 0x15];
+    let processor = run(&rom, 3);
+    assert_eq!(lit(&processor), 0);
+    assert_eq!(v(&processor, 0xF), 1);
+}
+
+#[test]
+fn drw_clears_vf_without_collision() {
+    let processor = run_with(&[0xF2, 0x29, 0xD0, 0x15], 2, |r| r.v[0xF] = 1);
+    assert_eq!(v(&processor, 0xF), 0);
+}
+
+#[test]
+fn drw_wraps_at_right_edge() {
+    // top row of the 0 glyph, 0xF0, at x = 62 spills into x = 0 and 1
+    let processor = run_with(&[0xF2, 0x29, 0xD0, 0x11], 2, |r| r.v[0] = 62);
+    assert!(pixel(&processor, 62, 0));
+    assert!(pixel(&processor, 63, 0));
+    assert!(pixel(&processor, 0, 0));
+    assert!(pixel(&processor, 1, 0));
+    assert_eq!(lit(&processor), 4);
+}
+
+#[test]
+fn drw_wraps_at_bottom_edge() {
+    let processor = run_with(&[0xF2, 0x29, 0xD0, 0x15], 2, |r| r.v[1] = 30);
+    assert!(pixel(&processor, 0, 30));
+    assert!(pixel(&processor, 0, 31));
+    assert!(pixel(&processor, 0, 0));
+    assert!(pixel(&processor, 0, 2));
+    assert_eq!(lit(&processor), 14);
+}
+
+#[test]
+fn drw_wraps_starting_coordinates() {
+    let processor = run_with(&[0xF2, 0x29, 0xD0, 0x11], 2, |r| {
+        r.v[0] = SCREEN_WIDTH as u8 + 4;
+        r.v[1] = SCREEN_HEIGHT as u8 + 1;
+    });
+    assert!(pixel(&processor, 4, 1));
+    assert_eq!(lit(&processor), 4);
+}
+
+#[test]
+fn drw_zero_rows_draws_nothing() {
+    let processor = run(&[0xF2, 0x29, 0xD0, 0x10], 2);
+    assert_eq!(lit(&processor), 0);
+}
+
+#[test]
+fn drw_past_end_of_memory_faults() {
+    let error = fault(&[0xD0, 0x15], |r| r.i = 0xFFE);
+    assert_eq!(error, ErrorKind::MemoryOutOfBounds(0x1002));
+}
+
+#[test]
+fn skp_and_sknp_follow_key() {
+    let mut processor = run_with(&[0xE4, 0x9E], 0, |r| r.v[4] = 0xA);
+    processor.keypress(0xA, true);
+    processor.try_tick().unwrap();
+    assert_eq!(pc(&processor), 0x204);
+    assert_eq!(pc(&run_with(&[0xE4, 0x9E], 1, |r| r.v[4] = 0xA)), 0x202);
+
+    let mut processor = run_with(&[0xE4, 0xA1], 0, |r| r.v[4] = 0xA);
+    processor.keypress(0xA, true);
+    processor.try_tick().unwrap();
+    assert_eq!(pc(&processor), 0x202);
+    assert_eq!(pc(&run_with(&[0xE4, 0xA1], 1, |r| r.v[4] = 0xA)), 0x204);
+}
+
+#[test]
+fn skp_with_invalid_key_faults() {
+    assert_eq!(
+        fault(&[0xE4, 0x9E], |r| r.v[4] = 0x10),
+        ErrorKind::InvalidKey(0x10)
+    );
+    assert_eq!(
+        fault(&[0xE4, 0xA1], |r| r.v[4] = 0xFF),
+        ErrorKind::InvalidKey(0xFF)
+    );
+}
+
+#[test]
+fn ld_key_blocks_until_pressed() {
+    let mut processor = run(&[0xF3, 0x0A], 5);
+    assert_eq!(pc(&processor), 0x200);
+
+    processor.keypress(0x7, true);
+    processor.try_tick().unwrap();
+    assert_eq!(pc(&processor), 0x202);
+    assert_eq!(v(&processor, 3), 0x7);
+}
+
+#[test]
+fn timers_load_and_read() {
+    // DT = V1, ST = V1, V2 = DT
+    let processor = run_with(&[0xF1, 0x15, 0xF1, 0x18, 0xF2, 0x07], 3, |r| r.v[1] = 9);
+    let registers = processor.registers();
+    assert_eq!(registers.dt, 9);
+    assert_eq!(registers.st, 9);
+    assert_eq!(registers.v[2], 9);
+}
+
+#[test]
+fn timers_count_down() {
+    let mut processor = run_with(&[], 0, |r| {
+        r.dt = 2;
+        r.st = 2;
+    });
+    processor.tick_timers();
+    assert!(!processor.get_sound());
+    processor.tick_timers();
+    assert!(processor.get_sound());
+    processor.tick_timers();
+    assert!(!processor.get_sound());
+    assert_eq!(processor.registers().dt, 0);
+    assert_eq!(processor.registers().st, 0);
+}
+
+#[test]
+fn add_i_adds_vx() {
+    let processor = run_with(&[0xF1, 0x1E], 1, |r| {
+        r.i = 0x300;
+        r.v[1] = 0x25;
+    });
+    assert_eq!(processor.registers().i, 0x325);
+}
+
+#[test]
+fn ld_font_points_at_glyph() {
+    let processor = run_with(&[0xF1, 0x29], 1, |r| r.v[1] = 0xA);
+    let i = processor.registers().i as usize;
+    assert_eq!(i, 0xA * 5);
+    // top row of A
+    assert_eq!(processor.read_ram(i), Some(0xF0));
+}
+
+#[test]
+fn bcd_stores_digits() {
+    for (value, digits) in [
+        (0, [0, 0, 0]),
+        (9, [0, 0, 9]),
+        (255, [2, 5, 5]),
+        (107, [1, 0, 7]),
+    ] {
+        let processor = run_with(&[0xF1, 0x33], 1, |r| {
+            r.i = 0x300;
+            r.v[1] = value;
+        });
+        let stored: Vec<u8> = (0x300..0x303)
+            .map(|address| processor.read_ram(address).unwrap())
+            .collect();
+        assert_eq!(stored, digits, "BCD of {}", value);
+    }
+}
+
+#[test]
+fn bcd_past_end_of_memory_faults() {
+    let error = fault(&[0xF1, 0x33], |r| r.i = 0xFFE);
+    assert_eq!(error, ErrorKind::MemoryOutOfBounds(0x1000));
+}
+
+#[test]
+fn store_and_load_are_inclusive() {
+    let processor = run_with(&[0xF2, 0x55], 1, |r| {
+        r.i = 0x300;
+        r.v = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+    });
+    assert_eq!(processor.read_ram(0x300), Some(1));
+    assert_eq!(processor.read_ram(0x302), Some(3));
+    assert_eq!(processor.read_ram(0x303), Some(0));
+    // I is left alone
+    assert_eq!(processor.registers().i, 0x300);
+
+    // V0-V2 = the ROM's own first three bytes
+    let processor = run_with(&[0xF2, 0x65, 0xAB], 1, |r| {
+        r.i = 0x200;
+        r.v[3] = 0x77;
+    });
+    assert_eq!(&processor.registers().v[..4], &[0xF2, 0x65, 0xAB, 0x77]);
+    assert_eq!(processor.registers().i, 0x200);
+}
+
+#[test]
+fn store_and_load_past_end_of_memory_fault() {
+    assert_eq!(
+        fault(&[0xF3, 0x55], |r| r.i = 0xFFE),
+        ErrorKind::MemoryOutOfBounds(0x1001)
+    );
+    assert_eq!(
+        fault(&[0xF3, 0x65], |r| r.i = 0xFFE),
+        ErrorKind::MemoryOutOfBounds(0x1001)
+    );
+}
+
+#[test]
+fn unknown_opcodes_fault() {
+    for opcode in [
+        [0x01, 0x23],
+        [0x51, 0x21],
+        [0x81, 0x28],
+        [0x91, 0x2F],
+        [0xE1, 0x00],
+        [0xF1, 0xFF],
+    ] {
+        assert_eq!(
+            fault(&opcode, |_| {}),
+            ErrorKind::InvalidOpcode,
+            "{:02X?}",
+            opcode
+        );
+    }
+}
+
+#[test]
+fn pc_past_end_of_memory_faults() {
+    // JP 0xFFF leaves half an instruction before the end
+    let mut processor = run(&[0x1F, 0xFF], 1);
+    assert_eq!(
+        processor.try_tick().unwrap_err().kind,
+        ErrorKind::PcOutOfBounds
+    );
+}
@@ -0,0 +1,162 @@
+#![cfg(feature = "std")]
+
+use backend::{Bus, Processor, Ram};
+
+// Calls the routine at 0x208 twice and spins, leaving 0x206 unused
+const ROM: [u8; 12] = [
+    0x22, 0x08, // CALL 0x208
+    0x22, 0x08, // CALL 0x208
+    0x12, 0x04, // JP 0x204
+    0x00, 0x00, // never runs
+    0x60, 0x01, // LD V0, 0x01
+    0x00, 0xEE, // RET
+];
+const CODE: std::ops::Range<usize> = 0x200..0x20C;
+
+// 1 + 1 instructions in the main loop, 2 + 2 in the routine, then 4 jumps
+fn profiled() -> Processor {
+    let mut processor = Processor::with_seed(0);
+    processor.load(&ROM);
+    processor.start_profiling();
+    processor.run(10).unwrap();
+    processor
+}
+
+fn unused<B: Bus>(processor: &Processor<B>, range: std::ops::Range<usize>) -> Vec<(usize, usize)> {
+    let profile = processor.profile().unwrap();
+    let ranges = profile.never_executed(range);
+    ranges.iter().map(|range| (range.start, range.end)).collect()
+}
+
+#[test]
+fn counts_per_address_and_opcode() {
+    let processor = profiled();
+    let profile = processor.profile().unwrap();
+    let counts: Vec<_> = CODE.step_by(2).map(|a| profile.count(a as u16)).collect();
+    assert_eq!(counts, [1, 1, 4, 0, 2, 2]);
+    assert_eq!(profile.total(), 10);
+
+    let classes: Vec<_> = profile.class_counts().iter().map(|(c, n)| (*c, *n)).collect();
+    assert_eq!(classes, [("00EE", 2), ("1NNN", 4), ("2NNN", 2), ("6XNN", 2)]);
+}
+
+#[test]
+fn call_edges_and_hot_routines() {
+    let processor = profiled();
+    let profile = processor.profile().unwrap();
+    let edges: Vec<_> = profile.call_edges().iter().map(|(e, n)| (*e, *n)).collect();
+    assert_eq!(edges, [((0x200, 0x208), 2)]);
+    // the RET counts towards the routine it returns from
+    assert_eq!(profile.hot_routines(), [(0x200, 6), (0x208, 4)]);
+}
+
+#[test]
+fn loading_a_state_inside_a_call() {
+    let mut processor = Processor::with_seed(0);
+    processor.load(&ROM);
+    let state = processor.save_state();
+    processor.start_profiling();
+    processor.run(1).unwrap(); // into the routine at 0x208
+    processor.load_state(&state).unwrap();
+    processor.run(10).unwrap();
+
+    let profile = processor.profile().unwrap();
+    let edges: Vec<_> = profile.call_edges().iter().map(|(e, n)| (*e, *n)).collect();
+    assert_eq!(edges, [((0x200, 0x208), 3)]);
+    assert_eq!(profile.hot_routines(), [(0x200, 7), (0x208, 4)]);
+}
+
+#[test]
+fn never_executed_ranges() {
+    let processor = profiled();
+    assert_eq!(unused(&processor, CODE), [(0x206, 0x208)]);
+    assert_eq!(unused(&processor, 0x200..0x210), [(0x206, 0x208), (0x20C, 0x210)]);
+    // ranges past the end of memory are cut off
+    assert_eq!(unused(&processor, 0xFFE..0x2000), [(0xFFE, 0x1000)]);
+}
+
+#[test]
+fn json_report() {
+    let processor = profiled();
+    let mut out = Vec::new();
+    processor.profile().unwrap().write_json(&mut out, CODE).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        concat!(
+            r#"{"total":10,"#,
+            r#""addresses":{"0x0200":1,"0x0202":1,"0x0204":4,"0x0208":2,"0x020A":2},"#,
+            r#""opcodes":{"00EE":2,"1NNN":4,"2NNN":2,"6XNN":2},"#,
+            r#""routines":[{"address":"0x0200","count":6},{"address":"0x0208","count":4}],"#,
+            r#""calls":[{"from":"0x0200","to":"0x0208","count":2}],"#,
+            r#""never_executed":[{"start":"0x0206","end":"0x0207"}]}"#,
+            "\n"
+        )
+    );
+}
+
+#[test]
+fn text_report() {
+    let processor = profiled();
+    let mut out = Vec::new();
+    processor.profile().unwrap().write_text(&mut out, CODE).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    let header = "instructions executed: 10\n\nhot routines:\n  0200            6  60.00%\n";
+    assert!(text.starts_with(header));
+    assert!(text.contains("\ncalls:\n  0200 -> 0208            2\n"));
+    assert!(text.ends_with("\nnever executed:\n  0206-0207\n"));
+}
+
+#[test]
+fn annotated_listing() {
+    let processor = profiled();
+    let mut out = Vec::new();
+    let profile = processor.profile().unwrap();
+    profile.write_listing(&mut out, processor.ram(), CODE).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "sub_0200:\n\
+         \x20          1  0200  2208  CALL 0x208\n\
+         \x20          1  0202  2208  CALL 0x208\n\
+         \x20          4  0204  1204  JP 0x204\n\
+         \x20          .  0206  0000  NOP\n\
+         sub_0208:\n\
+         \x20          2  0208  6001  LD V0, 0x01\n\
+         \x20          2  020A  00EE  RET\n"
+    );
+}
+
+#[test]
+fn chip8x_opcodes() {
+    let mut processor = Processor::with_seed(0);
+    processor.set_chip8x(true);
+    // BGCOL, ADDN V0, V1, COL V0, V2, 0, then spin
+    processor.load(&[0x02, 0xA0, 0x50, 0x11, 0xB0, 0x20, 0x13, 0x06]);
+    processor.start_profiling();
+    processor.run(4).unwrap();
+    let profile = processor.profile().unwrap();
+    let classes: Vec<_> = profile.class_counts().keys().copied().collect();
+    assert_eq!(classes, ["02A0", "1NNN", "5XY1", "BXYN"]);
+
+    let mut out = Vec::new();
+    profile.write_listing(&mut out, processor.ram(), 0x300..0x306).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "sub_0300:\n\
+         \x20          1  0300  02A0  BGCOL\n\
+         \x20          1  0302  5011  ADDN V0, V1\n\
+         \x20          1  0304  B020  COL V0, V2, 0\n"
+    );
+}
+
+#[test]
+fn addresses_past_4k_on_larger_buses() {
+    // jumps can't reach up here, so straight-line code
+    let mut processor = Processor::with_bus(Ram::<0x10000>::new(), 0);
+    processor.set_start_address(0x8000);
+    processor.load(&[0x70, 0x01, 0x70, 0x01, 0x70, 0x01]);
+    processor.start_profiling();
+    processor.run(2).unwrap();
+    let profile = processor.profile().unwrap();
+    assert_eq!((profile.count(0x8000), profile.count(0x8002)), (1, 1));
+    assert_eq!(unused(&processor, 0x8000..0x8006), [(0x8004, 0x8006)]);
+}
@@ -0,0 +1,148 @@
+// Random ROMs and key presses must never crash the host. Runs the fuzz
+// harness on proptest's inputs and on the regression corpus, and checks
+// the interpreter doesn't allocate while running
+
+#![cfg(feature = "std")]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::fs;
+
+use backend::memory_constants::{RAM_SIZE, START_ADDRESS};
+use backend::Processor;
+use proptest::prelude::*;
+
+#[path = "../fuzz/harness.rs"]
+mod harness;
+
+const MAX_ROM: usize = RAM_SIZE - START_ADDRESS as usize;
+const MAX_WORDS: usize = 256;
+
+// Counts allocations per thread, so tests running in parallel don't see
+// each other's
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+// Inverse of the parsing in harness::run
+fn encode(rom: &[u8], keys: &[u16]) -> Vec<u8> {
+    let mut data = vec![keys.len() as u8];
+    for mask in keys {
+        data.extend_from_slice(&mask.to_be_bytes());
+    }
+    data.extend_from_slice(rom);
+    data
+}
+
+// Uniform bytes hit 0NNN and other invalid opcodes too often to get far,
+// so most instructions are picked from the valid ones
+fn instruction() -> impl Strategy<Value = [u8; 2]> {
+    let x = 0u16..16;
+    let y = 0u16..16;
+    prop_oneof![
+        1 => any::<u16>(),
+        1 => Just(0x00E0u16),
+        // jumps and calls stay inside the program
+        2 => (prop::sample::select(vec![0x1000u16, 0x2000, 0xB000]), 0u16..MAX_WORDS as u16)
+            .prop_map(|(family, word)| family | (START_ADDRESS + word * 2)),
+        4 => (prop::sample::select(vec![3u16, 4, 6, 7, 0xA, 0xC, 0xD]), 0u16..0x1000)
+            .prop_map(|(family, operands)| family << 12 | operands),
+        8 => (x.clone(), y.clone(), prop::sample::select(vec![0u16, 1, 2, 3, 4, 5, 6, 7, 0xE]))
+            .prop_map(|(x, y, n)| 0x8000 | x << 8 | y << 4 | n),
+        1 => (prop::sample::select(vec![0x5000u16, 0x9000]), x.clone(), y)
+            .prop_map(|(family, x, y)| family | x << 8 | y << 4),
+        1 => (prop::sample::select(vec![0xE09Eu16, 0xE0A1]), x.clone())
+            .prop_map(|(opcode, x)| opcode | x << 8),
+        2 => (prop::sample::select(vec![0x07u16, 0x0A, 0x15, 0x18, 0x1E, 0x29, 0x33, 0x55, 0x65]), x)
+            .prop_map(|(low, x)| 0xF000 | x << 8 | low),
+    ]
+    .prop_map(u16::to_be_bytes)
+}
+
+// Loops of arithmetic that rarely fault, so the recompiler gets long
+// blocks run many times over
+fn arithmetic() -> impl Strategy<Value = [u8; 2]> {
+    let x = 0u16..16;
+    prop_oneof![
+        1 => (x.clone(), 0u16..0x100).prop_map(|(x, nn)| 0x6000 | x << 8 | nn),
+        1 => (x.clone(), 0u16..0x100).prop_map(|(x, nn)| 0x7000 | x << 8 | nn),
+        4 => (x, 0u16..16, prop::sample::select(vec![0u16, 1, 2, 3, 4, 5, 6, 7, 0xE]))
+            .prop_map(|(x, y, n)| 0x8000 | x << 8 | y << 4 | n),
+    ]
+    .prop_map(u16::to_be_bytes)
+}
+
+fn rom() -> impl Strategy<Value = Vec<u8>> {
+    prop_oneof![
+        prop::collection::vec(any::<u8>(), 0..=MAX_ROM),
+        prop::collection::vec(instruction(), 0..=MAX_WORDS).prop_map(|words| words.concat()),
+        prop::collection::vec(arithmetic(), 1..=MAX_WORDS).prop_map(|words| {
+            let mut rom = words.concat();
+            rom.extend_from_slice(&(0x1000 | START_ADDRESS).to_be_bytes());
+            rom
+        }),
+    ]
+}
+
+fn keys() -> impl Strategy<Value = Vec<u16>> {
+    prop::collection::vec(any::<u16>(), 0..=u8::MAX as usize)
+}
+
+proptest! {
+    #[test]
+    fn random_roms_never_panic(rom in rom(), keys in keys()) {
+        harness::run(&encode(&rom, &keys));
+    }
+
+    #[test]
+    fn interpreter_does_not_allocate(rom in rom(), keys in keys()) {
+        let mut processor = Processor::with_seed(0);
+        processor.load(&rom);
+
+        let before = allocations();
+        'frames: for mask in keys {
+            for key in 0..16 {
+                processor.keypress(key, mask & (1 << key) != 0);
+            }
+            for _ in 0..10 {
+                if processor.try_tick().is_err() {
+                    break 'frames;
+                }
+            }
+            processor.tick_timers();
+        }
+        prop_assert_eq!(allocations(), before);
+    }
+}
+
+#[test]
+fn corpus_replays_cleanly() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/fuzz/corpus/interpreter");
+    let mut replayed = 0;
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        harness::run(&fs::read(&path).unwrap());
+        replayed += 1;
+    }
+    assert!(replayed > 0, "empty corpus");
+}
@@ -0,0 +1,125 @@
+// Runs the recompiler side by side with the interpreter on random
+// programs, which have to end up in exactly the same state
+
+#![cfg(feature = "std")]
+
+use backend::Processor;
+
+const PROGRAMS: u64 = 500;
+const ROM_WORDS: usize = 64;
+const TICKS: usize = 2000;
+
+// xorshift64, good enough for making up programs
+struct Gen(u64);
+
+impl Gen {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u16 {
+        (self.next() % n) as u16
+    }
+}
+
+// Mostly valid instructions, with jumps, calls and I pointing into the
+// program so stores rewrite code that has already been compiled
+fn random_rom(seed: u64) -> Vec<u8> {
+    let mut gen = Gen(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1);
+    let mut rom = Vec::with_capacity(ROM_WORDS * 2);
+    for _ in 0..ROM_WORDS {
+        let x = gen.below(16);
+        let y = gen.below(16);
+        let nn = gen.below(256);
+        let target = 0x200 + gen.below(ROM_WORDS as u64) * 2;
+        let alu = [0, 1, 2, 3, 4, 5, 6, 7, 0xE][gen.below(9) as usize];
+        let timer = [0x07, 0x15, 0x18, 0x1E, 0x29][gen.below(5) as usize];
+        let opcode = match gen.below(24) {
+            0 => 0x00E0,
+            1 => 0x00EE,
+            2 => 0x1000 | target,
+            3 => 0x2000 | target,
+            4 => 0x3000 | x << 8 | nn,
+            5 => 0x4000 | x << 8 | nn,
+            6 => 0x5000 | x << 8 | y << 4,
+            7 | 8 => 0x6000 | x << 8 | nn,
+            9 | 10 => 0x7000 | x << 8 | nn,
+            11 => 0x8000 | x << 8 | y << 4 | alu,
+            12 => 0x9000 | x << 8 | y << 4,
+            13 => 0xA000 | (0x200 + gen.below(ROM_WORDS as u64 * 2)),
+            14 => 0xA000 | (0x300 + nn),
+            15 => 0xB000 | target,
+            16 => 0xC000 | x << 8 | nn,
+            17 => 0xD000 | x << 8 | y << 4 | gen.below(16),
+            18 => 0xE09E | x << 8,
+            19 => 0xE0A1 | x << 8,
+            20 => 0xF000 | x << 8 | timer,
+            21 => 0xF033 | x << 8,
+            22 => 0xF055 | gen.below(4) << 8,
+            _ => 0xF065 | x << 8,
+        };
+        rom.extend_from_slice(&opcode.to_be_bytes());
+    }
+    rom
+}
+
+fn assert_same(interpreted: &Processor, recompiled: &Processor, seed: u64) {
+    assert_eq!(interpreted.registers(), recompiled.registers(), "program {}", seed);
+    assert_eq!(interpreted.stack(), recompiled.stack(), "program {}", seed);
+    assert_eq!(interpreted.ram(), recompiled.ram(), "program {}", seed);
+    assert_eq!(interpreted.get_display(), recompiled.get_display(), "program {}", seed);
+}
+
+#[test]
+fn recompiler_matches_interpreter() {
+    for seed in 0..PROGRAMS {
+        let rom = random_rom(seed);
+        let mut interpreted = Processor::with_seed(seed);
+        let mut recompiled = Processor::with_seed(seed);
+        interpreted.load(&rom);
+        recompiled.load(&rom);
+        recompiled.set_recompiler(true);
+        // key 5 held so EX9E, EXA1 go both ways
+        interpreted.keypress(5, true);
+        recompiled.keypress(5, true);
+
+        // uneven slices stop blocks part way through
+        let mut gen = Gen(seed + 1);
+        let mut ticks = 0;
+        while ticks < TICKS {
+            let slice = 1 + gen.below(37) as usize;
+            let expected = (0..slice).try_for_each(|_| interpreted.try_tick());
+            let actual = recompiled.run(slice);
+            assert_eq!(expected, actual, "program {}", seed);
+            assert_same(&interpreted, &recompiled, seed);
+            if expected.is_err() {
+                break;
+            }
+            interpreted.tick_timers();
+            recompiled.tick_timers();
+            ticks += slice;
+        }
+    }
+}
+
+#[test]
+fn recompiler_sees_writes_from_outside() {
+    // LD V0, 0x01, then JP 0x202 forever
+    let rom = [0x60, 0x01, 0x12, 0x02];
+    let mut processor = Processor::with_seed(0);
+    processor.set_recompiler(true);
+    processor.load(&rom);
+    processor.run(10).unwrap();
+    assert_eq!(processor.registers().pc, 0x202);
+
+    // turn the jump into ADD V0, 0x02 and run into the zeroes after it
+    processor.write_ram(0x202, 0x70);
+    processor.write_ram(0x203, 0x02);
+    processor.run(2).unwrap();
+    let registers = processor.registers();
+    assert_eq!(registers.v[0], 0x03);
+    assert_eq!(registers.pc, 0x206);
+}
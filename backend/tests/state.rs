@@ -0,0 +1,122 @@
+use backend::chip8x::Color;
+use backend::{Processor, StateError, STATE_SIZE};
+
+// Draws the 0 from the font, colors the top left block green, steps the
+// background to black and waits for a key
+const CHIP8X_ROM: [u8; 14] = [
+    0xD0, 0x05, // DRW V0, V0, 5
+    0x60, 0x00, 0x61, 0x00, 0x62, 0x04, 0xB0, 0x20, // green block
+    0x02, 0xA0, // black background
+    0xF3, 0x0A, // LD V3, K
+];
+
+fn assert_same(a: &Processor, b: &Processor) {
+    assert_eq!(a.registers(), b.registers());
+    assert_eq!(a.stack(), b.stack());
+    assert_eq!(a.ram(), b.ram());
+    assert_eq!(a.get_display(), b.get_display());
+    assert_eq!(a.start_address(), b.start_address());
+    assert_eq!(a.chip8x(), b.chip8x());
+    assert_eq!(a.cdp1802(), b.cdp1802());
+}
+
+#[test]
+fn round_trips() {
+    let mut processor = Processor::with_seed(0);
+    processor.load(&[0x6A, 0x42, 0xFA, 0x15, 0xD0, 0x05, 0x22, 0x08, 0x12, 0x08]);
+    processor.run(5).unwrap();
+    let state = processor.save_state();
+    assert_eq!(state.len(), STATE_SIZE);
+
+    let mut restored = Processor::with_seed(1);
+    restored.load_state(&state).unwrap();
+    assert_same(&processor, &restored);
+    assert_eq!(restored.save_state(), state);
+}
+
+#[test]
+fn chip8x_round_trips() {
+    let mut processor = Processor::with_seed(0);
+    processor.set_chip8x(true);
+    processor.load(&CHIP8X_ROM);
+    processor.run(6).unwrap();
+    processor.second_keypress(0xA, true);
+    let state = processor.save_state();
+
+    // turns CHIP-8X on to match
+    let mut restored = Processor::with_seed(0);
+    restored.load_state(&state).unwrap();
+    assert_same(&processor, &restored);
+    let colors = restored.chip8x().unwrap();
+    assert_eq!(colors.background(), Color::Black);
+    assert_eq!(colors.foreground(0, 0), Color::Green);
+    assert_eq!(colors.foreground(8, 0), Color::Red);
+
+    // the second keypad came along, so EXF2 still sees key A
+    restored.load(&[0x63, 0x0A, 0xE3, 0xF2, 0x60, 0x01]);
+    restored.run(3).unwrap();
+    assert_eq!(restored.registers().v[0], 0);
+
+    // and off again
+    let mut plain = Processor::with_seed(0);
+    plain.load(&CHIP8X_ROM);
+    let mut chip8x = Processor::with_seed(0);
+    chip8x.load_state(&state).unwrap();
+    chip8x.load_state(&plain.save_state()).unwrap();
+    assert!(chip8x.chip8x().is_none());
+    assert_eq!(chip8x.start_address(), plain.start_address());
+}
+
+#[test]
+fn cdp1802_and_start_address_round_trip() {
+    // SEX R2 and return
+    let mut processor = Processor::with_seed(0);
+    processor.set_cdp1802(true);
+    processor.set_start_address(0x600);
+    processor.load(&[0x07, 0x00]);
+    processor.write_ram(0x700, 0xE2);
+    processor.write_ram(0x701, 0xD4);
+    processor.run(1).unwrap();
+    assert_eq!(processor.cdp1802().unwrap().x, 2);
+
+    let mut restored = Processor::with_seed(0);
+    restored.load_state(&processor.save_state()).unwrap();
+    assert_same(&processor, &restored);
+    assert_eq!(restored.start_address(), 0x600);
+    restored.reset();
+    assert_eq!(restored.registers().pc, 0x600);
+}
+
+#[test]
+fn rejects_bad_states() {
+    let mut processor = Processor::with_seed(0);
+    processor.set_chip8x(true);
+    let state = processor.save_state();
+    assert_eq!(
+        processor.load_state(&state[1..]),
+        Err(StateError::WrongSize)
+    );
+
+    // version 1 states are the same up to the version byte
+    let mut old = state;
+    old[2] = 1;
+    assert_eq!(processor.load_state(&old), Err(StateError::WrongVersion));
+
+    // stack pointer, unknown modes, background and a zone color
+    for offset in [
+        3 + 2 + 4096 + 256 + 16 + 2,
+        STATE_SIZE - 40 - 2 - 256 - 1 - 1,
+        STATE_SIZE - 40 - 2 - 256 - 1,
+        STATE_SIZE - 40 - 2 - 1,
+    ] {
+        let mut corrupt = state;
+        corrupt[offset] = 0xFF;
+        assert_eq!(
+            processor.load_state(&corrupt),
+            Err(StateError::Corrupt),
+            "offset {}",
+            offset
+        );
+    }
+    assert_eq!(processor.save_state(), state);
+}
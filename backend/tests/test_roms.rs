@@ -0,0 +1,91 @@
+// Runs every ROM in tests/roms and compares the screen with the
+// expectation next to it. Set BLESS=1 to write the current screens as the
+// expectations instead, e.g. after adding a ROM, and check them by eye
+
+#![cfg(feature = "std")]
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use backend::testrom::Expectation;
+
+#[test]
+fn test_roms_match_expected_screens() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/roms");
+    let bless = env::var_os("BLESS").is_some();
+    let mut failures = Vec::new();
+    let mut checked = 0;
+
+    let mut roms: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ch8"))
+        .collect();
+    roms.sort();
+
+    for rom_path in roms {
+        let rom = fs::read(&rom_path).unwrap();
+        let expected_path = rom_path.with_extension("txt");
+        let mut expectation = if expected_path.exists() {
+            Expectation::load(&expected_path).unwrap()
+        } else {
+            Expectation::new()
+        };
+
+        let name = rom_path.file_stem().unwrap().to_string_lossy();
+        let actual = match expectation.run(&rom) {
+            Ok(actual) => actual,
+            Err(error) => {
+                failures.push(format!("{}: {}", name, error));
+                continue;
+            }
+        };
+        checked += 1;
+
+        if bless {
+            expectation.screen = actual;
+            fs::write(&expected_path, expectation.to_string()).unwrap();
+        } else if !expected_path.exists() {
+            failures.push(format!("{}: no {}, run with BLESS=1", name, expected_path.display()));
+        } else if let Some(diff) = expectation.diff(&actual) {
+            failures.push(format!("{}: {}", name, diff));
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+    assert!(checked > 0, "no ROMs in {}", dir.display());
+}
+
+#[test]
+fn diff_marks_differing_rows() {
+    let mut expectation = Expectation::new();
+    let mut actual = expectation.screen.clone();
+    assert_eq!(expectation.diff(&actual), None);
+
+    expectation.screen[0] = true;
+    actual[64 * 2 + 3] = true;
+    let diff = expectation.diff(&actual).unwrap();
+    let lines: Vec<_> = diff.lines().collect();
+    assert_eq!(lines[0], "2 pixels differ");
+    assert!(lines[2].starts_with("> #..."));
+    assert!(lines[3].starts_with("  ...."));
+    assert!(lines[4].starts_with("> ....") && lines[4].contains("| ...#"));
+}
+
+#[test]
+fn expectation_round_trips() {
+    let mut expectation = Expectation::new();
+    expectation.frames = 5;
+    expectation.selector = Some(3);
+    expectation.screen[100] = true;
+    let text = expectation.to_string();
+    assert!(text.starts_with("# frames 5\n# selector 3\n"));
+    assert_eq!(Expectation::parse(&text), Ok(expectation));
+
+    // comments are fine before the screen, a short screen isn't
+    let text = format!("# from the suite\n{}", text);
+    assert!(Expectation::parse(&text).is_ok());
+    let short: String = text.lines().take(10).map(|line| format!("{}\n", line)).collect();
+    assert!(Expectation::parse(&short).is_err());
+}
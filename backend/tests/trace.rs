@@ -0,0 +1,162 @@
+#![cfg(feature = "std")]
+
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use backend::trace::{
+    AddressFilter, RingTrace, TraceFormat, TraceLog, TraceStep, Tracer, BINARY_MAGIC,
+};
+use backend::{ErrorKind, Processor};
+
+// LD VA, 0x02, LD I, 0x22A, then RET with nothing to return to
+const ROM: [u8; 6] = [0x6A, 0x02, 0xA2, 0x2A, 0x00, 0xEE];
+
+// Writer the test can still read after handing the log to the processor
+#[derive(Clone, Default)]
+struct Shared(Arc<Mutex<Vec<u8>>>);
+
+impl Shared {
+    fn bytes(&self) -> Vec<u8> {
+        self.0.lock().unwrap().clone()
+    }
+
+    fn text(&self) -> String {
+        String::from_utf8(self.bytes()).unwrap()
+    }
+}
+
+impl Write for Shared {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn traced<T: Tracer + Send + 'static>(rom: &[u8], tracer: T) -> ErrorKind {
+    let mut processor = Processor::with_seed(0);
+    processor.load(rom);
+    processor.set_tracer(Box::new(tracer));
+    processor.run(16).unwrap_err().kind
+}
+
+#[test]
+fn text_format() {
+    let out = Shared::default();
+    traced(&ROM, TraceLog::new(out.clone(), TraceFormat::Text));
+    assert_eq!(
+        out.text(),
+        "0200 6A02 LD VA, 0x02 ; VA=00->02\n\
+         0202 A22A LD I, 0x22A ; I=000->22A\n\
+         ! stack underflow at 0x204 (opcode 0x00EE)\n"
+    );
+}
+
+#[test]
+fn text_lists_every_change() {
+    // LD V0, 0x01 then CALL 0x206, where LD DT, V0 and LD ST, V0 run
+    let rom = [0x60, 0x01, 0x22, 0x06, 0x00, 0x00, 0xF0, 0x15, 0xF0, 0x18, 0x00, 0xEE];
+    let out = Shared::default();
+    let mut processor = Processor::with_seed(0);
+    processor.load(&rom);
+    processor.set_tracer(Box::new(TraceLog::new(out.clone(), TraceFormat::Text)));
+    processor.run(5).unwrap();
+    let text = out.text();
+    let lines: Vec<_> = text.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "0200 6001 LD V0, 0x01 ; V0=00->01",
+            "0202 2206 CALL 0x206 ; SP=0->1",
+            "0206 F015 LD DT, V0 ; DT=00->01",
+            "0208 F018 LD ST, V0 ; ST=00->01",
+            "020A 00EE RET ; SP=1->0",
+        ]
+    );
+}
+
+#[test]
+fn binary_format() {
+    let out = Shared::default();
+    traced(&ROM, TraceLog::new(out.clone(), TraceFormat::Binary));
+
+    let mut expected = BINARY_MAGIC.to_vec();
+    // VA is bit 10 of the mask
+    expected.extend_from_slice(&[0x00, 0x02, 0x00, 0x6A, 0x02, 0x00, 0x00, 0x04, 0x00, 0x02]);
+    // I is bit 16, and a u16
+    expected.extend_from_slice(&[0x00, 0x02, 0x02, 0xA2, 0x2A, 0x00, 0x01, 0x00, 0x00, 0x02, 0x2A]);
+    expected.extend_from_slice(&[0x01, 0x02, 0x04, 0x00, 0xEE, 15]);
+    expected.extend_from_slice(b"stack underflow");
+    assert_eq!(out.bytes(), expected);
+}
+
+#[test]
+fn binary_magic_only_once_something_is_traced() {
+    let out = Shared::default();
+    let log = TraceLog::new(out.clone(), TraceFormat::Binary);
+    assert!(log.into_inner().unwrap().bytes().is_empty());
+}
+
+#[test]
+fn address_filter_keeps_steps_in_range_and_all_errors() {
+    let out = Shared::default();
+    let log = TraceLog::new(out.clone(), TraceFormat::Text);
+    traced(&ROM, AddressFilter::new(0x202..=0x202, log));
+    assert_eq!(
+        out.text(),
+        "0202 A22A LD I, 0x22A ; I=000->22A\n\
+         ! stack underflow at 0x204 (opcode 0x00EE)\n"
+    );
+}
+
+#[test]
+fn ring_trace_dumps_the_last_steps_on_error() {
+    // four loads, then RET with nothing to return to
+    let rom = [0x60, 0x01, 0x61, 0x02, 0x62, 0x03, 0x63, 0x04, 0x00, 0xEE];
+    let out = Shared::default();
+    let log = TraceLog::new(out.clone(), TraceFormat::Text);
+    let kind = traced(&rom, RingTrace::new(2, log));
+    assert_eq!(kind, ErrorKind::StackUnderflow);
+    assert_eq!(
+        out.text(),
+        "0204 6203 LD V2, 0x03 ; V2=00->03\n\
+         0206 6304 LD V3, 0x04 ; V3=00->04\n\
+         ! stack underflow at 0x208 (opcode 0x00EE)\n"
+    );
+}
+
+#[test]
+fn ring_trace_stays_quiet_without_errors() {
+    let out = Shared::default();
+    let mut processor = Processor::with_seed(0);
+    processor.load(&[0x60, 0x01, 0x12, 0x02]);
+    let log = TraceLog::new(out.clone(), TraceFormat::Text);
+    processor.set_tracer(Box::new(RingTrace::new(3, log)));
+    processor.run(100).unwrap();
+    assert!(out.bytes().is_empty());
+}
+
+#[test]
+fn ring_trace_keeps_at_most_its_capacity() {
+    let mut ring = RingTrace::new(3, TraceLog::new(Vec::new(), TraceFormat::Text));
+    let mut processor = Processor::with_seed(0);
+    processor.load(&[0x70, 0x01, 0x12, 0x00]);
+    let mut before = processor.registers();
+    for _ in 0..10 {
+        let address = processor.registers().pc;
+        processor.tick();
+        let after = processor.registers();
+        ring.record(&TraceStep {
+            address,
+            opcode: 0,
+            before,
+            after,
+        });
+        before = after;
+    }
+    let addresses: Vec<_> = ring.steps().map(|step| step.address).collect();
+    assert_eq!(addresses, [0x202, 0x200, 0x202]);
+}
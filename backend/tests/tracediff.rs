@@ -0,0 +1,176 @@
+#![cfg(feature = "std")]
+
+use std::env;
+use std::fs;
+use std::process::{self, Command};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use backend::memory_constants::START_ADDRESS;
+use backend::tracediff::{compare_traces, run_lockstep, Difference, KeyScript, Schedule};
+use backend::Processor;
+
+// The same ROM on both sides, the second with CHIP-8X on so BNNN doesn't
+// jump there. Both load at 0x200
+fn pair(rom: &[u8]) -> (Processor, Processor) {
+    let mut a = Processor::with_seed(0);
+    let mut b = Processor::with_seed(0);
+    b.set_chip8x(true);
+    b.set_start_address(START_ADDRESS);
+    a.load(rom);
+    b.load(rom);
+    (a, b)
+}
+
+#[test]
+fn identical_runs_agree() {
+    // CXNN into V0, store it, then RET with an empty stack
+    let rom = [0xC0, 0xFF, 0xA3, 0x00, 0xF0, 0x55, 0x00, 0xEE];
+    let mut a = Processor::with_seed(7);
+    let mut b = Processor::with_seed(7);
+    a.load(&rom);
+    b.load(&rom);
+    assert_eq!(run_lockstep(&mut a, &mut b, &Schedule::default(), 100, 4), None);
+}
+
+#[test]
+fn reports_the_first_difference_with_context() {
+    let rom = [0xC0, 0xFF, 0x61, 0x01, 0x62, 0x02, 0x12, 0x06];
+    let mut a = Processor::with_seed(1);
+    let mut b = Processor::with_seed(2);
+    a.load(&rom);
+    b.load(&rom);
+    // the seeds only show in V0
+    let divergence = run_lockstep(&mut a, &mut b, &Schedule::default(), 100, 2).unwrap();
+    assert_eq!(divergence.step, 0);
+    assert!(matches!(divergence.differences[..], [Difference::V(0, _, _)]));
+    assert_eq!(divergence.history.len(), 1);
+    let after: Vec<_> = divergence.after_a.iter().map(|step| step.address).collect();
+    assert_eq!(after, [0x202, 0x204]);
+    assert!(divergence.to_string().starts_with("runs diverge at step 0\n  V0 "));
+}
+
+#[test]
+fn timers_count_down_between_frames() {
+    // DT = 3, wait for it to run out, then BNNN
+    let rom = [
+        0x60, 0x03, 0xF0, 0x15, // LD V0, 0x03; LD DT, V0
+        0xF1, 0x07, 0x31, 0x00, 0x12, 0x04, // wait for DT == 0
+        0xB2, 0x10,
+    ];
+    let (mut a, mut b) = pair(&rom);
+    let divergence = run_lockstep(&mut a, &mut b, &Schedule::default(), 1000, 2).unwrap();
+    assert!(divergence.step >= 30, "{}", divergence);
+    assert!(divergence.differences.contains(&Difference::Pc(0x213, 0x20C)));
+
+    // with four instructions a frame DT runs out after fewer of them
+    let (mut a, mut b) = pair(&rom);
+    let schedule = Schedule {
+        ticks_per_frame: 4,
+        ..Schedule::default()
+    };
+    let shorter = run_lockstep(&mut a, &mut b, &schedule, 1000, 2).unwrap();
+    assert!((12..30).contains(&shorter.step), "{}", shorter);
+}
+
+#[test]
+fn keys_come_from_the_script() {
+    // wait for a key into V0, then BNNN
+    let rom = [0xF0, 0x0A, 0xB2, 0x00];
+    let (mut a, mut b) = pair(&rom);
+    assert_eq!(run_lockstep(&mut a, &mut b, &Schedule::default(), 1000, 2), None);
+
+    let schedule = Schedule {
+        keys: KeyScript::parse("# tap 5\npress 5 from 3 to 4\n").unwrap(),
+        ..Schedule::default()
+    };
+    let (mut a, mut b) = pair(&rom);
+    let divergence = run_lockstep(&mut a, &mut b, &schedule, 1000, 2).unwrap();
+    assert_eq!(divergence.step, 31);
+    assert!(divergence.differences.contains(&Difference::Pc(0x205, 0x204)));
+}
+
+#[test]
+fn key_script_errors() {
+    assert!(KeyScript::parse("press 5 from 0 to 10\n\n# comment").is_ok());
+    for text in ["press 10 from 0 to 1", "press 5 from a to 1", "hold 5"] {
+        let error = KeyScript::parse(text).unwrap_err();
+        assert_eq!(error.to_string(), "invalid key script on line 1");
+    }
+}
+
+#[test]
+fn traces_that_match() {
+    let trace = "0200 6001 LD V0, 0x01 ; V0=00->01\n0202 1202 JP 0x202\n";
+    assert_eq!(compare_traces(trace.as_bytes(), trace.as_bytes(), 2).unwrap(), None);
+}
+
+#[test]
+fn first_differing_line() {
+    let a = "one\ntwo\nthree\nfour\nfive\nsix\n";
+    let b = "one\ntwo\nthree\nFOUR\nfive\n";
+    let divergence = compare_traces(a.as_bytes(), b.as_bytes(), 2).unwrap().unwrap();
+    assert_eq!(divergence.line, 4);
+    assert_eq!(divergence.before, ["two", "three"]);
+    assert_eq!(divergence.a, ["four", "five", "six"]);
+    assert_eq!(divergence.b, ["FOUR", "five"]);
+}
+
+#[test]
+fn one_trace_ends_first() {
+    let a = "one\ntwo\n";
+    let b = "one\n";
+    let divergence = compare_traces(a.as_bytes(), b.as_bytes(), 1).unwrap().unwrap();
+    assert_eq!(divergence.line, 2);
+    assert!(divergence.b.is_empty());
+    assert_eq!(
+        divergence.to_string(),
+        "traces diverge at line 2\n    one\n  a two\n  b <end of trace>\n"
+    );
+}
+
+// LD V0, 2 and JP V0, 0x204, which CHIP-8X reads as a BXYN, then a RET
+// with nothing to return to either way
+const QUIRK_ROM: [u8; 8] = [0x60, 0x02, 0xB2, 0x04, 0x00, 0xEE, 0x00, 0xEE];
+
+// Tests run in parallel, so each run gets its own copy of the ROM
+static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+fn trace_diff(args: &[&str]) -> (Option<i32>, String, String) {
+    let run = RUNS.fetch_add(1, Ordering::SeqCst);
+    let name = format!("chip8-trace-diff-{}-{}.ch8", process::id(), run);
+    let path = env::temp_dir().join(name);
+    fs::write(&path, QUIRK_ROM).unwrap();
+    let rom = path.to_str().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_trace-diff"))
+        .args(["--roms", rom, rom])
+        .args(args)
+        .output()
+        .unwrap();
+    let _ = fs::remove_file(&path);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    (output.status.code(), stdout, stderr)
+}
+
+#[test]
+fn one_rom_under_two_settings() {
+    let (code, stdout, _) = trace_diff(&[]);
+    assert_eq!((code, stdout.as_str()), (Some(0), "no divergence found\n"));
+
+    // both at 0x200, so only the quirk differs
+    let (code, stdout, _) = trace_diff(&["--b-chip8x", "--b-start", "200"]);
+    assert_eq!(code, Some(1));
+    assert!(stdout.contains("PC"), "{}", stdout);
+
+    // the recompiler agrees with the interpreter
+    let (code, _, _) = trace_diff(&["--a-recompiler"]);
+    assert_eq!(code, Some(0));
+}
+
+#[test]
+fn usage_goes_to_stderr() {
+    let (code, stdout, stderr) = trace_diff(&["--c-chip8x"]);
+    assert_eq!(code, Some(2));
+    assert!(stdout.is_empty());
+    assert!(stderr.starts_with("usage: trace-diff"));
+}
@@ -0,0 +1,159 @@
+// The original interpreter can't be shipped, so these run a small
+// stand-in written for the tests. It turns the display on, points the
+// 1861 at 0xF00 on every interrupt and sets Q while key 5 is held. Set
+// VIP_INTERPRETER to the path of a real image and run the ignored tests
+// to also run the homebrew ROMs on it
+
+use backend::machine::{Control, Core, InputSource, Machine, VideoSink};
+use backend::vip::{Vip, INTERPRETER_SIZE};
+use backend::Processor;
+
+#[rustfmt::skip]
+const STAND_IN: [u8; 0x4E] = {
+    let mut image = [0; 0x4E];
+    let main = [
+        0xF8, 0x04, 0xA3, 0xD3, // LDI 0x04, PLO R3, SEP R3: R0 is for DMA
+        0xF8, 0x00, 0xB1,       // LDI 0x00, PHI R1
+        0xF8, 0x42, 0xA1,       // LDI 0x42, PLO R1: interrupts run from 0x042
+        0xF8, 0x0E, 0xB2,       // LDI 0x0E, PHI R2
+        0xF8, 0xCF, 0xA2,       // LDI 0xCF, PLO R2: stack at 0xECF
+        0xE2,                   // SEX R2
+        0x69,                   // INP 1: display on
+        0xF8, 0x05, 0x52,       // LDI 0x05, STR R2
+        0x62,                   // OUT 2: scan key 5
+        0x22,                   // DEC R2
+        0x36, 0x1C,             // 0x17: B3 0x1C
+        0x7A,                   // REQ
+        0x30, 0x17,             // BR 0x17
+        0x7B,                   // 0x1C: SEQ
+        0x30, 0x17,             // BR 0x17
+    ];
+    let interrupt = [
+        0x72,                   // 0x40: LDXA
+        0x70,                   // RET
+        0x22, 0x78, 0x22, 0x52, // 0x42: DEC R2, SAV, DEC R2, STR R2
+        0xF8, 0x0F, 0xB0,       // LDI 0x0F, PHI R0
+        0xF8, 0x00, 0xA0,       // LDI 0x00, PLO R0
+        0x30, 0x40,             // BR 0x40
+    ];
+    let mut i = 0;
+    while i < main.len() {
+        image[i] = main[i];
+        i += 1;
+    }
+    let mut i = 0;
+    while i < interrupt.len() {
+        image[0x40 + i] = interrupt[i];
+        i += 1;
+    }
+    image
+};
+
+fn vip() -> Vip {
+    let mut vip = Vip::new(&STAND_IN).unwrap();
+    // top left pixels of rows 0 and 1, a row is 4 lines of 8 bytes
+    vip.load_at(0xF00, &[0xFF]).unwrap();
+    vip.load_at(0xF00 + 4 * 8, &[0x80]).unwrap();
+    vip
+}
+
+#[test]
+fn oversized_interpreter_is_refused() {
+    assert!(Vip::new(&[0; INTERPRETER_SIZE]).is_ok());
+    assert!(Vip::new(&[0; INTERPRETER_SIZE + 1]).is_err());
+}
+
+#[test]
+fn display_comes_from_dma() {
+    let mut vip = vip();
+    vip.run(0).unwrap();
+    vip.run(0).unwrap();
+    let display = vip.get_display();
+    assert_eq!(display[..9], [true, true, true, true, true, true, true, true, false]);
+    assert_eq!(display[64..66], [true, false]);
+    // the stand-in doesn't rewind R0 to repeat rows, so the rest of the
+    // screen runs on past the end of RAM into the interpreter
+    assert!(display[128..8 * 64].iter().all(|pixel| !pixel));
+    // each line's 8 bytes were read, so R0 ended past the picture
+    assert_eq!(vip.cpu().r[0], 0xF00 + 128 * 8);
+}
+
+#[test]
+fn keypad_and_speaker() {
+    let mut vip = vip();
+    vip.run(0).unwrap();
+    assert!(!vip.get_sound());
+    vip.keypress(5, true);
+    vip.run(0).unwrap();
+    assert!(vip.get_sound());
+    vip.keypress(5, false);
+    vip.keypress(6, true);
+    vip.run(0).unwrap();
+    assert!(!vip.get_sound());
+}
+
+#[test]
+fn reset_restores_the_interpreter() {
+    let mut vip = vip();
+    vip.run(0).unwrap();
+    vip.reset();
+    assert_eq!(vip.ram()[..STAND_IN.len()], STAND_IN);
+    assert_eq!(vip.ram()[0xF00], 0);
+    assert_eq!(vip.cpu().r[1], 0xF00);
+    assert!(vip.get_display().iter().all(|pixel| !pixel));
+}
+
+#[derive(Default)]
+struct LastFrame(Vec<bool>);
+
+impl VideoSink for LastFrame {
+    fn present(&mut self, display: &[bool]) {
+        self.0 = display.to_vec();
+    }
+}
+
+struct NoKeys;
+
+impl InputSource for NoKeys {
+    fn poll(&mut self, _keys: &mut [bool; 16]) -> Control {
+        Control::Continue
+    }
+}
+
+#[test]
+fn machine_runs_either_core() {
+    fn frame<C: Core>(core: C) -> Vec<bool> {
+        let mut machine = Machine::new(core, LastFrame::default(), (), NoKeys);
+        machine.run_frame().unwrap();
+        machine.run_frame().unwrap();
+        machine.into_parts().1 .0
+    }
+
+    // both draw the top row of a sprite at the top left
+    let mut processor = Processor::with_seed(0);
+    processor.load(&[0xA2, 0x06, 0xD0, 0x01, 0x12, 0x04, 0xFF]);
+    assert_eq!(frame(processor)[..9], frame(vip())[..9]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+#[ignore = "needs VIP_INTERPRETER set to an image of the original interpreter"]
+fn homebrew_roms_pass_on_the_original_interpreter() {
+    use backend::testrom::{assemble_self_check, SIGNATURE_ADDRESS};
+
+    let path = std::env::var_os("VIP_INTERPRETER").expect("VIP_INTERPRETER isn't set");
+    let interpreter = std::fs::read(path).unwrap();
+    // the others pin down this crate's quirks or its instructions per frame
+    for name in ["bcd", "collision", "flags"] {
+        let path = format!("{}/tests/homebrew/{}.asm", env!("CARGO_MANIFEST_DIR"), name);
+        let rom = assemble_self_check(&std::fs::read_to_string(path).unwrap()).unwrap();
+        let mut vip = Vip::new(&interpreter).unwrap();
+        vip.load(&rom);
+        for _ in 0..120 {
+            vip.run(0).unwrap();
+        }
+        let signature = &vip.ram()[SIGNATURE_ADDRESS..SIGNATURE_ADDRESS + 2];
+        assert_eq!(signature, b"OK", "{} failed check {}", name, signature[1]);
+    }
+}
+
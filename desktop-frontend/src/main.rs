@@ -1,21 +1,34 @@
 use std::env;
 use std::fs::File;
 use std::io::Read;
+use std::process;
+use std::sync::mpsc::{self, Receiver, Sender};
 use backend::*;
+use backend::cheats::{Cheat, CheatList, Comparison, MemorySearch};
+use backend::chip8x::Chip8x;
+use backend::gdb::{GdbStub, Status};
+use backend::machine::{AudioSink, Control, InputSource, Machine, VideoSink};
+use backend::manifest::Manifest;
+use backend::trace::{TraceFormat, TraceLog};
+use backend::vip::Vip;
+use sdl2::EventPump;
 use sdl2::event::Event;
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use sdl2::render::Canvas;
 use sdl2::video::Window;
 use sdl2::keyboard::Keycode;
-use sdl2::audio::{AudioCallback, AudioSpecDesired};
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
 
 use backend::display_constants::{SCREEN_WIDTH, SCREEN_HEIGHT};
+use backend::input_constants::NUM_KEYS;
 
 const SCALE: u32 = 15;
 const WINDOW_WIDTH: u32 = (SCREEN_WIDTH as u32) * SCALE;
 const WINDOW_HEIGHT: u32 = (SCREEN_HEIGHT as u32) * SCALE;
 const TICKS_PER_FRAME: usize = 10;
+// candidates to print after a search, and to freeze at most
+const SHOWN_CANDIDATES: usize = 16;
 
 struct SquareWave {
     phase_inc: f32,
@@ -39,12 +52,153 @@ impl AudioCallback for SquareWave {
     }
 }
 
+struct SdlVideo {
+    canvas: Canvas<Window>,
+}
+
+impl VideoSink for SdlVideo {
+    fn present(&mut self, display: &[bool]) {
+        draw_screen(display, None, &mut self.canvas);
+    }
+
+    fn present_colors(&mut self, display: &[bool], colors: &Chip8x) {
+        draw_screen(display, Some(colors), &mut self.canvas);
+    }
+}
+
+struct SdlAudio {
+    device: AudioDevice<SquareWave>,
+}
+
+impl AudioSink for SdlAudio {
+    fn set_beeping(&mut self, beeping: bool) {
+        if beeping {
+            self.device.resume();
+        } else {
+            self.device.pause();
+        }
+    }
+}
+
+// Cheat search on the function keys: F1 starts a search, F2 to F5 keep
+// the addresses that decreased, increased, changed or stayed the same
+// since the last search, F6 freezes what's left at its current value and
+// F7 saves the cheats for the ROM
+enum CheatCommand {
+    Search,
+    Narrow(Comparison),
+    Freeze,
+    Save,
+}
+
+struct SdlInput {
+    event_pump: EventPump,
+    cheat_commands: Sender<CheatCommand>,
+    second_keys: [bool; NUM_KEYS], // CHIP-8X's, on the numpad
+}
+
+impl InputSource for SdlInput {
+    fn poll(&mut self, keys: &mut [bool; NUM_KEYS]) -> Control {
+        for event in self.event_pump.poll_iter() {
+            match event {
+                Event::Quit{..} => {
+                    return Control::Quit;
+                },
+                Event::KeyDown{keycode: Some(key), repeat: false, ..} => {
+                    if let Some(k) = key_to_button(key) {
+                        keys[k] = true;
+                    }
+                    if let Some(k) = key_to_second_button(key) {
+                        self.second_keys[k] = true;
+                    }
+                    if let Some(command) = key_to_cheat_command(key) {
+                        // nobody listens when cheats aren't available
+                        let _ = self.cheat_commands.send(command);
+                    }
+                },
+                Event::KeyUp{keycode: Some(key), ..} => {
+                    if let Some(k) = key_to_button(key) {
+                        keys[k] = false;
+                    }
+                    if let Some(k) = key_to_second_button(key) {
+                        self.second_keys[k] = false;
+                    }
+                },
+                _ => ()
+            }
+        }
+        Control::Continue
+    }
+
+    fn poll_second(&mut self, keys: &mut [bool; NUM_KEYS]) {
+        *keys = self.second_keys;
+    }
+}
+
 fn main() {
-    let args: Vec<_> = env::args().collect();
-    if args.len() != 2 {
-        println!("usage: cargo run path/to/game");
-        return;
+    let mut gdb_port: Option<u16> = None;
+    let mut cheat_dir = None;
+    let mut start_address: Option<u16> = None;
+    let mut cdp1802 = false;
+    let mut chip8x = false;
+    let mut trace_path = None;
+    let mut trace_format = TraceFormat::Text;
+    let mut interpreter_path = None;
+    let mut rom_path = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--gdb" => {
+                gdb_port = args.next().and_then(|port| port.parse().ok());
+                if gdb_port.is_none() {
+                    println!("--gdb needs a port number");
+                    return;
+                }
+            },
+            "--cheats" => {
+                cheat_dir = args.next();
+                if cheat_dir.is_none() {
+                    println!("--cheats needs a directory");
+                    return;
+                }
+            },
+            "--start" => {
+                start_address = args.next().and_then(|address| {
+                    let hex = address.trim_start_matches("0x");
+                    u16::from_str_radix(hex, 16).ok()
+                });
+                if start_address.is_none() {
+                    println!("--start needs a hex address, e.g. 600 for ETI 660 programs");
+                    return;
+                }
+            },
+            "--cdp1802" => cdp1802 = true,
+            "--chip8x" => chip8x = true,
+            "--trace" => {
+                trace_path = args.next();
+                if trace_path.is_none() {
+                    println!("--trace needs a file to write");
+                    return;
+                }
+            },
+            "--binary" => trace_format = TraceFormat::Binary,
+            "--vip" => {
+                interpreter_path = args.next();
+                if interpreter_path.is_none() {
+                    println!("--vip needs the interpreter image");
+                    return;
+                }
+            },
+            _ => rom_path = Some(arg),
+        }
     }
+    let rom_path = match rom_path {
+        Some(path) => path,
+        None => {
+            println!("usage: cargo run [--gdb port] [--cheats dir] [--start address] [--cdp1802] [--chip8x] [--trace file [--binary]] [--vip interpreter] path/to/game");
+            return;
+        }
+    };
 
     // Setup SDL
     let sdl_context = sdl2::init().unwrap();
@@ -80,64 +234,185 @@ fn main() {
         }
     }).unwrap();
 
-    let mut previous_sound = false;
-    let mut sound: bool;
+    let event_pump = sdl_context.event_pump().unwrap();
+    let (cheat_commands, cheat_receiver) = mpsc::channel();
+    let input = SdlInput { event_pump, cheat_commands, second_keys: [false; NUM_KEYS] };
+
+    // The whole VIP runs at its own speed, without the debugger, cheats or
+    // tracing
+    if let Some(path) = interpreter_path {
+        let interpreter = std::fs::read(path).expect("Unable to open interpreter");
+        let mut vip = match Vip::new(&interpreter) {
+            Ok(vip) => vip,
+            Err(error) => {
+                println!("{}", error);
+                return;
+            }
+        };
+        let rom = std::fs::read(&rom_path).expect("Unable to open file");
+        if let Err(error) = vip.try_load(&rom) {
+            println!("{}", error);
+            return;
+        }
 
-    let mut event_pump = sdl_context.event_pump().unwrap();
+        drop(cheat_receiver);
+        let mut machine = Machine::new(vip,
+                                       SdlVideo { canvas },
+                                       SdlAudio { device },
+                                       input);
+        if let Err(error) = machine.run() {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+        return;
+    }
 
     let mut chip8 = Processor::new();
+    chip8.set_cdp1802(cdp1802);
+    // CHIP-8X moves the start to 0x300, --start still wins
+    chip8.set_chip8x(chip8x);
+    if let Some(address) = start_address {
+        chip8.set_start_address(address);
+    }
+    // every instruction from the first, written as the game runs
+    if let Some(path) = &trace_path {
+        let log = TraceLog::create(path, trace_format).expect("Unable to create trace");
+        chip8.set_tracer(Box::new(log));
+    }
 
-    let mut rom = File::open(&args[1]).expect("Unable to open file");
-    let mut buffer = Vec::new();
+    // a manifest loads several files, cheats go by the first one
+    let buffer = if rom_path.ends_with(".manifest") {
+        let manifest = Manifest::load(&rom_path).expect("Unable to load manifest");
+        if let Err(error) = manifest.apply(&mut chip8) {
+            println!("{}", error);
+            return;
+        }
+        manifest.segments[0].data.clone()
+    } else {
+        let mut rom = File::open(&rom_path).expect("Unable to open file");
+        let mut buffer = Vec::new();
 
-    rom.read_to_end(&mut buffer).unwrap();
-    chip8.load(&buffer);
+        rom.read_to_end(&mut buffer).unwrap();
+        if let Err(error) = chip8.try_load(&buffer) {
+            println!("{}", error);
+            return;
+        }
+        buffer
+    };
 
-    'gameloop: loop {
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit{..} => {
-                    break 'gameloop;
-                },
-                Event::KeyDown{keycode: Some(key), ..} => {
-                    if let Some(k) = key_to_button(key) {
-                        chip8.keypress(k, true);
-                    }
+    let mut cheats = match &cheat_dir {
+        Some(dir) => CheatList::load_for_rom(dir, &buffer).expect("Unable to load cheats"),
+        None => CheatList::new(),
+    };
+
+    // Debugger connects before the first instruction runs
+    let mut gdb = gdb_port.map(|port| {
+        println!("waiting for gdb on port {}", port);
+        GdbStub::listen(port).expect("Unable to start gdb server")
+    });
+
+    let mut search = None;
+
+    let mut machine = Machine::new(chip8,
+                                   SdlVideo { canvas },
+                                   SdlAudio { device },
+                                   input)
+        .with_ticks_per_frame(TICKS_PER_FRAME);
+
+    let result = machine.run_with(|chip8, ticks| {
+        // timers stay frozen while the debugger has the target halted
+        let running = match gdb.as_mut() {
+            Some(stub) => match stub.run_frame(chip8, ticks) {
+                Ok(Status::Running) => true,
+                Ok(Status::Halted) => false,
+                Ok(Status::Detached) | Err(_) => {
+                    gdb = None;
+                    true
                 },
-                Event::KeyUp{keycode: Some(key), ..} => {
-                    if let Some(k) = key_to_button(key) {
-                        chip8.keypress(k, false);
-                    }
+            },
+            None => {
+                chip8.run(ticks)?;
+                true
+            },
+        };
+        run_cheat_commands(&cheat_receiver, chip8, &mut search, &mut cheats,
+                           cheat_dir.as_deref(), &buffer);
+        cheats.apply(chip8);
+        Ok(running)
+    });
+
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
+
+fn run_cheat_commands(commands: &Receiver<CheatCommand>,
+                      chip8: &Processor,
+                      search: &mut Option<MemorySearch>,
+                      cheats: &mut CheatList,
+                      cheat_dir: Option<&str>,
+                      rom: &[u8]) {
+    for command in commands.try_iter() {
+        match (command, search.as_mut()) {
+            (CheatCommand::Search, _) => {
+                *search = Some(MemorySearch::new(chip8));
+                println!("searching {} addresses", chip8.ram().len());
+            },
+            (CheatCommand::Narrow(comparison), Some(search)) => {
+                search.narrow(chip8, comparison);
+                print_candidates(search, chip8);
+            },
+            (CheatCommand::Freeze, Some(search)) => {
+                let candidates = search.candidates();
+                if candidates.len() > SHOWN_CANDIDATES {
+                    println!("{} candidates left, narrow the search first", candidates.len());
+                    continue;
+                }
+                for &address in candidates {
+                    let value = chip8.ram()[address];
+                    cheats.cheats.push(Cheat::Freeze { address: address as u16, value });
+                    println!("freezing {:#05X} at {:#04X}", address, value);
+                }
+            },
+            (CheatCommand::Narrow(_), None) | (CheatCommand::Freeze, None) => {
+                println!("no search running, press F1 to start one");
+            },
+            (CheatCommand::Save, _) => match cheat_dir {
+                Some(dir) => match cheats.save_for_rom(dir, rom) {
+                    Ok(()) => println!("saved {} cheats", cheats.cheats.len()),
+                    Err(error) => println!("unable to save cheats: {}", error),
                 },
-                _ => ()
-            }
+                None => println!("--cheats needs a directory to save to"),
+            },
         }
+    }
+}
 
-        for _ in 0..TICKS_PER_FRAME {
-            chip8.tick();
+fn print_candidates(search: &MemorySearch, chip8: &Processor) {
+    let candidates = search.candidates();
+    println!("{} candidates", candidates.len());
+    if candidates.len() <= SHOWN_CANDIDATES {
+        for &address in candidates {
+            println!("  {:#05X} = {:#04X}", address, chip8.ram()[address]);
         }
-        chip8.tick_timers();
-        draw_screen(&chip8, &mut canvas);
-        sound = chip8.get_sound();
-        if sound && !previous_sound {
-            device.resume();
-        } else if !sound && previous_sound {
-            device.pause();
-        }
-        previous_sound = sound;
     }
 }
 
-fn draw_screen(processor: &Processor, canvas: &mut Canvas<Window>) {
-    // Clear canvas as black
-    canvas.set_draw_color(Color::RGB(0, 0, 0));
+// White on black, unless CHIP-8X colors are given
+fn draw_screen(screen_buffer: &[bool], colors: Option<&Chip8x>, canvas: &mut Canvas<Window>) {
+    let rgb = |[r, g, b]: [u8; 3]| Color::RGB(r, g, b);
+    // Clear canvas with the background
+    let background = colors.map_or([0, 0, 0], |colors| colors.background().rgb());
+    canvas.set_draw_color(rgb(background));
     canvas.clear();
-    let screen_buffer = processor.get_display();
-    // Now set draw color to white, iterate through each point and
-    // see if it should be drawn
+    // Draw the lit pixels in white, or in their zone's color with CHIP-8X
     canvas.set_draw_color(Color::RGB(255, 255, 255));
     for (i, pixel) in screen_buffer.iter().enumerate() {
         if *pixel {
+            if let Some(colors) = colors {
+                canvas.set_draw_color(rgb(colors.pixel(i, true).rgb()));
+            }
             // Convert our 1D array's index into a 2D (x,y) position
             let x = (i % SCREEN_WIDTH) as u32;
             let y = (i / SCREEN_WIDTH) as u32;
@@ -152,6 +427,19 @@ fn draw_screen(processor: &Processor, canvas: &mut Canvas<Window>) {
     canvas.present();
 }
 
+fn key_to_cheat_command(key: Keycode) -> Option<CheatCommand> {
+    match key {
+        Keycode::F1 => Some(CheatCommand::Search),
+        Keycode::F2 => Some(CheatCommand::Narrow(Comparison::Decreased)),
+        Keycode::F3 => Some(CheatCommand::Narrow(Comparison::Increased)),
+        Keycode::F4 => Some(CheatCommand::Narrow(Comparison::Changed)),
+        Keycode::F5 => Some(CheatCommand::Narrow(Comparison::Unchanged)),
+        Keycode::F6 => Some(CheatCommand::Freeze),
+        Keycode::F7 => Some(CheatCommand::Save),
+        _ => None,
+    }
+}
+
 fn key_to_button(key: Keycode) -> Option<usize> {
     match key {
         Keycode::Num1 => Some(0x1),
@@ -173,3 +461,27 @@ fn key_to_button(key: Keycode) -> Option<usize> {
         _ => None,
     }
 }
+
+// CHIP-8X's second keypad: digits as themselves, then / * - + Enter .
+// for A to F
+fn key_to_second_button(key: Keycode) -> Option<usize> {
+    match key {
+        Keycode::Kp0 => Some(0x0),
+        Keycode::Kp1 => Some(0x1),
+        Keycode::Kp2 => Some(0x2),
+        Keycode::Kp3 => Some(0x3),
+        Keycode::Kp4 => Some(0x4),
+        Keycode::Kp5 => Some(0x5),
+        Keycode::Kp6 => Some(0x6),
+        Keycode::Kp7 => Some(0x7),
+        Keycode::Kp8 => Some(0x8),
+        Keycode::Kp9 => Some(0x9),
+        Keycode::KpDivide => Some(0xA),
+        Keycode::KpMultiply => Some(0xB),
+        Keycode::KpMinus => Some(0xC),
+        Keycode::KpPlus => Some(0xD),
+        Keycode::KpEnter => Some(0xE),
+        Keycode::KpPeriod => Some(0xF),
+        _ => None,
+    }
+}
@@ -0,0 +1,170 @@
+/* Generated by cbindgen from ffi/src/lib.rs, don't edit by hand. */
+
+#ifndef CHIP8_H
+#define CHIP8_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+/*
+ Result of every fallible call.
+ */
+typedef enum Chip8Error {
+  CHIP8_ERROR_OK = 0,
+  CHIP8_ERROR_NULL_POINTER,
+  CHIP8_ERROR_ROM_TOO_LARGE,
+  CHIP8_ERROR_INVALID_OPCODE,
+  CHIP8_ERROR_STACK_OVERFLOW,
+  CHIP8_ERROR_STACK_UNDERFLOW,
+  CHIP8_ERROR_PC_OUT_OF_BOUNDS,
+  CHIP8_ERROR_MEMORY_OUT_OF_BOUNDS,
+  CHIP8_ERROR_INVALID_KEY,
+  CHIP8_ERROR_BUFFER_TOO_SMALL,
+  CHIP8_ERROR_INVALID_STATE,
+  CHIP8_ERROR_PANIC,
+  CHIP8_ERROR_WRITE_PROTECTED,
+  CHIP8_ERROR_MACHINE_CODE_TIMEOUT,
+} Chip8Error;
+
+/*
+ Opaque emulator handle.
+ */
+typedef struct Chip8 Chip8;
+
+/*
+ Creates an emulator with a randomly seeded `CXNN`. Free it with
+ `chip8_destroy`.
+ */
+struct Chip8 *chip8_create(void);
+
+/*
+ Creates an emulator whose `CXNN` results depend only on `seed`.
+ */
+struct Chip8 *chip8_create_seeded(uint64_t seed);
+
+/*
+ # Safety
+ `chip8` must be null or come from `chip8_create`, and not be used again.
+ */
+void chip8_destroy(struct Chip8 *chip8);
+
+/*
+ Clears RAM, registers and the screen. The ROM has to be loaded again.
+
+ # Safety
+ `chip8` must be null or a live handle.
+ */
+enum Chip8Error chip8_reset(struct Chip8 *chip8);
+
+/*
+ Copies `len` bytes of ROM to the program start address.
+
+ # Safety
+ `chip8` must be null or a live handle, `data` must point to `len` bytes.
+ */
+enum Chip8Error chip8_load_rom(struct Chip8 *chip8, const uint8_t *data, size_t len);
+
+/*
+ Copies `len` bytes to `address` without moving the program counter,
+ e.g. for data a program expects at a fixed address.
+
+ # Safety
+ `chip8` must be null or a live handle, `data` must point to `len` bytes.
+ */
+enum Chip8Error chip8_load_at(struct Chip8 *chip8,
+                              uint16_t address,
+                              const uint8_t *data,
+                              size_t len);
+
+/*
+ Sets where `chip8_load_rom` copies ROMs and the program counter
+ starts, 0x200 by default and 0x600 for ETI 660 programs. Also moves
+ the program counter there.
+
+ # Safety
+ `chip8` must be null or a live handle.
+ */
+enum Chip8Error chip8_set_start_address(struct Chip8 *chip8, uint16_t address);
+
+/*
+ Executes a single instruction.
+
+ # Safety
+ `chip8` must be null or a live handle.
+ */
+enum Chip8Error chip8_step(struct Chip8 *chip8);
+
+/*
+ Executes up to `count` instructions, stopping at the first error.
+
+ # Safety
+ `chip8` must be null or a live handle.
+ */
+enum Chip8Error chip8_tick(struct Chip8 *chip8, size_t count);
+
+/*
+ Counts the delay and sound timers down, call at 60 Hz.
+
+ # Safety
+ `chip8` must be null or a live handle.
+ */
+enum Chip8Error chip8_tick_timers(struct Chip8 *chip8);
+
+/*
+ Presses or releases key 0x0-0xF.
+
+ # Safety
+ `chip8` must be null or a live handle.
+ */
+enum Chip8Error chip8_set_key(struct Chip8 *chip8, uint8_t key, bool pressed);
+
+/*
+ Row-major framebuffer of `chip8_framebuffer_width() *
+ chip8_framebuffer_height()` bytes, 1 for a lit pixel and 0 otherwise.
+ Valid until the next call that runs instructions.
+
+ # Safety
+ `chip8` must be null or a live handle.
+ */
+const uint8_t *chip8_framebuffer(const struct Chip8 *chip8);
+
+size_t chip8_framebuffer_width(void);
+
+size_t chip8_framebuffer_height(void);
+
+/*
+ Whether the beeper is on.
+
+ # Safety
+ `chip8` must be null or a live handle.
+ */
+bool chip8_sound(const struct Chip8 *chip8);
+
+/*
+ Size of the buffer `chip8_save_state` needs.
+ */
+size_t chip8_state_size(void);
+
+/*
+ # Safety
+ `chip8` must be null or a live handle, `buffer` must point to `len`
+ writable bytes.
+ */
+enum Chip8Error chip8_save_state(struct Chip8 *chip8, uint8_t *buffer, size_t len);
+
+/*
+ Leaves the emulator untouched if the state is rejected.
+
+ # Safety
+ `chip8` must be null or a live handle, `buffer` must point to `len`
+ bytes.
+ */
+enum Chip8Error chip8_load_state(struct Chip8 *chip8, const uint8_t *buffer, size_t len);
+
+/*
+ Static, human readable description of an error.
+ */
+const char *chip8_error_message(enum Chip8Error error);
+
+#endif  /* CHIP8_H */
@@ -0,0 +1,342 @@
+//! C API for embedding the emulator.
+//!
+//! Every function that can fail returns a `Chip8Error`, and no Rust panic
+//! ever crosses the boundary: unexpected panics come back as
+//! `CHIP8_ERROR_PANIC`. The header is generated into `include/chip8.h` by
+//! the build script.
+
+use std::ffi::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use backend::display_constants::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use backend::{ErrorKind, Processor, StateError, STATE_SIZE};
+
+/// Opaque emulator handle.
+pub struct Chip8 {
+    processor: Processor,
+}
+
+/// Result of every fallible call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Error {
+    Ok = 0,
+    NullPointer,
+    RomTooLarge,
+    InvalidOpcode,
+    StackOverflow,
+    StackUnderflow,
+    PcOutOfBounds,
+    MemoryOutOfBounds,
+    InvalidKey,
+    BufferTooSmall,
+    InvalidState,
+    Panic,
+    WriteProtected,
+    MachineCodeTimeout,
+}
+
+impl From<ErrorKind> for Chip8Error {
+    fn from(kind: ErrorKind) -> Self {
+        match kind {
+            ErrorKind::InvalidOpcode => Chip8Error::InvalidOpcode,
+            ErrorKind::StackOverflow => Chip8Error::StackOverflow,
+            ErrorKind::StackUnderflow => Chip8Error::StackUnderflow,
+            ErrorKind::PcOutOfBounds => Chip8Error::PcOutOfBounds,
+            ErrorKind::MemoryOutOfBounds(_) => Chip8Error::MemoryOutOfBounds,
+            ErrorKind::InvalidKey(_) => Chip8Error::InvalidKey,
+            ErrorKind::WriteProtected(_) => Chip8Error::WriteProtected,
+            ErrorKind::MachineCodeTimeout(_) => Chip8Error::MachineCodeTimeout,
+        }
+    }
+}
+
+impl From<StateError> for Chip8Error {
+    fn from(_: StateError) -> Self {
+        Chip8Error::InvalidState
+    }
+}
+
+// Runs `f` on the handle, turning null pointers and panics into errors
+unsafe fn with_chip8<F>(chip8: *mut Chip8, f: F) -> Chip8Error
+where
+    F: FnOnce(&mut Chip8) -> Result<(), Chip8Error>,
+{
+    let chip8 = match chip8.as_mut() {
+        Some(chip8) => chip8,
+        None => return Chip8Error::NullPointer,
+    };
+    match panic::catch_unwind(AssertUnwindSafe(|| f(chip8))) {
+        Ok(Ok(())) => Chip8Error::Ok,
+        Ok(Err(error)) => error,
+        Err(_) => Chip8Error::Panic,
+    }
+}
+
+/// Creates an emulator with a randomly seeded `CXNN`. Free it with
+/// `chip8_destroy`.
+#[no_mangle]
+pub extern "C" fn chip8_create() -> *mut Chip8 {
+    panic::catch_unwind(|| {
+        Box::into_raw(Box::new(Chip8 {
+            processor: Processor::new(),
+        }))
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Creates an emulator whose `CXNN` results depend only on `seed`.
+#[no_mangle]
+pub extern "C" fn chip8_create_seeded(seed: u64) -> *mut Chip8 {
+    panic::catch_unwind(|| {
+        Box::into_raw(Box::new(Chip8 {
+            processor: Processor::with_seed(seed),
+        }))
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// # Safety
+/// `chip8` must be null or come from `chip8_create`, and not be used again.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_destroy(chip8: *mut Chip8) {
+    if !chip8.is_null() {
+        drop(Box::from_raw(chip8));
+    }
+}
+
+/// Clears RAM, registers and the screen. The ROM has to be loaded again.
+///
+/// # Safety
+/// `chip8` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_reset(chip8: *mut Chip8) -> Chip8Error {
+    with_chip8(chip8, |chip8| {
+        chip8.processor.reset();
+        Ok(())
+    })
+}
+
+/// Copies `len` bytes of ROM to the program start address.
+///
+/// # Safety
+/// `chip8` must be null or a live handle, `data` must point to `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_rom(
+    chip8: *mut Chip8,
+    data: *const u8,
+    len: usize,
+) -> Chip8Error {
+    if data.is_null() {
+        return Chip8Error::NullPointer;
+    }
+    let rom = slice::from_raw_parts(data, len);
+    with_chip8(chip8, |chip8| {
+        chip8
+            .processor
+            .try_load(rom)
+            .map_err(|_| Chip8Error::RomTooLarge)
+    })
+}
+
+/// Copies `len` bytes to `address` without moving the program counter,
+/// e.g. for data a program expects at a fixed address.
+///
+/// # Safety
+/// `chip8` must be null or a live handle, `data` must point to `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_at(
+    chip8: *mut Chip8,
+    address: u16,
+    data: *const u8,
+    len: usize,
+) -> Chip8Error {
+    if data.is_null() {
+        return Chip8Error::NullPointer;
+    }
+    let data = slice::from_raw_parts(data, len);
+    with_chip8(chip8, |chip8| {
+        chip8
+            .processor
+            .load_at(address, data)
+            .map_err(|_| Chip8Error::RomTooLarge)
+    })
+}
+
+/// Sets where `chip8_load_rom` copies ROMs and the program counter
+/// starts, 0x200 by default and 0x600 for ETI 660 programs. Also moves
+/// the program counter there.
+///
+/// # Safety
+/// `chip8` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_start_address(chip8: *mut Chip8, address: u16) -> Chip8Error {
+    with_chip8(chip8, |chip8| {
+        chip8.processor.set_start_address(address);
+        Ok(())
+    })
+}
+
+/// Executes a single instruction.
+///
+/// # Safety
+/// `chip8` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_step(chip8: *mut Chip8) -> Chip8Error {
+    with_chip8(chip8, |chip8| {
+        chip8
+            .processor
+            .try_tick()
+            .map_err(|error| error.kind.into())
+    })
+}
+
+/// Executes up to `count` instructions, stopping at the first error.
+///
+/// # Safety
+/// `chip8` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_tick(chip8: *mut Chip8, count: usize) -> Chip8Error {
+    with_chip8(chip8, |chip8| {
+        for _ in 0..count {
+            chip8
+                .processor
+                .try_tick()
+                .map_err(|error| Chip8Error::from(error.kind))?;
+        }
+        Ok(())
+    })
+}
+
+/// Counts the delay and sound timers down, call at 60 Hz.
+///
+/// # Safety
+/// `chip8` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_tick_timers(chip8: *mut Chip8) -> Chip8Error {
+    with_chip8(chip8, |chip8| {
+        chip8.processor.tick_timers();
+        Ok(())
+    })
+}
+
+/// Presses or releases key 0x0-0xF.
+///
+/// # Safety
+/// `chip8` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_key(chip8: *mut Chip8, key: u8, pressed: bool) -> Chip8Error {
+    with_chip8(chip8, |chip8| {
+        if key > 0xF {
+            return Err(Chip8Error::InvalidKey);
+        }
+        chip8.processor.keypress(key as usize, pressed);
+        Ok(())
+    })
+}
+
+/// Row-major framebuffer of `chip8_framebuffer_width() *
+/// chip8_framebuffer_height()` bytes, 1 for a lit pixel and 0 otherwise.
+/// Valid until the next call that runs instructions.
+///
+/// # Safety
+/// `chip8` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(chip8: *const Chip8) -> *const u8 {
+    match chip8.as_ref() {
+        Some(chip8) => chip8.processor.get_display().as_ptr() as *const u8,
+        None => ptr::null(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn chip8_framebuffer_width() -> usize {
+    SCREEN_WIDTH
+}
+
+#[no_mangle]
+pub extern "C" fn chip8_framebuffer_height() -> usize {
+    SCREEN_HEIGHT
+}
+
+/// Whether the beeper is on.
+///
+/// # Safety
+/// `chip8` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_sound(chip8: *const Chip8) -> bool {
+    match chip8.as_ref() {
+        Some(chip8) => chip8.processor.get_sound(),
+        None => false,
+    }
+}
+
+/// Size of the buffer `chip8_save_state` needs.
+#[no_mangle]
+pub extern "C" fn chip8_state_size() -> usize {
+    STATE_SIZE
+}
+
+/// # Safety
+/// `chip8` must be null or a live handle, `buffer` must point to `len`
+/// writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_save_state(
+    chip8: *mut Chip8,
+    buffer: *mut u8,
+    len: usize,
+) -> Chip8Error {
+    if buffer.is_null() {
+        return Chip8Error::NullPointer;
+    }
+    if len < STATE_SIZE {
+        return Chip8Error::BufferTooSmall;
+    }
+    let buffer = slice::from_raw_parts_mut(buffer, STATE_SIZE);
+    with_chip8(chip8, |chip8| {
+        buffer.copy_from_slice(&chip8.processor.save_state());
+        Ok(())
+    })
+}
+
+/// Leaves the emulator untouched if the state is rejected.
+///
+/// # Safety
+/// `chip8` must be null or a live handle, `buffer` must point to `len`
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_state(
+    chip8: *mut Chip8,
+    buffer: *const u8,
+    len: usize,
+) -> Chip8Error {
+    if buffer.is_null() {
+        return Chip8Error::NullPointer;
+    }
+    let state = slice::from_raw_parts(buffer, len);
+    with_chip8(chip8, |chip8| Ok(chip8.processor.load_state(state)?))
+}
+
+/// Static, human readable description of an error.
+#[no_mangle]
+pub extern "C" fn chip8_error_message(error: Chip8Error) -> *const c_char {
+    let message: &'static std::ffi::CStr = match error {
+        Chip8Error::Ok => c"ok",
+        Chip8Error::NullPointer => c"null pointer",
+        Chip8Error::RomTooLarge => c"ROM doesn't fit in memory",
+        Chip8Error::InvalidOpcode => c"invalid opcode",
+        Chip8Error::StackOverflow => c"stack overflow",
+        Chip8Error::StackUnderflow => c"stack underflow",
+        Chip8Error::PcOutOfBounds => c"program counter out of bounds",
+        Chip8Error::MemoryOutOfBounds => c"memory access out of bounds",
+        Chip8Error::InvalidKey => c"invalid key index",
+        Chip8Error::BufferTooSmall => c"buffer too small",
+        Chip8Error::InvalidState => c"invalid save state",
+        Chip8Error::Panic => c"internal error",
+        Chip8Error::WriteProtected => c"write to protected memory",
+        Chip8Error::MachineCodeTimeout => c"machine code didn't return",
+    };
+    message.as_ptr()
+}
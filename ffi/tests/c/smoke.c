@@ -0,0 +1,81 @@
+/* Exercises the C API end to end, exits non-zero on the first failure. */
+
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "chip8.h"
+
+#define CHECK(cond)                                                   \
+    do {                                                              \
+        if (!(cond)) {                                                \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__,    \
+                    __LINE__, #cond);                                 \
+            return 1;                                                 \
+        }                                                             \
+    } while (0)
+
+int main(void) {
+    /* draw the font sprite for 0 at (0, 0), then loop forever */
+    const uint8_t rom[] = {0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06};
+    /* 0x0001 isn't a valid instruction */
+    const uint8_t bad_rom[] = {0x00, 0x01};
+
+    Chip8 *chip8 = chip8_create_seeded(1);
+    CHECK(chip8 != NULL);
+
+    CHECK(chip8_load_rom(chip8, rom, sizeof rom) == CHIP8_ERROR_OK);
+    CHECK(chip8_tick(chip8, 10) == CHIP8_ERROR_OK);
+    CHECK(chip8_tick_timers(chip8) == CHIP8_ERROR_OK);
+
+    const uint8_t *screen = chip8_framebuffer(chip8);
+    size_t width = chip8_framebuffer_width();
+    CHECK(screen != NULL);
+    CHECK(width == 64 && chip8_framebuffer_height() == 32);
+    /* top row of the 0 glyph is 0xF0 */
+    CHECK(screen[0] == 1 && screen[3] == 1 && screen[4] == 0);
+    /* second row is 0x90 */
+    CHECK(screen[width] == 1 && screen[width + 1] == 0);
+    CHECK(!chip8_sound(chip8));
+
+    CHECK(chip8_set_key(chip8, 0xF, true) == CHIP8_ERROR_OK);
+    CHECK(chip8_set_key(chip8, 0x10, true) == CHIP8_ERROR_INVALID_KEY);
+
+    size_t state_size = chip8_state_size();
+    uint8_t *state = malloc(state_size);
+    CHECK(state != NULL);
+    CHECK(chip8_save_state(chip8, state, state_size - 1) == CHIP8_ERROR_BUFFER_TOO_SMALL);
+    CHECK(chip8_save_state(chip8, state, state_size) == CHIP8_ERROR_OK);
+
+    CHECK(chip8_reset(chip8) == CHIP8_ERROR_OK);
+    CHECK(chip8_framebuffer(chip8)[0] == 0);
+    CHECK(chip8_load_state(chip8, state, state_size) == CHIP8_ERROR_OK);
+    CHECK(chip8_framebuffer(chip8)[0] == 1);
+    CHECK(chip8_load_state(chip8, state, 3) == CHIP8_ERROR_INVALID_STATE);
+    free(state);
+
+    CHECK(chip8_reset(chip8) == CHIP8_ERROR_OK);
+    CHECK(chip8_load_rom(chip8, bad_rom, sizeof bad_rom) == CHIP8_ERROR_OK);
+    CHECK(chip8_step(chip8) == CHIP8_ERROR_INVALID_OPCODE);
+    CHECK(chip8_error_message(CHIP8_ERROR_INVALID_OPCODE) != NULL);
+
+    uint8_t big_rom[4096] = {0};
+    CHECK(chip8_load_rom(chip8, big_rom, sizeof big_rom) == CHIP8_ERROR_ROM_TOO_LARGE);
+
+    /* ETI 660 programs load and start at 0x600 */
+    CHECK(chip8_reset(chip8) == CHIP8_ERROR_OK);
+    CHECK(chip8_set_start_address(chip8, 0x600) == CHIP8_ERROR_OK);
+    CHECK(chip8_load_rom(chip8, rom, 6) == CHIP8_ERROR_OK);
+    CHECK(chip8_tick(chip8, 3) == CHIP8_ERROR_OK);
+    CHECK(screen[0] == 1);
+    CHECK(chip8_load_rom(chip8, big_rom, 0xA01) == CHIP8_ERROR_ROM_TOO_LARGE);
+    CHECK(chip8_load_at(chip8, 0xFFF, rom, 2) == CHIP8_ERROR_ROM_TOO_LARGE);
+    CHECK(chip8_load_at(chip8, 0xFFF, rom, 1) == CHIP8_ERROR_OK);
+
+    CHECK(chip8_step(NULL) == CHIP8_ERROR_NULL_POINTER);
+
+    chip8_destroy(chip8);
+    chip8_destroy(NULL);
+
+    printf("ok\n");
+    return 0;
+}
@@ -0,0 +1,39 @@
+// Compiles tests/c/smoke.c against the generated header and the static
+// library, then runs it
+
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn c_program_runs() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    // test binaries live in target/<profile>/deps, the library one level up
+    let exe = env::current_exe().unwrap();
+    let profile_dir = exe.parent().unwrap().parent().unwrap();
+    let library = profile_dir.join("libchip8.a");
+    let output = profile_dir.join("c_smoke_test");
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+
+    let status = Command::new(compiler)
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg(manifest_dir.join("tests/c/smoke.c"))
+        .arg(&library)
+        .args(["-lpthread", "-ldl", "-lm"])
+        .arg("-o")
+        .arg(&output)
+        .status()
+        .expect("Unable to run the C compiler");
+    assert!(status.success(), "C test program failed to compile");
+
+    let result = Command::new(&output).output().unwrap();
+    assert!(
+        result.status.success(),
+        "C test program failed: {}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+}
@@ -0,0 +1,126 @@
+// Minimal libretro host for trying the core without RetroArch:
+//
+//     cargo build && cargo run --example host -- target/debug/libchip8_libretro.so game.ch8 120
+//
+// Runs the given number of frames with no input, round-trips a save
+// state and prints the last frame as ASCII.
+
+use std::env;
+use std::ffi::c_void;
+use std::fs;
+use std::sync::Mutex;
+
+use libloading::{Library, Symbol};
+
+#[repr(C)]
+struct RetroGameInfo {
+    path: *const i8,
+    data: *const c_void,
+    size: usize,
+    meta: *const i8,
+}
+
+static FRAME: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+static FRAME_WIDTH: Mutex<usize> = Mutex::new(0);
+
+extern "C" fn environment(_cmd: u32, _data: *mut c_void) -> bool {
+    true
+}
+
+extern "C" fn video_refresh(data: *const c_void, width: u32, height: u32, pitch: usize) {
+    let pixels = unsafe { std::slice::from_raw_parts(data as *const u8, pitch * height as usize) };
+    let mut frame = FRAME.lock().unwrap();
+    frame.clear();
+    for row in pixels.chunks(pitch) {
+        for pixel in row[..width as usize * 4].chunks(4) {
+            frame.push(u32::from_ne_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]));
+        }
+    }
+    *FRAME_WIDTH.lock().unwrap() = width as usize;
+}
+
+extern "C" fn audio_sample_batch(_data: *const i16, frames: usize) -> usize {
+    frames
+}
+
+extern "C" fn input_poll() {}
+
+extern "C" fn input_state(_port: u32, _device: u32, _index: u32, _id: u32) -> i16 {
+    0
+}
+
+fn main() {
+    let args: Vec<_> = env::args().collect();
+    if args.len() != 4 {
+        println!("usage: host path/to/core.so path/to/game frames");
+        return;
+    }
+    let rom = fs::read(&args[2]).expect("Unable to open file");
+    let frames: usize = args[3].parse().expect("Invalid frame count");
+
+    unsafe {
+        let core = Library::new(&args[1]).expect("Unable to load core");
+
+        let set_environment: Symbol<extern "C" fn(extern "C" fn(u32, *mut c_void) -> bool)> =
+            core.get(b"retro_set_environment").unwrap();
+        let set_video_refresh: Symbol<
+            extern "C" fn(extern "C" fn(*const c_void, u32, u32, usize)),
+        > = core.get(b"retro_set_video_refresh").unwrap();
+        let set_audio_sample_batch: Symbol<
+            extern "C" fn(extern "C" fn(*const i16, usize) -> usize),
+        > = core.get(b"retro_set_audio_sample_batch").unwrap();
+        let set_input_poll: Symbol<extern "C" fn(extern "C" fn())> =
+            core.get(b"retro_set_input_poll").unwrap();
+        let set_input_state: Symbol<extern "C" fn(extern "C" fn(u32, u32, u32, u32) -> i16)> =
+            core.get(b"retro_set_input_state").unwrap();
+        let init: Symbol<extern "C" fn()> = core.get(b"retro_init").unwrap();
+        let load_game: Symbol<unsafe extern "C" fn(*const RetroGameInfo) -> bool> =
+            core.get(b"retro_load_game").unwrap();
+        let run: Symbol<extern "C" fn()> = core.get(b"retro_run").unwrap();
+        let serialize_size: Symbol<extern "C" fn() -> usize> =
+            core.get(b"retro_serialize_size").unwrap();
+        let serialize: Symbol<unsafe extern "C" fn(*mut c_void, usize) -> bool> =
+            core.get(b"retro_serialize").unwrap();
+        let unserialize: Symbol<unsafe extern "C" fn(*const c_void, usize) -> bool> =
+            core.get(b"retro_unserialize").unwrap();
+        let unload_game: Symbol<extern "C" fn()> = core.get(b"retro_unload_game").unwrap();
+        let deinit: Symbol<extern "C" fn()> = core.get(b"retro_deinit").unwrap();
+
+        set_environment(environment);
+        set_video_refresh(video_refresh);
+        set_audio_sample_batch(audio_sample_batch);
+        set_input_poll(input_poll);
+        set_input_state(input_state);
+        init();
+
+        let game = RetroGameInfo {
+            path: std::ptr::null(),
+            data: rom.as_ptr() as *const c_void,
+            size: rom.len(),
+            meta: std::ptr::null(),
+        };
+        assert!(load_game(&game), "core rejected the game");
+
+        for _ in 0..frames {
+            run();
+        }
+
+        let mut state = vec![0u8; serialize_size()];
+        assert!(serialize(state.as_mut_ptr() as *mut c_void, state.len()));
+        assert!(unserialize(state.as_ptr() as *const c_void, state.len()));
+        println!("save state: {} bytes", state.len());
+
+        unload_game();
+        deinit();
+    }
+
+    let frame = FRAME.lock().unwrap();
+    let width = *FRAME_WIDTH.lock().unwrap();
+    for row in frame.chunks(width.max(1)) {
+        let line: String = row
+            .iter()
+            .map(|pixel| if *pixel & 0x00FF_FFFF != 0 { '#' } else { '.' })
+            .collect();
+        println!("{}", line);
+    }
+}
@@ -0,0 +1,393 @@
+// libretro core wrapping the backend's Processor
+//
+// Video is XRGB8888 at the native 64x32, audio is a 440 Hz square wave
+// while the beeper is on, and the 16 RetroPad buttons map onto the 16
+// CHIP-8 keys (the d-pad on 5/7/8/9, like WASD in the desktop frontend).
+
+use std::ffi::{c_char, c_void};
+use std::slice;
+use std::sync::Mutex;
+
+use backend::display_constants::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use backend::{Processor, STATE_SIZE};
+
+const RETRO_API_VERSION: u32 = 1;
+const RETRO_DEVICE_JOYPAD: u32 = 1;
+const RETRO_REGION_NTSC: u32 = 0;
+const RETRO_ENVIRONMENT_SET_PIXEL_FORMAT: u32 = 10;
+const RETRO_PIXEL_FORMAT_XRGB8888: u32 = 1;
+
+const TICKS_PER_FRAME: usize = 10;
+const FPS: f64 = 60.0;
+const SAMPLE_RATE: f64 = 44_100.0;
+const SAMPLES_PER_FRAME: usize = (SAMPLE_RATE / FPS) as usize;
+const TONE: f32 = 440.0;
+const VOLUME: i16 = i16::MAX / 4;
+
+const PIXEL_ON: u32 = 0x00FF_FFFF;
+const PIXEL_OFF: u32 = 0x0000_0000;
+
+// CHIP-8 key for each RetroPad button id: B, Y, Select, Start, Up, Down,
+// Left, Right, A, X, L, R, L2, R2, L3, R3
+const JOYPAD_KEYS: [usize; 16] = [
+    0x4, 0x1, 0x3, 0xC, 0x5, 0x8, 0x7, 0x9, 0x6, 0x2, 0xD, 0xE, 0xA, 0xB, 0x0, 0xF,
+];
+
+#[repr(C)]
+pub struct RetroSystemInfo {
+    library_name: *const c_char,
+    library_version: *const c_char,
+    valid_extensions: *const c_char,
+    need_fullpath: bool,
+    block_extract: bool,
+}
+
+#[repr(C)]
+pub struct RetroGameGeometry {
+    base_width: u32,
+    base_height: u32,
+    max_width: u32,
+    max_height: u32,
+    aspect_ratio: f32,
+}
+
+#[repr(C)]
+pub struct RetroSystemTiming {
+    fps: f64,
+    sample_rate: f64,
+}
+
+#[repr(C)]
+pub struct RetroSystemAvInfo {
+    geometry: RetroGameGeometry,
+    timing: RetroSystemTiming,
+}
+
+#[repr(C)]
+pub struct RetroGameInfo {
+    path: *const c_char,
+    data: *const c_void,
+    size: usize,
+    meta: *const c_char,
+}
+
+type EnvironmentFn = extern "C" fn(cmd: u32, data: *mut c_void) -> bool;
+type VideoRefreshFn = extern "C" fn(data: *const c_void, width: u32, height: u32, pitch: usize);
+type AudioSampleFn = extern "C" fn(left: i16, right: i16);
+type AudioSampleBatchFn = extern "C" fn(data: *const i16, frames: usize) -> usize;
+type InputPollFn = extern "C" fn();
+type InputStateFn = extern "C" fn(port: u32, device: u32, index: u32, id: u32) -> i16;
+
+struct Core {
+    processor: Processor,
+    rom: Vec<u8>,
+    crashed: bool, // stop running after the ROM hits an execution error
+    phase: f32,
+}
+
+struct Frontend {
+    environment: Option<EnvironmentFn>,
+    video_refresh: Option<VideoRefreshFn>,
+    audio_sample_batch: Option<AudioSampleBatchFn>,
+    input_poll: Option<InputPollFn>,
+    input_state: Option<InputStateFn>,
+    core: Option<Box<Core>>,
+}
+
+static FRONTEND: Mutex<Frontend> = Mutex::new(Frontend {
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+    core: None,
+});
+
+fn frontend() -> std::sync::MutexGuard<'static, Frontend> {
+    // a panic while holding the lock can't leave the callbacks half set
+    FRONTEND.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> u32 {
+    RETRO_API_VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_environment(callback: EnvironmentFn) {
+    frontend().environment = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(callback: VideoRefreshFn) {
+    frontend().video_refresh = Some(callback);
+}
+
+// Audio goes out in batches, single samples are never used
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_callback: AudioSampleFn) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(callback: AudioSampleBatchFn) {
+    frontend().audio_sample_batch = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(callback: InputPollFn) {
+    frontend().input_poll = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(callback: InputStateFn) {
+    frontend().input_state = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    frontend().core = None;
+}
+
+/// # Safety
+/// `info` must point to a writable retro_system_info
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut RetroSystemInfo) {
+    if info.is_null() {
+        return;
+    }
+    *info = RetroSystemInfo {
+        library_name: c"CHIP-8".as_ptr(),
+        library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char,
+        valid_extensions: c"ch8|c8|rom".as_ptr(),
+        need_fullpath: false,
+        block_extract: false,
+    };
+}
+
+/// # Safety
+/// `info` must point to a writable retro_system_av_info
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut RetroSystemAvInfo) {
+    if info.is_null() {
+        return;
+    }
+    *info = RetroSystemAvInfo {
+        geometry: RetroGameGeometry {
+            base_width: SCREEN_WIDTH as u32,
+            base_height: SCREEN_HEIGHT as u32,
+            max_width: SCREEN_WIDTH as u32,
+            max_height: SCREEN_HEIGHT as u32,
+            aspect_ratio: SCREEN_WIDTH as f32 / SCREEN_HEIGHT as f32,
+        },
+        timing: RetroSystemTiming {
+            fps: FPS,
+            sample_rate: SAMPLE_RATE,
+        },
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: u32, _device: u32) {}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    if let Some(core) = frontend().core.as_mut() {
+        core.processor.reset();
+        core.processor.load(&core.rom);
+        core.crashed = false;
+    }
+}
+
+// The lock is only held while the processor runs, never across a
+// callback, so the frontend can call back into the core from them
+#[no_mangle]
+pub extern "C" fn retro_run() {
+    let (input_poll, input_state, video_refresh, audio_sample_batch) = {
+        let frontend = frontend();
+        if frontend.core.is_none() {
+            return;
+        }
+        (
+            frontend.input_poll,
+            frontend.input_state,
+            frontend.video_refresh,
+            frontend.audio_sample_batch,
+        )
+    };
+
+    let mut keys = None;
+    if let (Some(input_poll), Some(input_state)) = (input_poll, input_state) {
+        input_poll();
+        let mut pressed = [false; JOYPAD_KEYS.len()];
+        for (id, button) in pressed.iter_mut().enumerate() {
+            *button = input_state(0, RETRO_DEVICE_JOYPAD, 0, id as u32) != 0;
+        }
+        keys = Some(pressed);
+    }
+
+    let mut frame = [PIXEL_OFF; SCREEN_WIDTH * SCREEN_HEIGHT];
+    let mut samples = [0; SAMPLES_PER_FRAME * 2];
+    {
+        let mut frontend = frontend();
+        // unloaded by one of the callbacks
+        let core = match frontend.core.as_mut() {
+            Some(core) => core,
+            None => return,
+        };
+
+        if let Some(pressed) = keys {
+            for (key, pressed) in JOYPAD_KEYS.iter().zip(pressed) {
+                core.processor.keypress(*key, pressed);
+            }
+        }
+
+        if !core.crashed {
+            for _ in 0..TICKS_PER_FRAME {
+                if core.processor.try_tick().is_err() {
+                    core.crashed = true;
+                    break;
+                }
+            }
+            core.processor.tick_timers();
+        }
+
+        for (pixel, on) in frame.iter_mut().zip(core.processor.get_display()) {
+            *pixel = if *on { PIXEL_ON } else { PIXEL_OFF };
+        }
+
+        let sound = core.processor.get_sound();
+        for frame in samples.chunks_mut(2) {
+            let sample = match (sound, core.phase <= 0.5) {
+                (false, _) => 0,
+                (true, true) => VOLUME,
+                (true, false) => -VOLUME,
+            };
+            frame[0] = sample;
+            frame[1] = sample;
+            core.phase = (core.phase + TONE / SAMPLE_RATE as f32) % 1.0;
+        }
+    }
+
+    if let Some(video_refresh) = video_refresh {
+        video_refresh(
+            frame.as_ptr() as *const c_void,
+            SCREEN_WIDTH as u32,
+            SCREEN_HEIGHT as u32,
+            SCREEN_WIDTH * 4,
+        );
+    }
+    if let Some(audio_sample_batch) = audio_sample_batch {
+        audio_sample_batch(samples.as_ptr(), SAMPLES_PER_FRAME);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    STATE_SIZE
+}
+
+/// # Safety
+/// `data` must point to `size` writable bytes
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    let frontend = frontend();
+    match frontend.core.as_ref() {
+        Some(core) if !data.is_null() && size >= STATE_SIZE => {
+            let out = slice::from_raw_parts_mut(data as *mut u8, STATE_SIZE);
+            out.copy_from_slice(&core.processor.save_state());
+            true
+        }
+        _ => false,
+    }
+}
+
+/// # Safety
+/// `data` must point to `size` readable bytes
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    let mut frontend = frontend();
+    match frontend.core.as_mut() {
+        Some(core) if !data.is_null() => {
+            let state = slice::from_raw_parts(data as *const u8, size);
+            let loaded = core.processor.load_state(state).is_ok();
+            if loaded {
+                core.crashed = false;
+            }
+            loaded
+        }
+        _ => false,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_index: u32, _enabled: bool, _code: *const c_char) {}
+
+/// # Safety
+/// `game` must be null or point to a valid retro_game_info whose data
+/// holds `size` bytes
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const RetroGameInfo) -> bool {
+    if game.is_null() || (*game).data.is_null() {
+        return false;
+    }
+    let rom = slice::from_raw_parts((*game).data as *const u8, (*game).size).to_vec();
+
+    // not under the lock, the frontend can call back into the core
+    let environment = frontend().environment;
+    if let Some(environment) = environment {
+        let mut format = RETRO_PIXEL_FORMAT_XRGB8888;
+        if !environment(
+            RETRO_ENVIRONMENT_SET_PIXEL_FORMAT,
+            &mut format as *mut u32 as *mut c_void,
+        ) {
+            return false;
+        }
+    }
+
+    let mut processor = Processor::new();
+    if processor.try_load(&rom).is_err() {
+        return false;
+    }
+    frontend().core = Some(Box::new(Core {
+        processor,
+        rom,
+        crashed: false,
+        phase: 0.0,
+    }));
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(
+    _game_type: u32,
+    _info: *const RetroGameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    frontend().core = None;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> u32 {
+    RETRO_REGION_NTSC
+}
+
+// RAM isn't exposed, the frontend can't write through it safely
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(_id: u32) -> *mut c_void {
+    std::ptr::null_mut()
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(_id: u32) -> usize {
+    0
+}
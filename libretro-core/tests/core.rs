@@ -0,0 +1,169 @@
+// Loads the built core the way a libretro frontend does and drives it
+// through its C entry points
+
+use std::env;
+use std::ffi::c_void;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use libloading::{Library, Symbol};
+
+#[repr(C)]
+struct RetroGameInfo {
+    path: *const i8,
+    data: *const c_void,
+    size: usize,
+    meta: *const i8,
+}
+
+type SerializeFn = unsafe extern "C" fn(*mut c_void, usize) -> bool;
+
+// LD V1, K, then draw the 0 from the font at the top left and spin
+const ROM: [u8; 6] = [0xF1, 0x0A, 0xD0, 0x05, 0x12, 0x04];
+const WIDTH: usize = 64;
+const UP: u32 = 4; // RetroPad up, CHIP-8 key 5
+
+static FRAME: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+static POLLS: AtomicUsize = AtomicUsize::new(0);
+static AUDIO_FRAMES: AtomicUsize = AtomicUsize::new(0);
+static HOLD_UP: AtomicBool = AtomicBool::new(false);
+// called from inside video_refresh, which used to deadlock
+static SERIALIZE: OnceLock<(SerializeFn, usize)> = OnceLock::new();
+static SERIALIZED_IN_CALLBACK: AtomicBool = AtomicBool::new(false);
+// and from inside environment while loading the game
+static SERIALIZE_FROM_ENVIRONMENT: OnceLock<SerializeFn> = OnceLock::new();
+static SERIALIZED_IN_ENVIRONMENT: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn environment(_cmd: u32, _data: *mut c_void) -> bool {
+    if let Some(serialize) = SERIALIZE_FROM_ENVIRONMENT.get() {
+        // no game yet, so nothing to save
+        let mut state = [0u8; 1];
+        let saved = unsafe { serialize(state.as_mut_ptr() as *mut c_void, state.len()) };
+        SERIALIZED_IN_ENVIRONMENT.store(!saved, Ordering::SeqCst);
+    }
+    true
+}
+
+extern "C" fn video_refresh(data: *const c_void, width: u32, height: u32, pitch: usize) {
+    assert_eq!((width as usize, pitch), (WIDTH, WIDTH * 4));
+    let pixels = unsafe { std::slice::from_raw_parts(data as *const u32, WIDTH * height as usize) };
+    *FRAME.lock().unwrap() = pixels.to_vec();
+
+    if let Some((serialize, size)) = SERIALIZE.get() {
+        let mut state = vec![0u8; *size];
+        let saved = unsafe { serialize(state.as_mut_ptr() as *mut c_void, state.len()) };
+        SERIALIZED_IN_CALLBACK.store(saved, Ordering::SeqCst);
+    }
+}
+
+extern "C" fn audio_sample_batch(_data: *const i16, frames: usize) -> usize {
+    AUDIO_FRAMES.fetch_add(frames, Ordering::SeqCst);
+    frames
+}
+
+extern "C" fn input_poll() {
+    POLLS.fetch_add(1, Ordering::SeqCst);
+}
+
+extern "C" fn input_state(port: u32, _device: u32, _index: u32, id: u32) -> i16 {
+    (port == 0 && id == UP && HOLD_UP.load(Ordering::SeqCst)) as i16
+}
+
+// Next to the test binary in target/<profile>/deps
+fn core_path() -> PathBuf {
+    let exe = env::current_exe().unwrap();
+    exe.parent()
+        .unwrap()
+        .join(libloading::library_filename("chip8_libretro"))
+}
+
+fn lit_pixels() -> Vec<usize> {
+    let frame = FRAME.lock().unwrap();
+    let lit = frame.iter().enumerate().filter(|(_, pixel)| **pixel != 0);
+    lit.map(|(i, _)| i).collect()
+}
+
+#[test]
+fn loads_runs_and_saves() {
+    unsafe {
+        let core = Library::new(core_path()).expect("Unable to load core");
+
+        let api_version: Symbol<extern "C" fn() -> u32> = core.get(b"retro_api_version").unwrap();
+        let set_environment: Symbol<extern "C" fn(extern "C" fn(u32, *mut c_void) -> bool)> =
+            core.get(b"retro_set_environment").unwrap();
+        let set_video_refresh: Symbol<
+            extern "C" fn(extern "C" fn(*const c_void, u32, u32, usize)),
+        > = core.get(b"retro_set_video_refresh").unwrap();
+        let set_audio_sample_batch: Symbol<
+            extern "C" fn(extern "C" fn(*const i16, usize) -> usize),
+        > = core.get(b"retro_set_audio_sample_batch").unwrap();
+        let set_input_poll: Symbol<extern "C" fn(extern "C" fn())> =
+            core.get(b"retro_set_input_poll").unwrap();
+        let set_input_state: Symbol<extern "C" fn(extern "C" fn(u32, u32, u32, u32) -> i16)> =
+            core.get(b"retro_set_input_state").unwrap();
+        let init: Symbol<extern "C" fn()> = core.get(b"retro_init").unwrap();
+        let load_game: Symbol<unsafe extern "C" fn(*const RetroGameInfo) -> bool> =
+            core.get(b"retro_load_game").unwrap();
+        let run: Symbol<extern "C" fn()> = core.get(b"retro_run").unwrap();
+        let serialize_size: Symbol<extern "C" fn() -> usize> =
+            core.get(b"retro_serialize_size").unwrap();
+        let serialize: Symbol<SerializeFn> = core.get(b"retro_serialize").unwrap();
+        let unserialize: Symbol<unsafe extern "C" fn(*const c_void, usize) -> bool> =
+            core.get(b"retro_unserialize").unwrap();
+        let unload_game: Symbol<extern "C" fn()> = core.get(b"retro_unload_game").unwrap();
+        let deinit: Symbol<extern "C" fn()> = core.get(b"retro_deinit").unwrap();
+
+        assert_eq!(api_version(), 1);
+        set_environment(environment);
+        set_video_refresh(video_refresh);
+        set_audio_sample_batch(audio_sample_batch);
+        set_input_poll(input_poll);
+        set_input_state(input_state);
+        init();
+
+        let mut state = vec![0u8; serialize_size()];
+        // nothing to save before a game is loaded
+        assert!(!serialize(state.as_mut_ptr() as *mut c_void, state.len()));
+        assert!(!load_game(std::ptr::null()));
+        let game = RetroGameInfo {
+            path: std::ptr::null(),
+            data: ROM.as_ptr() as *const c_void,
+            size: ROM.len(),
+            meta: std::ptr::null(),
+        };
+        let _ = SERIALIZE_FROM_ENVIRONMENT.set(*serialize);
+        assert!(load_game(&game));
+        assert!(SERIALIZED_IN_ENVIRONMENT.load(Ordering::SeqCst));
+
+        // waiting for a key
+        run();
+        assert_eq!(POLLS.load(Ordering::SeqCst), 1);
+        assert_eq!(AUDIO_FRAMES.load(Ordering::SeqCst), 735);
+        assert!(lit_pixels().is_empty());
+        assert!(serialize(state.as_mut_ptr() as *mut c_void, state.len()));
+        assert!(!serialize(
+            state.as_mut_ptr() as *mut c_void,
+            state.len() - 1
+        ));
+
+        HOLD_UP.store(true, Ordering::SeqCst);
+        let _ = SERIALIZE.set((*serialize, state.len()));
+        run();
+        assert!(SERIALIZED_IN_CALLBACK.load(Ordering::SeqCst));
+        let zero = [0, 1, 2, 3, 64, 67, 128, 131, 192, 195, 256, 257, 258, 259];
+        assert_eq!(lit_pixels(), zero);
+
+        // back to before the key press
+        HOLD_UP.store(false, Ordering::SeqCst);
+        assert!(unserialize(state.as_ptr() as *const c_void, state.len()));
+        run();
+        assert!(lit_pixels().is_empty());
+        assert!(!unserialize(state.as_ptr() as *const c_void, 3));
+
+        unload_game();
+        run();
+        assert_eq!(POLLS.load(Ordering::SeqCst), 3);
+        deinit();
+    }
+}
@@ -0,0 +1,229 @@
+// Python bindings for the backend's Processor
+//
+// The display comes back as `bytes` with one byte per pixel (0 or 1),
+// row major, so `numpy.frombuffer(p.display(), dtype=numpy.uint8)
+// .reshape(SCREEN_HEIGHT, SCREEN_WIDTH)` gives an image without copying
+// twice.
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyIndexError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use backend::display_constants::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use backend::input_constants::NUM_KEYS;
+use backend::memory_constants::RAM_SIZE;
+
+const TICKS_PER_FRAME: usize = 10;
+
+create_exception!(chip8, ExecutionError, PyException);
+
+// Unsendable because tracers aren't Sync, which only matters if a
+// processor is handed between Python threads
+#[pyclass(module = "chip8", unsendable)]
+struct Processor {
+    inner: backend::Processor,
+}
+
+#[pymethods]
+impl Processor {
+    // Without a seed, CXNN is seeded randomly
+    #[new]
+    #[pyo3(signature = (seed=None))]
+    fn new(seed: Option<u64>) -> Self {
+        let inner = match seed {
+            Some(seed) => backend::Processor::with_seed(seed),
+            None => backend::Processor::new(),
+        };
+        Self { inner }
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.inner.seed(seed);
+    }
+
+    // Copies to the start address, or to `address` without moving the PC,
+    // e.g. for data a program expects at a fixed address
+    #[pyo3(signature = (rom, address=None))]
+    fn load(&mut self, rom: &[u8], address: Option<u16>) -> PyResult<()> {
+        let result = match address {
+            Some(address) => self.inner.load_at(address, rom),
+            None => self.inner.try_load(rom),
+        };
+        result.map_err(|error| PyValueError::new_err(error.to_string()))
+    }
+
+    // Where load puts ROMs and the PC starts, e.g. 0x600 for ETI 660
+    // programs. Setting it also moves the PC
+    #[getter]
+    fn start_address(&self) -> u16 {
+        self.inner.start_address()
+    }
+
+    #[setter]
+    fn set_start_address(&mut self, address: u16) {
+        self.inner.set_start_address(address);
+    }
+
+    // Executes one instruction, raising ExecutionError if the ROM does
+    // something invalid
+    fn tick(&mut self) -> PyResult<()> {
+        self.inner
+            .try_tick()
+            .map_err(|error| ExecutionError::new_err(error.to_string()))
+    }
+
+    fn tick_timers(&mut self) {
+        self.inner.tick_timers();
+    }
+
+    // Executes `ticks` instructions and then counts the timers down once,
+    // like the desktop frontend does every frame
+    #[pyo3(signature = (ticks=TICKS_PER_FRAME))]
+    fn run_frame(&mut self, ticks: usize) -> PyResult<()> {
+        for _ in 0..ticks {
+            self.tick()?;
+        }
+        self.inner.tick_timers();
+        Ok(())
+    }
+
+    fn keypress(&mut self, index: usize, pressed: bool) -> PyResult<()> {
+        if index >= NUM_KEYS {
+            return Err(PyIndexError::new_err("key index out of range"));
+        }
+        self.inner.keypress(index, pressed);
+        Ok(())
+    }
+
+    fn display<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        let pixels: Vec<u8> = self
+            .inner
+            .get_display()
+            .iter()
+            .map(|pixel| *pixel as u8)
+            .collect();
+        PyBytes::new(py, &pixels)
+    }
+
+    #[getter]
+    fn sound(&self) -> bool {
+        self.inner.get_sound()
+    }
+
+    #[getter]
+    fn pc(&self) -> u16 {
+        self.inner.registers().pc
+    }
+
+    #[setter]
+    fn set_pc(&mut self, value: u16) {
+        let mut registers = self.inner.registers();
+        registers.pc = value;
+        self.inner.set_registers(registers);
+    }
+
+    #[getter]
+    fn i(&self) -> u16 {
+        self.inner.registers().i
+    }
+
+    #[setter]
+    fn set_i(&mut self, value: u16) {
+        let mut registers = self.inner.registers();
+        registers.i = value;
+        self.inner.set_registers(registers);
+    }
+
+    #[getter]
+    fn sp(&self) -> u16 {
+        self.inner.registers().sp
+    }
+
+    #[getter]
+    fn dt(&self) -> u8 {
+        self.inner.registers().dt
+    }
+
+    #[setter]
+    fn set_dt(&mut self, value: u8) {
+        let mut registers = self.inner.registers();
+        registers.dt = value;
+        self.inner.set_registers(registers);
+    }
+
+    #[getter]
+    fn st(&self) -> u8 {
+        self.inner.registers().st
+    }
+
+    #[setter]
+    fn set_st(&mut self, value: u8) {
+        let mut registers = self.inner.registers();
+        registers.st = value;
+        self.inner.set_registers(registers);
+    }
+
+    // Copy of V0-VF, use set_v to change one
+    #[getter]
+    fn v(&self) -> Vec<u8> {
+        self.inner.registers().v.to_vec()
+    }
+
+    fn set_v(&mut self, index: usize, value: u8) -> PyResult<()> {
+        let mut registers = self.inner.registers();
+        let register = registers
+            .v
+            .get_mut(index)
+            .ok_or_else(|| PyIndexError::new_err("register index out of range"))?;
+        *register = value;
+        self.inner.set_registers(registers);
+        Ok(())
+    }
+
+    // Copy of the whole RAM
+    #[getter]
+    fn ram<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.inner.ram())
+    }
+
+    fn read_ram(&self, address: usize) -> PyResult<u8> {
+        self.inner
+            .ram()
+            .get(address)
+            .copied()
+            .ok_or_else(|| PyIndexError::new_err("address out of range"))
+    }
+
+    fn write_ram(&mut self, address: usize, value: u8) -> PyResult<()> {
+        if address >= RAM_SIZE {
+            return Err(PyIndexError::new_err("address out of range"));
+        }
+        self.inner.write_ram(address, value);
+        Ok(())
+    }
+
+    fn save_state<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.inner.save_state())
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> PyResult<()> {
+        self.inner
+            .load_state(state)
+            .map_err(|error| PyValueError::new_err(error.to_string()))
+    }
+}
+
+#[pymodule]
+fn chip8(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Processor>()?;
+    m.add("ExecutionError", m.py().get_type::<ExecutionError>())?;
+    m.add("SCREEN_WIDTH", SCREEN_WIDTH)?;
+    m.add("SCREEN_HEIGHT", SCREEN_HEIGHT)?;
+    m.add("RAM_SIZE", RAM_SIZE)?;
+    Ok(())
+}
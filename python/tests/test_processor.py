@@ -0,0 +1,129 @@
+import pytest
+
+import chip8
+
+# draw the font sprite for 0 at (0, 0), then loop forever
+DRAW_ZERO = bytes([0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06])
+
+
+def test_run_frame_draws_sprite():
+    p = chip8.Processor(seed=1)
+    p.load(DRAW_ZERO)
+    p.run_frame()
+
+    display = p.display()
+    assert len(display) == chip8.SCREEN_WIDTH * chip8.SCREEN_HEIGHT
+    # top row of the 0 glyph is 0xF0
+    assert list(display[:5]) == [1, 1, 1, 1, 0]
+    assert p.pc == 0x206
+
+
+def test_display_as_numpy_array():
+    numpy = pytest.importorskip("numpy")
+
+    p = chip8.Processor(seed=1)
+    p.load(DRAW_ZERO)
+    p.run_frame()
+
+    image = numpy.frombuffer(p.display(), dtype=numpy.uint8).reshape(
+        chip8.SCREEN_HEIGHT, chip8.SCREEN_WIDTH
+    )
+    assert image[:5, :4].tolist() == [
+        [1, 1, 1, 1],
+        [1, 0, 0, 1],
+        [1, 0, 0, 1],
+        [1, 0, 0, 1],
+        [1, 1, 1, 1],
+    ]
+
+
+def test_registers_and_ram():
+    p = chip8.Processor()
+    p.load(bytes([0x61, 0x2A]))
+    p.tick()
+
+    assert p.v[1] == 0x2A
+    p.set_v(2, 7)
+    assert p.v[2] == 7
+    p.i = 0x300
+    assert p.i == 0x300
+
+    p.write_ram(0x300, 0xAB)
+    assert p.read_ram(0x300) == 0xAB
+    assert p.ram[0x300] == 0xAB
+    assert len(p.ram) == chip8.RAM_SIZE
+
+    with pytest.raises(IndexError):
+        p.read_ram(chip8.RAM_SIZE)
+    with pytest.raises(IndexError):
+        p.set_v(16, 0)
+
+
+def test_keypress_unblocks_wait():
+    # FX0A waits for a key
+    p = chip8.Processor()
+    p.load(bytes([0xF3, 0x0A]))
+    p.tick()
+    assert p.pc == 0x200
+
+    p.keypress(0xB, True)
+    p.tick()
+    assert p.pc == 0x202
+    assert p.v[3] == 0xB
+
+    with pytest.raises(IndexError):
+        p.keypress(16, True)
+
+
+def test_save_and_load_state():
+    p = chip8.Processor(seed=1)
+    p.load(DRAW_ZERO)
+    p.run_frame()
+    state = p.save_state()
+
+    p.reset()
+    assert not any(p.display())
+    p.load_state(state)
+    assert p.display()[0] == 1
+
+    with pytest.raises(ValueError):
+        p.load_state(b"not a state")
+
+
+def test_invalid_rom_raises():
+    p = chip8.Processor()
+    p.load(bytes([0x00, 0x01]))
+    with pytest.raises(chip8.ExecutionError):
+        p.tick()
+
+    with pytest.raises(ValueError):
+        p.load(bytes(chip8.RAM_SIZE))
+
+
+def test_start_address():
+    # ETI 660 programs load and start at 0x600
+    p = chip8.Processor()
+    p.start_address = 0x600
+    assert p.pc == 0x600
+    p.load(bytes([0x61, 0x2A]))
+    p.load(bytes([0x42]), address=0xE00)
+    assert p.read_ram(0x600) == 0x61
+    assert p.read_ram(0xE00) == 0x42
+    p.tick()
+    assert p.v[1] == 0x2A
+
+    with pytest.raises(ValueError):
+        p.load(bytes(0xA01))
+
+
+def test_seeded_runs_match():
+    # CXNN with the same seed gives the same numbers
+    rom = bytes([0xC0, 0xFF, 0xC1, 0xFF, 0xC2, 0xFF])
+    a = chip8.Processor(seed=42)
+    b = chip8.Processor(seed=42)
+    a.load(rom)
+    b.load(rom)
+    for _ in range(3):
+        a.tick()
+        b.tick()
+    assert a.v == b.v
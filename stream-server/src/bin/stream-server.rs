@@ -0,0 +1,69 @@
+// Runs a ROM headlessly and streams it to WebSocket clients on localhost:
+//
+//     stream-server [--port 8080] path/to/game
+
+use std::env;
+use std::fs;
+use std::net::Ipv4Addr;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use backend::Processor;
+use stream_server::Server;
+
+const DEFAULT_PORT: u16 = 8080;
+const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+fn main() {
+    let mut port = DEFAULT_PORT;
+    let mut rom_path = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => {
+                port = args
+                    .next()
+                    .and_then(|port| port.parse().ok())
+                    .expect("Invalid port");
+            }
+            _ => rom_path = Some(arg),
+        }
+    }
+
+    let rom_path = match rom_path {
+        Some(rom_path) => rom_path,
+        None => {
+            println!("usage: stream-server [--port port] path/to/game");
+            process::exit(1);
+        }
+    };
+
+    let rom = fs::read(rom_path).expect("Unable to open file");
+    let mut processor = Processor::new();
+    if let Err(error) = processor.try_load(&rom) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+
+    let mut server =
+        Server::bind((Ipv4Addr::LOCALHOST, port), processor).expect("Unable to listen");
+    println!("Streaming on ws://{}", server.local_addr().unwrap());
+
+    let mut next_frame = Instant::now();
+    loop {
+        if let Some(error) = server.run_frame().expect("Unable to accept clients") {
+            eprintln!("{}", error);
+        }
+
+        next_frame += FRAME_TIME;
+        let now = Instant::now();
+        if next_frame > now {
+            thread::sleep(next_frame - now);
+        } else {
+            // fell behind, don't try to catch up
+            next_frame = now;
+        }
+    }
+}
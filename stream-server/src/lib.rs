@@ -0,0 +1,263 @@
+// Headless session streamed over WebSocket
+//
+// The first client to connect is the player and its key events are
+// applied, everyone else is a spectator. When the player leaves, the
+// longest connected spectator takes over. New clients get a full frame,
+// after that only the screen bytes that changed are sent.
+//
+// Nothing blocks: handshakes are picked up where they left off each
+// frame, so a slow or silent client can't stall the stream.
+
+pub mod protocol;
+
+use std::io::{self, ErrorKind as IoErrorKind};
+use std::mem;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use tungstenite::handshake::server::{NoCallback, ServerHandshake};
+use tungstenite::handshake::{HandshakeError, MidHandshake};
+use tungstenite::protocol::WebSocketConfig;
+use tungstenite::{Error, Message, WebSocket};
+
+use backend::input_constants::NUM_KEYS;
+use backend::{ExecutionError, Processor};
+
+use crate::protocol::{encode_delta, encode_full, encode_role, pack, PackedScreen};
+
+const TICKS_PER_FRAME: usize = 10;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(1);
+// clients that fall this far behind are dropped rather than buffered
+const MAX_WRITE_BUFFER: usize = 64 * 1024;
+
+type Handshake = ServerHandshake<TcpStream, NoCallback>;
+
+// A connection that hasn't finished its handshake yet
+struct Pending {
+    handshake: MidHandshake<Handshake>,
+    started: Instant,
+}
+
+struct Client {
+    socket: WebSocket<TcpStream>,
+    keys: [bool; NUM_KEYS], // released when the client stops being player
+}
+
+pub struct Server {
+    listener: TcpListener,
+    pending: Vec<Pending>,
+    clients: Vec<Client>, // the player is first
+    processor: Processor,
+    crashed: bool, // stop running after the ROM hits an execution error
+    screen: PackedScreen,
+    sound: bool,
+}
+
+impl Server {
+    pub fn bind<A: ToSocketAddrs>(address: A, processor: Processor) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        let screen = pack(processor.get_display());
+        let sound = processor.get_sound();
+        Ok(Self {
+            listener,
+            pending: Vec::new(),
+            clients: Vec::new(),
+            processor,
+            crashed: false,
+            screen,
+            sound,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn processor(&self) -> &Processor {
+        &self.processor
+    }
+
+    pub fn clients(&self) -> usize {
+        self.clients.len()
+    }
+
+    pub fn crashed(&self) -> bool {
+        self.crashed
+    }
+
+    // Accepts new clients, applies the player's keys, runs one frame and
+    // streams the result. Call at 60 Hz. Returns the execution error on
+    // the frame the ROM hits one, the screen stays up after that
+    pub fn run_frame(&mut self) -> io::Result<Option<ExecutionError>> {
+        self.accept()?;
+        self.receive();
+
+        let mut crash = None;
+        if !self.crashed {
+            if let Err(error) = self.processor.run(TICKS_PER_FRAME) {
+                crash = Some(error);
+                self.crashed = true;
+            }
+            self.processor.tick_timers();
+        }
+
+        let screen = pack(self.processor.get_display());
+        let sound = self.processor.get_sound();
+        if let Some(delta) = encode_delta(&self.screen, &screen, self.sound, sound) {
+            self.broadcast(delta);
+        }
+        self.screen = screen;
+        self.sound = sound;
+        self.flush();
+        Ok(crash)
+    }
+
+    // Clients that haven't finished the handshake in time are dropped
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    fn accept(&mut self) -> io::Result<()> {
+        for pending in mem::take(&mut self.pending) {
+            if pending.started.elapsed() < HANDSHAKE_TIMEOUT {
+                self.handshake(pending.handshake.handshake(), pending.started);
+            }
+        }
+
+        loop {
+            let stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(error) if error.kind() == IoErrorKind::WouldBlock => return Ok(()),
+                Err(error) => return Err(error),
+            };
+
+            if stream.set_nonblocking(true).is_ok() && stream.set_nodelay(true).is_ok() {
+                let config = WebSocketConfig {
+                    write_buffer_size: 0,
+                    max_write_buffer_size: MAX_WRITE_BUFFER,
+                    ..Default::default()
+                };
+                let result = tungstenite::accept_with_config(stream, Some(config));
+                self.handshake(result, Instant::now());
+            }
+        }
+    }
+
+    fn handshake(
+        &mut self,
+        result: Result<WebSocket<TcpStream>, HandshakeError<Handshake>>,
+        started: Instant,
+    ) {
+        match result {
+            Ok(mut socket) => {
+                let player = self.clients.is_empty();
+                let role = socket.send(Message::Binary(encode_role(player)));
+                let frame = socket.send(Message::Binary(encode_full(&self.screen, self.sound)));
+                if sent(&role) && sent(&frame) {
+                    self.clients.push(Client {
+                        socket,
+                        keys: [false; NUM_KEYS],
+                    });
+                }
+            }
+            // waiting on the client
+            Err(HandshakeError::Interrupted(handshake)) => {
+                self.pending.push(Pending { handshake, started });
+            }
+            // a client that fails the handshake is just ignored
+            Err(HandshakeError::Failure(_)) => {}
+        }
+    }
+
+    fn receive(&mut self) {
+        let mut index = 0;
+        while index < self.clients.len() {
+            let player = index == 0;
+            let client = &mut self.clients[index];
+            let mut connected = true;
+
+            loop {
+                match client.socket.read() {
+                    Ok(Message::Binary(data)) => {
+                        if let [key, pressed] = data[..] {
+                            if player && (key as usize) < NUM_KEYS {
+                                client.keys[key as usize] = pressed != 0;
+                                self.processor.keypress(key as usize, pressed != 0);
+                            }
+                        }
+                    }
+                    Ok(Message::Close(_)) => {
+                        connected = false;
+                        break;
+                    }
+                    // pings are answered by tungstenite, text is ignored
+                    Ok(_) => {}
+                    Err(Error::Io(ref error)) if error.kind() == IoErrorKind::WouldBlock => break,
+                    Err(_) => {
+                        connected = false;
+                        break;
+                    }
+                }
+            }
+
+            if connected {
+                index += 1;
+            } else {
+                self.disconnect(index);
+            }
+        }
+    }
+
+    fn broadcast(&mut self, message: Vec<u8>) {
+        let mut index = 0;
+        while index < self.clients.len() {
+            let result = self.clients[index]
+                .socket
+                .send(Message::Binary(message.clone()));
+            if sent(&result) {
+                index += 1;
+            } else {
+                self.disconnect(index);
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        let mut index = 0;
+        while index < self.clients.len() {
+            let result = self.clients[index].socket.flush();
+            if sent(&result) {
+                index += 1;
+            } else {
+                self.disconnect(index);
+            }
+        }
+    }
+
+    fn disconnect(&mut self, index: usize) {
+        let client = self.clients.remove(index);
+        if index != 0 {
+            return;
+        }
+
+        // don't leave the old player's keys held down
+        for (key, pressed) in client.keys.iter().enumerate() {
+            if *pressed {
+                self.processor.keypress(key, false);
+            }
+        }
+        if let Some(next) = self.clients.first_mut() {
+            let _ = next.socket.send(Message::Binary(encode_role(true)));
+        }
+    }
+}
+
+// Messages that didn't fit in the socket stay queued until the next flush
+fn sent(result: &Result<(), Error>) -> bool {
+    match result {
+        Ok(()) => true,
+        Err(Error::Io(error)) => error.kind() == IoErrorKind::WouldBlock,
+        Err(_) => false,
+    }
+}
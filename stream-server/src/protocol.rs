@@ -0,0 +1,118 @@
+// Messages exchanged over the WebSocket, all binary
+//
+// Server to client:
+//
+//     0x00 sound:u8 screen[SCREEN_BYTES]     full frame
+//     0x01 sound:u8 (index:u8 byte:u8)*      changed screen bytes
+//     0x02 player:u8                         role, 1 if keys are accepted
+//
+// Client to server:
+//
+//     key:u8 pressed:u8
+//
+// The screen is packed 8 pixels per byte, leftmost pixel in the high bit,
+// so a whole frame is only 256 bytes and its byte indices fit in a u8.
+
+use backend::display_constants::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+pub const SCREEN_BYTES: usize = SCREEN_WIDTH * SCREEN_HEIGHT / 8;
+
+pub const FULL_FRAME: u8 = 0x00;
+pub const DELTA_FRAME: u8 = 0x01;
+pub const ROLE: u8 = 0x02;
+
+pub type PackedScreen = [u8; SCREEN_BYTES];
+
+pub fn pack(display: &[bool]) -> PackedScreen {
+    let mut screen = [0; SCREEN_BYTES];
+    for (byte, pixels) in screen.iter_mut().zip(display.chunks(8)) {
+        *byte = pixels
+            .iter()
+            .fold(0, |byte, pixel| (byte << 1) | *pixel as u8);
+    }
+    screen
+}
+
+pub fn encode_full(screen: &PackedScreen, sound: bool) -> Vec<u8> {
+    let mut message = Vec::with_capacity(2 + SCREEN_BYTES);
+    message.push(FULL_FRAME);
+    message.push(sound as u8);
+    message.extend_from_slice(screen);
+    message
+}
+
+// Empty when nothing changed, so idle frames cost nothing
+pub fn encode_delta(
+    previous: &PackedScreen,
+    screen: &PackedScreen,
+    previous_sound: bool,
+    sound: bool,
+) -> Option<Vec<u8>> {
+    let mut message = vec![DELTA_FRAME, sound as u8];
+    for (index, (old, new)) in previous.iter().zip(screen).enumerate() {
+        if old != new {
+            message.push(index as u8);
+            message.push(*new);
+        }
+    }
+    if message.len() == 2 && previous_sound == sound {
+        return None;
+    }
+    Some(message)
+}
+
+pub fn encode_role(player: bool) -> Vec<u8> {
+    vec![ROLE, player as u8]
+}
+
+// Client side view of the stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Screen {
+    pub pixels: PackedScreen,
+    pub sound: bool,
+    pub player: bool,
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Screen {
+    pub fn new() -> Self {
+        Self {
+            pixels: [0; SCREEN_BYTES],
+            sound: false,
+            player: false,
+        }
+    }
+
+    // Returns false for malformed messages, leaving the screen as it was
+    pub fn apply(&mut self, message: &[u8]) -> bool {
+        match message {
+            [FULL_FRAME, sound, pixels @ ..] if pixels.len() == SCREEN_BYTES => {
+                self.pixels.copy_from_slice(pixels);
+                self.sound = *sound != 0;
+            }
+            [DELTA_FRAME, sound, changes @ ..] if changes.len() % 2 == 0 => {
+                for change in changes.chunks(2) {
+                    self.pixels[change[0] as usize] = change[1];
+                }
+                self.sound = *sound != 0;
+            }
+            [ROLE, player] => self.player = *player != 0,
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        let index = x + SCREEN_WIDTH * y;
+        self.pixels[index / 8] & (0b1000_0000 >> (index % 8)) != 0
+    }
+}
+
+pub fn encode_key(key: u8, pressed: bool) -> [u8; 2] {
+    [key, pressed as u8]
+}
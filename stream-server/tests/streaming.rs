@@ -0,0 +1,201 @@
+// Runs a server on an ephemeral localhost port with in-process clients
+
+use std::io::Write;
+use std::net::{Ipv4Addr, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tungstenite::{Message, WebSocket};
+
+use backend::{ErrorKind, Processor};
+use stream_server::protocol::{encode_key, Screen};
+use stream_server::Server;
+
+// waits for a key, then draws its font sprite at (0, 0) and loops
+const DRAW_KEY: [u8; 10] = [0xF0, 0x0A, 0xF0, 0x29, 0x61, 0x00, 0xD1, 0x15, 0x12, 0x08];
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Session {
+    address: String,
+    stop: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Session {
+    fn start(rom: &[u8]) -> Self {
+        let mut processor = Processor::with_seed(0);
+        processor.load(rom);
+        let mut server = Server::bind((Ipv4Addr::LOCALHOST, 0), processor).unwrap();
+        let address = format!("ws://{}", server.local_addr().unwrap());
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let running = stop.clone();
+        let thread = thread::spawn(move || {
+            while !running.load(Ordering::Relaxed) {
+                server.run_frame().unwrap();
+                thread::sleep(Duration::from_millis(1));
+            }
+        });
+
+        Self {
+            address,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    fn connect(&self) -> Client {
+        let host = self.address.trim_start_matches("ws://");
+        let stream = TcpStream::connect(host).unwrap();
+        stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+        let (socket, _) = tungstenite::client(self.address.as_str(), stream).unwrap();
+        Client {
+            socket,
+            screen: Screen::new(),
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.join().unwrap();
+        }
+    }
+}
+
+struct Client {
+    socket: WebSocket<TcpStream>,
+    screen: Screen,
+}
+
+impl Client {
+    fn wait_for<F: Fn(&Screen) -> bool>(&mut self, condition: F) {
+        let start = Instant::now();
+        while !condition(&self.screen) {
+            assert!(start.elapsed() < TIMEOUT, "timed out");
+            match self.socket.read().unwrap() {
+                Message::Binary(data) => assert!(self.screen.apply(&data)),
+                message => panic!("unexpected message {:?}", message),
+            }
+        }
+    }
+
+    fn press(&mut self, key: u8) {
+        self.socket
+            .send(Message::Binary(encode_key(key, true).to_vec()))
+            .unwrap();
+    }
+}
+
+// top row of every font sprite is 4 pixels, 0xF0 for the digits used here
+fn top_row(screen: &Screen) -> [bool; 4] {
+    [
+        screen.pixel(0, 0),
+        screen.pixel(1, 0),
+        screen.pixel(2, 0),
+        screen.pixel(3, 0),
+    ]
+}
+
+#[test]
+fn spectators_see_the_players_game() {
+    let session = Session::start(&DRAW_KEY);
+    let mut player = session.connect();
+    player.wait_for(|screen| screen.player);
+    let mut spectator = session.connect();
+    spectator.wait_for(|screen| !screen.player);
+
+    // keys from spectators are ignored
+    spectator.press(0x8);
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(top_row(&spectator.screen), [false; 4]);
+
+    player.press(0x8);
+    player.wait_for(|screen| top_row(screen) == [true; 4]);
+    spectator.wait_for(|screen| top_row(screen) == [true; 4]);
+    assert_eq!(player.screen.pixels, spectator.screen.pixels);
+
+    // late joiners get the whole screen at once
+    let mut late = session.connect();
+    late.wait_for(|screen| top_row(screen) == [true; 4]);
+    assert_eq!(late.screen.pixels, player.screen.pixels);
+}
+
+#[test]
+fn spectator_takes_over_when_player_leaves() {
+    let session = Session::start(&DRAW_KEY);
+    let mut player = session.connect();
+    player.wait_for(|screen| screen.player);
+    let mut spectator = session.connect();
+    spectator.wait_for(|screen| !screen.player);
+
+    player.socket.close(None).unwrap();
+    let _ = player.socket.flush();
+    spectator.wait_for(|screen| screen.player);
+
+    spectator.press(0x0);
+    spectator.wait_for(|screen| top_row(screen) == [true; 4]);
+}
+
+fn bind(rom: &[u8]) -> Server {
+    let mut processor = Processor::with_seed(0);
+    processor.load(rom);
+    Server::bind((Ipv4Addr::LOCALHOST, 0), processor).unwrap()
+}
+
+// Runs frames until the condition holds
+fn run_until<F: Fn(&Server) -> bool>(server: &mut Server, condition: F) {
+    let start = Instant::now();
+    while !condition(server) {
+        assert!(start.elapsed() < TIMEOUT, "timed out");
+        server.run_frame().unwrap();
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn silent_clients_dont_hold_up_frames() {
+    let mut server = bind(&DRAW_KEY);
+    let address = server.local_addr().unwrap();
+    let silent = TcpStream::connect(address).unwrap();
+    let start = Instant::now();
+    run_until(&mut server, |server| server.pending() == 1);
+    for _ in 0..10 {
+        server.run_frame().unwrap();
+    }
+    assert!(start.elapsed() < Duration::from_millis(500));
+    assert_eq!(server.clients(), 0);
+
+    // a handshake sent in pieces still goes through
+    let mut slow = TcpStream::connect(address).unwrap();
+    let request = format!(
+        "GET / HTTP/1.1\r\nHost: {}\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n\
+         Sec-WebSocket-Version: 13\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+        address
+    );
+    let (first, rest) = request.split_at(20);
+    slow.write_all(first.as_bytes()).unwrap();
+    run_until(&mut server, |server| server.pending() == 2);
+    slow.write_all(rest.as_bytes()).unwrap();
+    run_until(&mut server, |server| server.clients() == 1);
+
+    // the silent one gives up after a second
+    run_until(&mut server, |server| server.pending() == 0);
+    assert!(start.elapsed() >= Duration::from_secs(1));
+    drop(silent);
+}
+
+#[test]
+fn execution_errors_are_returned_once() {
+    // RET with nothing to return to
+    let mut server = bind(&[0x00, 0xEE]);
+    let error = server.run_frame().unwrap().unwrap();
+    assert_eq!(error.kind, ErrorKind::StackUnderflow);
+    assert!(server.crashed());
+    assert!(server.run_frame().unwrap().is_none());
+}